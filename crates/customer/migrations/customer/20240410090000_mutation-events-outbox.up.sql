@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS mutation_events_outbox
+(
+    seq            BIGSERIAL PRIMARY KEY,
+    id             uuid NOT NULL CONSTRAINT mutation_events_outbox_id_unique UNIQUE,
+    event_ns       VARCHAR(255) NOT NULL,
+    event          VARCHAR(255) NOT NULL,
+    ty             VARCHAR(255) NOT NULL,
+    object         TEXT NOT NULL,
+    attempts       INT NOT NULL DEFAULT 0,
+    last_error     TEXT,
+    created_at     TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,13 @@
+-- Add up migration script here
+ALTER TABLE customers
+    ADD COLUMN IF NOT EXISTS deleted_by uuid,
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+ALTER TABLE organizations
+    ADD COLUMN IF NOT EXISTS deleted_by uuid,
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+ALTER TABLE institutions
+    ADD COLUMN IF NOT EXISTS deleted_by uuid,
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+ALTER TABLE organization_units
+    ADD COLUMN IF NOT EXISTS deleted_by uuid,
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
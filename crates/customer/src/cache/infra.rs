@@ -326,21 +326,32 @@ impl InfraDB {
         std::process::exit(1);
     }
 
+    async fn new_customer_from_update(&self, new: CustomerUpdate) {
+        if let Some(created_at) = parse_date_time(&new.created_at) {
+            let customer = Arc::new(Customer {
+                id: new.id,
+                name: new.name,
+                ty: new.ty,
+                created_at,
+                created_by: new.created_by,
+                updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
+                updated_by: new.updated_by,
+            });
+            self.new_customer(customer).await;
+        }
+    }
+
     async fn customers_update(&self, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<CustomerUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
             (Op::Insert, Some(new), None) => {
-                if let Some(created_at) = parse_date_time(&new.created_at) {
-                    let customer = Arc::new(Customer {
-                        id: new.id,
-                        name: new.name,
-                        ty: new.ty,
-                        created_at,
-                        created_by: new.created_by,
-                        updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
-                        updated_by: new.updated_by,
-                    });
-                    self.new_customer(customer).await;
+                self.new_customer_from_update(new).await;
+            }
+            (Op::Update, Some(new), Some(old)) => {
+                if new.deleted_at.is_some() {
+                    self.remove_customer(old).await;
+                } else if old.deleted_at.is_some() {
+                    self.new_customer_from_update(new).await;
                 }
             }
             (Op::Delete, None, Some(old)) => {
@@ -351,22 +362,33 @@ impl InfraDB {
         Ok(())
     }
 
+    async fn new_organization_from_update(&self, new: OrganizationUpdate) {
+        if let Some(created_at) = parse_date_time(&new.created_at) {
+            let organization = Arc::new(Organization {
+                id: new.id,
+                customer_id: new.customer_id,
+                name: new.name,
+                ty: new.ty,
+                created_at,
+                created_by: new.created_by,
+                updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
+                updated_by: new.updated_by,
+            });
+            self.new_organization(organization).await;
+        }
+    }
+
     async fn organizations_update(&self, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<OrganizationUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
             (Op::Insert, Some(new), None) => {
-                if let Some(created_at) = parse_date_time(&new.created_at) {
-                    let organization = Arc::new(Organization {
-                        id: new.id,
-                        customer_id: new.customer_id,
-                        name: new.name,
-                        ty: new.ty,
-                        created_at,
-                        created_by: new.created_by,
-                        updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
-                        updated_by: new.updated_by,
-                    });
-                    self.new_organization(organization).await;
+                self.new_organization_from_update(new).await;
+            }
+            (Op::Update, Some(new), Some(old)) => {
+                if new.deleted_at.is_some() {
+                    self.remove_organization(old).await;
+                } else if old.deleted_at.is_some() {
+                    self.new_organization_from_update(new).await;
                 }
             }
             (Op::Delete, None, Some(old)) => {
@@ -377,24 +399,35 @@ impl InfraDB {
         Ok(())
     }
 
+    async fn new_organization_unit_from_update(&self, new: OrganizationUnitUpdate) {
+        if let Some(created_at) = parse_date_time(&new.created_at) {
+            let organization_unit = Arc::new(OrganizationUnit {
+                id: new.id,
+                customer_id: new.customer_id,
+                organization_id: new.organization_id,
+                name: new.name,
+                ty: new.ty,
+                created_at,
+                created_by: new.created_by,
+                updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
+                updated_by: new.updated_by,
+                members: Arc::from(vec![]),
+            });
+            self.new_organization_unit(organization_unit).await;
+        }
+    }
+
     async fn organization_units_update(&self, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<OrganizationUnitUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
             (Op::Insert, Some(new), None) => {
-                if let Some(created_at) = parse_date_time(&new.created_at) {
-                    let organization_unit = Arc::new(OrganizationUnit {
-                        id: new.id,
-                        customer_id: new.customer_id,
-                        organization_id: new.organization_id,
-                        name: new.name,
-                        ty: new.ty,
-                        created_at,
-                        created_by: new.created_by,
-                        updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
-                        updated_by: new.updated_by,
-                        members: Arc::from(vec![]),
-                    });
-                    self.new_organization_unit(organization_unit).await;
+                self.new_organization_unit_from_update(new).await;
+            }
+            (Op::Update, Some(new), Some(old)) => {
+                if new.deleted_at.is_some() {
+                    self.remove_organization_unit(old).await;
+                } else if old.deleted_at.is_some() {
+                    self.new_organization_unit_from_update(new).await;
                 }
             }
             (Op::Delete, None, Some(old)) => {
@@ -405,23 +438,34 @@ impl InfraDB {
         Ok(())
     }
 
+    async fn new_institution_from_update(&self, new: InstitutionUpdate) {
+        if let Some(created_at) = parse_date_time(&new.created_at) {
+            let organization = Arc::new(Institution {
+                id: new.id,
+                customer_id: new.customer_id,
+                organization_id: new.organization_id,
+                name: new.name,
+                ty: new.ty,
+                created_at,
+                created_by: new.created_by,
+                updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
+                updated_by: new.updated_by,
+            });
+            self.new_institution(organization).await;
+        }
+    }
+
     async fn institutions_update(&self, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<InstitutionUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
             (Op::Insert, Some(new), None) => {
-                if let Some(created_at) = parse_date_time(&new.created_at) {
-                    let organization = Arc::new(Institution {
-                        id: new.id,
-                        customer_id: new.customer_id,
-                        organization_id: new.organization_id,
-                        name: new.name,
-                        ty: new.ty,
-                        created_at,
-                        created_by: new.created_by,
-                        updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
-                        updated_by: new.updated_by,
-                    });
-                    self.new_institution(organization).await;
+                self.new_institution_from_update(new).await;
+            }
+            (Op::Update, Some(new), Some(old)) => {
+                if new.deleted_at.is_some() {
+                    self.remove_institution(old).await;
+                } else if old.deleted_at.is_some() {
+                    self.new_institution_from_update(new).await;
                 }
             }
             (Op::Delete, None, Some(old)) => {
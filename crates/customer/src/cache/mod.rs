@@ -1,8 +1,10 @@
 use prometheus_client::metrics::gauge::Gauge;
 
+use qm_entity::connection::{paginate, KeyConnection};
+use qm_entity::error::EntityResult;
 use qm_entity::ids::PartialEqual;
 use qm_entity::ids::{CustomerId, CustomerOrOrganization, InfraContext, InfraId};
-use qm_entity::model::ListFilter;
+use qm_entity::model::{ConnectionFilter, ListFilter};
 
 use std::str::FromStr;
 use std::sync::atomic::AtomicI64;
@@ -15,8 +17,27 @@ pub mod user;
 
 use crate::cache::infra::InfraDB;
 use crate::cache::user::UserDB;
+use crate::model::filter::{
+    sort_infra_items, sort_users, InfraQuery, InfraSort, UserQuery, UserSort,
+};
 use crate::model::*;
 
+/// Returns the page selected by `filter` of the items together with `(limit, total, page)`.
+fn list_page<T>(
+    items: Vec<T>,
+    filter: Option<ListFilter>,
+) -> (Arc<[T]>, Option<i64>, Option<i64>, Option<i64>) {
+    let total = Some(items.len() as i64);
+    if let Some(filter) = filter {
+        let page = filter.page.unwrap_or(0);
+        let limit = filter.limit.unwrap_or(100);
+        let items = items.into_iter().skip(page * limit).take(limit).collect();
+        (items, Some(limit as i64), total, Some(page as i64))
+    } else {
+        (Arc::from(items), None, total, Some(0))
+    }
+}
+
 struct Inner {
     infra: InfraDB,
     user: UserDB,
@@ -68,32 +89,24 @@ impl CacheDB {
         &self,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> CustomerList {
         let customers = self.inner.infra.customers.read().await;
-        let iter = if let Some(ty) = ty.as_ref() {
-            itertools::Either::Right(customers.values().filter(|c| c.ty.as_ref() == ty.as_str()))
-        } else {
-            itertools::Either::Left(customers.values())
-        };
-        if let Some(filter) = filter {
-            let page = filter.page.unwrap_or(0);
-            let limit = filter.limit.unwrap_or(100);
-            let offset = page * limit;
-            let items: Arc<[Arc<Customer>]> = iter.skip(offset).take(limit).cloned().collect();
-            CustomerList {
-                items,
-                limit: Some(limit as i64),
-                total: Some(self.inner.infra.customers_total.get()),
-                page: Some(page as i64),
-            }
-        } else {
-            let items: Arc<[Arc<Customer>]> = iter.cloned().collect();
-            CustomerList {
-                items,
-                limit: None,
-                total: Some(self.inner.infra.customers_total.get()),
-                page: Some(0),
-            }
+        let query = query.unwrap_or_default();
+        let mut items: Vec<Arc<Customer>> = customers
+            .values()
+            .filter(|c| ty.as_ref().map_or(true, |ty| c.ty.as_ref() == ty.as_str()))
+            .filter(|c| query.matches(c.as_ref()))
+            .cloned()
+            .collect();
+        sort_infra_items(&mut items, sort);
+        let (items, limit, total, page) = list_page(items, filter);
+        CustomerList {
+            items,
+            limit,
+            total,
+            page,
         }
     }
 
@@ -102,41 +115,29 @@ impl CacheDB {
         customer_id: Option<CustomerId>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> OrganizationList {
         let organizations = self.inner.infra.organizations.read().await;
-        let iter = if let Some(ty) = ty.as_ref() {
-            itertools::Either::Right(
-                organizations
-                    .values()
-                    .filter(|c| c.ty.as_ref() == ty.as_str()),
-            )
-        } else {
-            itertools::Either::Left(organizations.values())
-        };
-        let iter = if let Some(customer_id) = customer_id.as_ref() {
-            itertools::Either::Right(iter.filter(|v| v.as_ref().partial_equal(customer_id)))
-        } else {
-            itertools::Either::Left(iter)
-        };
-        if let Some(filter) = filter {
-            let page = filter.page.unwrap_or(0);
-            let limit = filter.limit.unwrap_or(100);
-            let offset = page * limit;
-            let items: Arc<[Arc<Organization>]> = iter.skip(offset).take(limit).cloned().collect();
-            OrganizationList {
-                items,
-                limit: Some(limit as i64),
-                total: Some(self.inner.infra.organizations_total.get()),
-                page: Some(page as i64),
-            }
-        } else {
-            let items: Arc<[Arc<Organization>]> = iter.cloned().collect();
-            OrganizationList {
-                items,
-                limit: None,
-                total: Some(self.inner.infra.organizations_total.get()),
-                page: Some(0),
-            }
+        let query = query.unwrap_or_default();
+        let mut items: Vec<Arc<Organization>> = organizations
+            .values()
+            .filter(|c| ty.as_ref().map_or(true, |ty| c.ty.as_ref() == ty.as_str()))
+            .filter(|v| {
+                customer_id
+                    .as_ref()
+                    .map_or(true, |customer_id| v.as_ref().partial_equal(customer_id))
+            })
+            .filter(|c| query.matches(c.as_ref()))
+            .cloned()
+            .collect();
+        sort_infra_items(&mut items, sort);
+        let (items, limit, total, page) = list_page(items, filter);
+        OrganizationList {
+            items,
+            limit,
+            total,
+            page,
         }
     }
 
@@ -145,49 +146,33 @@ impl CacheDB {
         customer_or_organization: Option<CustomerOrOrganization>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> OrganizationUnitList {
         let organization_units = self.inner.infra.organization_units.read().await;
-        let iter = if let Some(ty) = ty.as_ref() {
-            itertools::Either::Right(
-                organization_units
-                    .values()
-                    .filter(|c| c.ty.as_ref() == ty.as_str()),
-            )
-        } else {
-            itertools::Either::Left(organization_units.values())
-        };
-
-        let iter = match &customer_or_organization {
-            Some(CustomerOrOrganization::Customer(customer_id)) => itertools::Either::Left(
-                itertools::Either::Left(iter.filter(|v| v.as_ref().partial_equal(customer_id))),
-            ),
-            Some(CustomerOrOrganization::Organization(organization_id)) => {
-                itertools::Either::Left(itertools::Either::Right(
-                    iter.filter(|v| v.as_ref().partial_equal(organization_id)),
-                ))
-            }
-            _ => itertools::Either::Right(iter),
-        };
-        if let Some(filter) = filter {
-            let page = filter.page.unwrap_or(0);
-            let limit = filter.limit.unwrap_or(100);
-            let offset = page * limit;
-            let items: Arc<[Arc<OrganizationUnit>]> =
-                iter.skip(offset).take(limit).cloned().collect();
-            OrganizationUnitList {
-                items,
-                limit: Some(limit as i64),
-                total: Some(self.inner.infra.organization_units_total.get()),
-                page: Some(page as i64),
-            }
-        } else {
-            let items: Arc<[Arc<OrganizationUnit>]> = iter.cloned().collect();
-            OrganizationUnitList {
-                items,
-                limit: None,
-                total: Some(self.inner.infra.organization_units_total.get()),
-                page: Some(0),
-            }
+        let query = query.unwrap_or_default();
+        let mut items: Vec<Arc<OrganizationUnit>> = organization_units
+            .values()
+            .filter(|c| ty.as_ref().map_or(true, |ty| c.ty.as_ref() == ty.as_str()))
+            .filter(|v| match &customer_or_organization {
+                Some(CustomerOrOrganization::Customer(customer_id)) => {
+                    v.as_ref().partial_equal(customer_id)
+                }
+                Some(CustomerOrOrganization::Organization(organization_id)) => {
+                    v.as_ref().partial_equal(organization_id)
+                }
+                _ => true,
+            })
+            .filter(|c| query.matches(c.as_ref()))
+            .cloned()
+            .collect();
+        sort_infra_items(&mut items, sort);
+        let (items, limit, total, page) = list_page(items, filter);
+        OrganizationUnitList {
+            items,
+            limit,
+            total,
+            page,
         }
     }
 
@@ -196,47 +181,33 @@ impl CacheDB {
         customer_or_organization: Option<CustomerOrOrganization>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> InstitutionList {
         let institutions = self.inner.infra.institutions.read().await;
-        let iter = if let Some(ty) = ty.as_ref() {
-            itertools::Either::Right(
-                institutions
-                    .values()
-                    .filter(|c| c.ty.as_ref() == ty.as_str()),
-            )
-        } else {
-            itertools::Either::Left(institutions.values())
-        };
-        let iter = match &customer_or_organization {
-            Some(CustomerOrOrganization::Customer(customer_id)) => itertools::Either::Left(
-                itertools::Either::Left(iter.filter(|v| v.as_ref().partial_equal(customer_id))),
-            ),
-            Some(CustomerOrOrganization::Organization(organization_id)) => {
-                itertools::Either::Left(itertools::Either::Right(
-                    iter.filter(|v| v.as_ref().partial_equal(organization_id)),
-                ))
-            }
-            _ => itertools::Either::Right(iter),
-        };
-        if let Some(filter) = filter {
-            let page = filter.page.unwrap_or(0);
-            let limit = filter.limit.unwrap_or(100);
-            let offset = page * limit;
-            let items: Arc<[Arc<Institution>]> = iter.skip(offset).take(limit).cloned().collect();
-            InstitutionList {
-                items,
-                limit: Some(limit as i64),
-                total: Some(self.inner.infra.institutions_total.get()),
-                page: Some(page as i64),
-            }
-        } else {
-            let items: Arc<[Arc<Institution>]> = iter.cloned().collect();
-            InstitutionList {
-                items,
-                limit: None,
-                total: Some(self.inner.infra.institutions_total.get()),
-                page: Some(0),
-            }
+        let query = query.unwrap_or_default();
+        let mut items: Vec<Arc<Institution>> = institutions
+            .values()
+            .filter(|c| ty.as_ref().map_or(true, |ty| c.ty.as_ref() == ty.as_str()))
+            .filter(|v| match &customer_or_organization {
+                Some(CustomerOrOrganization::Customer(customer_id)) => {
+                    v.as_ref().partial_equal(customer_id)
+                }
+                Some(CustomerOrOrganization::Organization(organization_id)) => {
+                    v.as_ref().partial_equal(organization_id)
+                }
+                _ => true,
+            })
+            .filter(|c| query.matches(c.as_ref()))
+            .cloned()
+            .collect();
+        sort_infra_items(&mut items, sort);
+        let (items, limit, total, page) = list_page(items, filter);
+        InstitutionList {
+            items,
+            limit,
+            total,
+            page,
         }
     }
 
@@ -244,6 +215,8 @@ impl CacheDB {
         &self,
         context: Option<InfraContext>,
         filter: Option<ListFilter>,
+        query: Option<UserQuery>,
+        sort: Option<UserSort>,
     ) -> UserList {
         let users = self.inner.user.users.read().await;
         let user_roles = self.inner.user.user_roles.read().await;
@@ -259,70 +232,109 @@ impl CacheDB {
             }
             _ => &[],
         };
+        let query = query.unwrap_or_default();
         let user_list = users.list();
-        let iter = user_list.iter().map(|u| {
-            let context = user_roles
-                .by_user_id(&u.id)
-                .and_then(|r| r.iter().find_map(|r| roles.get(r).and_then(|r| r.context)));
-            let access = user_roles.by_user_id(&u.id).and_then(|r| {
-                r.iter().find_map(|r| {
-                    roles
-                        .get(r)
-                        .and_then(|r| qm_role::Access::from_str(r.name.as_ref()).ok())
-                })
-            });
-            let group = user_groups.by_user_id(&u.id).and_then(|g| {
-                g.iter().find_map(|g| {
-                    groups
-                        .get(g)
-                        .and_then(|r| group_attributes.get(&r.id).cloned())
-                })
-            });
-            UserDetails {
-                user: u.clone(),
-                context,
-                access,
-                group,
-            }
-        });
-        if let Some(filter) = filter {
-            let page = filter.page.unwrap_or(0);
-            let limit = filter.limit.unwrap_or(100);
-            let offset = page * limit;
-            let items: Vec<UserDetails> = if let Some(context) = context {
-                iter.filter(|v| {
-                    v.partial_equal(&context) || institutions.iter().any(|i| v.partial_equal(i))
-                })
-                .skip(offset)
-                .take(limit)
-                .collect::<Vec<UserDetails>>()
-            } else {
-                iter.skip(offset).take(limit).collect::<Vec<UserDetails>>()
-            };
-            UserList {
-                items: Arc::from(items),
-                limit: Some(limit as i64),
-                total: Some(self.inner.user.users_total.get()),
-                page: Some(page as i64),
-            }
-        } else {
-            let items: Vec<UserDetails> = if let Some(context) = context {
-                iter.filter(|v| {
-                    v.partial_equal(&context) || institutions.iter().any(|i| v.partial_equal(i))
+        let mut items: Vec<UserDetails> = user_list
+            .iter()
+            .map(|u| {
+                let context = user_roles
+                    .by_user_id(&u.id)
+                    .and_then(|r| r.iter().find_map(|r| roles.get(r).and_then(|r| r.context)));
+                let access = user_roles.by_user_id(&u.id).and_then(|r| {
+                    r.iter().find_map(|r| {
+                        roles
+                            .get(r)
+                            .and_then(|r| qm_role::Access::from_str(r.name.as_ref()).ok())
+                    })
+                });
+                let group = user_groups.by_user_id(&u.id).and_then(|g| {
+                    g.iter().find_map(|g| {
+                        groups
+                            .get(g)
+                            .and_then(|r| group_attributes.get(&r.id).cloned())
+                    })
+                });
+                UserDetails {
+                    user: u.clone(),
+                    context,
+                    access,
+                    group,
+                }
+            })
+            .filter(|v| {
+                context.as_ref().map_or(true, |context| {
+                    v.partial_equal(context) || institutions.iter().any(|i| v.partial_equal(i))
                 })
-                .collect::<Vec<UserDetails>>()
-            } else {
-                iter.collect::<Vec<UserDetails>>()
-            };
-            UserList {
-                items: Arc::from(items),
-                limit: None,
-                total: Some(self.inner.user.users_total.get()),
-                page: Some(0),
-            }
+            })
+            .filter(|v| query.matches(v))
+            .collect();
+        sort_users(&mut items, sort);
+        let (items, limit, total, page) = list_page(items, filter);
+        UserList {
+            items,
+            limit,
+            total,
+            page,
         }
     }
 
+    pub async fn customer_connection(
+        &self,
+        filter: Option<ConnectionFilter>,
+        ty: Option<String>,
+    ) -> EntityResult<KeyConnection<i64, Arc<Customer>>> {
+        let items = self.customer_list(None, ty, None, None).await.items;
+        paginate(items.to_vec(), filter.as_ref(), |v| *v.id)
+    }
+
+    pub async fn organization_connection(
+        &self,
+        customer_id: Option<CustomerId>,
+        filter: Option<ConnectionFilter>,
+        ty: Option<String>,
+    ) -> EntityResult<KeyConnection<i64, Arc<Organization>>> {
+        let items = self
+            .organization_list(customer_id, None, ty, None, None)
+            .await
+            .items;
+        paginate(items.to_vec(), filter.as_ref(), |v| *v.id)
+    }
+
+    pub async fn organization_unit_connection(
+        &self,
+        customer_or_organization: Option<CustomerOrOrganization>,
+        filter: Option<ConnectionFilter>,
+        ty: Option<String>,
+    ) -> EntityResult<KeyConnection<i64, Arc<OrganizationUnit>>> {
+        let items = self
+            .organization_unit_list(customer_or_organization, None, ty, None, None)
+            .await
+            .items;
+        paginate(items.to_vec(), filter.as_ref(), |v| *v.id)
+    }
+
+    pub async fn institution_connection(
+        &self,
+        customer_or_organization: Option<CustomerOrOrganization>,
+        filter: Option<ConnectionFilter>,
+        ty: Option<String>,
+    ) -> EntityResult<KeyConnection<i64, Arc<Institution>>> {
+        let items = self
+            .institution_list(customer_or_organization, None, ty, None, None)
+            .await
+            .items;
+        paginate(items.to_vec(), filter.as_ref(), |v| *v.id)
+    }
+
+    pub async fn user_connection(
+        &self,
+        context: Option<InfraContext>,
+        filter: Option<ConnectionFilter>,
+    ) -> EntityResult<KeyConnection<String, UserDetails>> {
+        let items = self.user_list(context, None, None, None).await.items;
+        paginate(items.to_vec(), filter.as_ref(), |v| v.user.id.to_string())
+    }
+
     pub async fn customer_by_id(&self, id: &InfraId) -> Option<Arc<Customer>> {
         self.inner
             .infra
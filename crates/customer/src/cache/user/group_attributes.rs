@@ -55,42 +55,39 @@ impl GroupAttributes {
     pub fn update(&mut self, groups: &Groups, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<GroupAttributeUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
-            (Op::Insert, Some(new), None) => {
-                if groups.contains(&new.group_id) {
-                    let mut group_detail =
-                        if let Some(group_detail) = self.group_attribute_map.get(&new.group_id) {
-                            group_detail.as_ref().to_owned()
-                        } else {
-                            GroupDetail {
-                                built_in: false,
-                                display_name: None,
-                                allowed_access_levels: None,
-                                context: None,
-                            }
-                        };
-                    if let Some((name, value)) = new.name.zip(new.value.as_ref()) {
-                        match name.as_str() {
-                            "built_in" => {
-                                group_detail.built_in = value == "1";
-                            }
-                            "allowed_access_levels" => {
-                                group_detail.allowed_access_levels =
-                                    Some(parse_access_level(value));
-                            }
-                            "display_name" => {
-                                group_detail.display_name = Some(Arc::from(value.to_string()));
-                            }
-                            "context" => {
-                                group_detail.context = value.as_str().parse().ok();
-                            }
-                            _ => {}
+            (Op::Insert, Some(new), None) if groups.contains(&new.group_id) => {
+                let mut group_detail =
+                    if let Some(group_detail) = self.group_attribute_map.get(&new.group_id) {
+                        group_detail.as_ref().to_owned()
+                    } else {
+                        GroupDetail {
+                            built_in: false,
+                            display_name: None,
+                            allowed_access_levels: None,
+                            context: None,
                         }
-                        self.group_attribute_map
-                            .insert(new.group_id.clone(), Arc::new(group_detail));
+                    };
+                if let Some((name, value)) = new.name.zip(new.value.as_ref()) {
+                    match name.as_str() {
+                        "built_in" => {
+                            group_detail.built_in = value == "1";
+                        }
+                        "allowed_access_levels" => {
+                            group_detail.allowed_access_levels = Some(parse_access_level(value));
+                        }
+                        "display_name" => {
+                            group_detail.display_name = Some(Arc::from(value.to_string()));
+                        }
+                        "context" => {
+                            group_detail.context = value.as_str().parse().ok();
+                        }
+                        _ => {}
                     }
+                    self.group_attribute_map
+                        .insert(new.group_id.clone(), Arc::new(group_detail));
                 }
             }
-            (Op::Delete, None, Some(old)) => if groups.contains(&old.group_id) {},
+            (Op::Delete, None, Some(old)) if groups.contains(&old.group_id) => {}
             _ => {}
         }
         Ok(())
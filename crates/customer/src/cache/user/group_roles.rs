@@ -53,38 +53,38 @@ impl GroupRoles {
     ) -> anyhow::Result<bool> {
         let payload: Payload<GroupRoleMappingUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
-            (Op::Insert, Some(new), None) => {
-                if groups.contains(&new.group_id) && roles.contains(&new.role_id) {
-                    self.group_id_role_map
-                        .entry(new.group_id.clone())
-                        .or_default()
-                        .insert(new.role_id.clone());
-                    self.role_id_group_map
-                        .entry(new.role_id)
-                        .or_default()
-                        .insert(new.group_id);
-                    return Ok(true);
-                }
+            (Op::Insert, Some(new), None)
+                if groups.contains(&new.group_id) && roles.contains(&new.role_id) =>
+            {
+                self.group_id_role_map
+                    .entry(new.group_id.clone())
+                    .or_default()
+                    .insert(new.role_id.clone());
+                self.role_id_group_map
+                    .entry(new.role_id)
+                    .or_default()
+                    .insert(new.group_id);
+                return Ok(true);
             }
-            (Op::Delete, None, Some(old)) => {
-                if groups.contains(&old.group_id) && roles.contains(&old.role_id) {
-                    let e = self
-                        .group_id_role_map
-                        .entry(old.group_id.clone())
-                        .or_default();
-                    e.remove(&old.role_id);
-                    if self.group_id_role_map.is_empty() {
-                        self.group_id_role_map.remove(&old.group_id);
-                    }
-                    self.role_id_group_map
-                        .entry(old.role_id.clone())
-                        .or_default()
-                        .insert(old.group_id);
-                    if self.role_id_group_map.is_empty() {
-                        self.role_id_group_map.remove(&old.role_id);
-                    }
-                    return Ok(true);
+            (Op::Delete, None, Some(old))
+                if groups.contains(&old.group_id) && roles.contains(&old.role_id) =>
+            {
+                let e = self
+                    .group_id_role_map
+                    .entry(old.group_id.clone())
+                    .or_default();
+                e.remove(&old.role_id);
+                if self.group_id_role_map.is_empty() {
+                    self.group_id_role_map.remove(&old.group_id);
+                }
+                self.role_id_group_map
+                    .entry(old.role_id.clone())
+                    .or_default()
+                    .insert(old.group_id);
+                if self.role_id_group_map.is_empty() {
+                    self.role_id_group_map.remove(&old.role_id);
                 }
+                return Ok(true);
             }
             _ => {}
         }
@@ -81,42 +81,38 @@ impl Groups {
     pub fn update(&mut self, realm: &Realm, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<KeycloakGroupUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
-            (Op::Insert, Some(new), None) => {
-                if realm.equals(new.realm_id.as_deref()) {
-                    let group = Arc::new(Group {
-                        id: new.id,
-                        parent_group: new.parent_group,
-                        name: new.name,
-                    });
-                    self.group_id_map.insert(group.id.clone(), group.clone());
-                    if let Some(parent) = group
-                        .parent_group
-                        .as_ref()
-                        .and_then(|id| self.group_id_map.get(id))
-                    {
-                        let e = self.group_name_map.entry(parent.name.clone()).or_default();
-                        e.insert(group.name.clone(), group);
-                    } else if group.parent_group.is_none() {
-                        self.group_name_map
-                            .insert(group.name.clone(), HashMap::default());
-                    }
+            (Op::Insert, Some(new), None) if realm.equals(new.realm_id.as_deref()) => {
+                let group = Arc::new(Group {
+                    id: new.id,
+                    parent_group: new.parent_group,
+                    name: new.name,
+                });
+                self.group_id_map.insert(group.id.clone(), group.clone());
+                if let Some(parent) = group
+                    .parent_group
+                    .as_ref()
+                    .and_then(|id| self.group_id_map.get(id))
+                {
+                    let e = self.group_name_map.entry(parent.name.clone()).or_default();
+                    e.insert(group.name.clone(), group);
+                } else if group.parent_group.is_none() {
+                    self.group_name_map
+                        .insert(group.name.clone(), HashMap::default());
                 }
             }
-            (Op::Delete, None, Some(old)) => {
-                if realm.equals(old.realm_id.as_deref()) {
-                    if let Some(parent) = old
-                        .parent_group
-                        .as_ref()
-                        .and_then(|id| self.group_id_map.get(id))
-                    {
-                        let e = self.group_name_map.entry(parent.name.clone()).or_default();
-                        e.remove(&old.name);
-                        if e.is_empty() {
-                            self.group_name_map.remove(&parent.name);
-                        }
-                    } else if old.parent_group.is_none() {
-                        self.group_name_map.remove(&old.name);
+            (Op::Delete, None, Some(old)) if realm.equals(old.realm_id.as_deref()) => {
+                if let Some(parent) = old
+                    .parent_group
+                    .as_ref()
+                    .and_then(|id| self.group_id_map.get(id))
+                {
+                    let e = self.group_name_map.entry(parent.name.clone()).or_default();
+                    e.remove(&old.name);
+                    if e.is_empty() {
+                        self.group_name_map.remove(&parent.name);
                     }
+                } else if old.parent_group.is_none() {
+                    self.group_name_map.remove(&old.name);
                 }
             }
             _ => {}
@@ -95,6 +95,26 @@ impl UserDB {
         self.users_total.set(self.users.read().await.total());
     }
 
+    pub async fn update_user(&self, user: Arc<User>) {
+        self.users.write().await.update_user(user);
+    }
+
+    pub async fn add_user_group(&self, user_id: Arc<str>, group_id: Arc<str>) {
+        self.user_groups.write().await.add(user_id, group_id);
+    }
+
+    pub async fn remove_user_group(&self, user_id: &str, group_id: &str) {
+        self.user_groups.write().await.remove(user_id, group_id);
+    }
+
+    pub async fn add_user_role(&self, user_id: Arc<str>, role_id: Arc<str>) {
+        self.user_roles.write().await.add(user_id, role_id);
+    }
+
+    pub async fn remove_user_role(&self, user_id: &str, role_id: &str) {
+        self.user_roles.write().await.remove(user_id, role_id);
+    }
+
     pub async fn cleanup(db: &DB) -> anyhow::Result<()> {
         let mut migrator = sqlx::migrate!("./migrations/keycloak");
         migrator.set_ignore_missing(true);
@@ -20,6 +20,7 @@ fn parse_context(name: &str) -> Option<InfraContext> {
     None
 }
 
+#[derive(Default)]
 pub struct Roles {
     role_name_map: RoleMap,
     role_id_map: RoleIdMap,
@@ -92,22 +93,18 @@ impl Roles {
     pub fn update(&mut self, realm: &Realm, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<KeycloakRoleUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
-            (Op::Insert, Some(new), None) => {
-                if realm.equals(new.realm_id.as_deref()) {
-                    let role = Arc::new(Role {
-                        id: new.id.clone(),
-                        name: new.name.clone(),
-                        context: parse_context(&new.name),
-                    });
-                    self.role_id_map.insert(role.id.clone(), role.clone());
-                    self.role_name_map.insert(role.name.clone(), role);
-                }
+            (Op::Insert, Some(new), None) if realm.equals(new.realm_id.as_deref()) => {
+                let role = Arc::new(Role {
+                    id: new.id.clone(),
+                    name: new.name.clone(),
+                    context: parse_context(&new.name),
+                });
+                self.role_id_map.insert(role.id.clone(), role.clone());
+                self.role_name_map.insert(role.name.clone(), role);
             }
-            (Op::Delete, None, Some(old)) => {
-                if realm.equals(old.realm_id.as_deref()) {
-                    self.role_id_map.remove(&old.id);
-                    self.role_name_map.remove(&old.name);
-                }
+            (Op::Delete, None, Some(old)) if realm.equals(old.realm_id.as_deref()) => {
+                self.role_id_map.remove(&old.id);
+                self.role_name_map.remove(&old.name);
             }
             _ => {}
         }
@@ -10,6 +10,7 @@ use crate::query::fetch_user_groups;
 
 use super::{groups::Groups, users::Users};
 
+#[derive(Default)]
 pub struct UserGroups {
     user_id_group_map: UserGroupMap,
     group_id_user_map: UserGroupMap,
@@ -44,6 +45,32 @@ impl UserGroups {
         self.user_id_group_map.get(user_id)
     }
 
+    pub fn add(&mut self, user_id: Arc<str>, group_id: Arc<str>) {
+        self.user_id_group_map
+            .entry(user_id.clone())
+            .or_default()
+            .insert(group_id.clone());
+        self.group_id_user_map
+            .entry(group_id)
+            .or_default()
+            .insert(user_id);
+    }
+
+    pub fn remove(&mut self, user_id: &str, group_id: &str) {
+        if let Some(e) = self.user_id_group_map.get_mut(user_id) {
+            e.remove(group_id);
+            if e.is_empty() {
+                self.user_id_group_map.remove(user_id);
+            }
+        }
+        if let Some(e) = self.group_id_user_map.get_mut(group_id) {
+            e.remove(user_id);
+            if e.is_empty() {
+                self.group_id_user_map.remove(group_id);
+            }
+        }
+    }
+
     pub fn update(
         &mut self,
         users: &Users,
@@ -52,41 +79,96 @@ impl UserGroups {
     ) -> anyhow::Result<bool> {
         let payload: Payload<UserGroupMembershipUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
-            (Op::Insert, Some(new), None) => {
-                if users.contains(&new.user_id) && groups.contains(&new.group_id) {
-                    self.user_id_group_map
-                        .entry(new.user_id.clone())
-                        .or_default()
-                        .insert(new.group_id.clone());
-                    self.group_id_user_map
-                        .entry(new.group_id)
-                        .or_default()
-                        .insert(new.user_id);
-                    return Ok(true);
-                }
+            (Op::Insert, Some(new), None)
+                if users.contains(&new.user_id) && groups.contains(&new.group_id) =>
+            {
+                self.add(new.user_id, new.group_id);
+                return Ok(true);
             }
-            (Op::Delete, None, Some(old)) => {
-                if users.contains(&old.user_id) && groups.contains(&old.group_id) {
-                    let e = self
-                        .user_id_group_map
-                        .entry(old.user_id.clone())
-                        .or_default();
-                    e.remove(&old.group_id);
-                    if self.user_id_group_map.is_empty() {
-                        self.user_id_group_map.remove(&old.user_id);
-                    }
-                    self.group_id_user_map
-                        .entry(old.group_id.clone())
-                        .or_default()
-                        .insert(old.user_id);
-                    if self.group_id_user_map.is_empty() {
-                        self.group_id_user_map.remove(&old.group_id);
-                    }
-                    return Ok(true);
-                }
+            (Op::Delete, None, Some(old))
+                if users.contains(&old.user_id) && groups.contains(&old.group_id) =>
+            {
+                self.remove(&old.user_id, &old.group_id);
+                return Ok(true);
             }
             _ => {}
         }
         Ok(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Groups, UserGroups, Users};
+    use crate::cache::{Group, User};
+
+    fn payload(op: &str, user_id: &str, group_id: &str) -> String {
+        let row = format!(r#"{{"user_id":"{user_id}","group_id":"{group_id}"}}"#);
+        match op {
+            "INSERT" => format!(r#"{{"op":"INSERT","new":{row},"old":null}}"#),
+            _ => format!(r#"{{"op":"{op}","new":null,"old":{row}}}"#),
+        }
+    }
+
+    #[test]
+    fn add_remove_test() {
+        let mut user_groups = UserGroups::default();
+        user_groups.add(Arc::from("u1"), Arc::from("g1"));
+        user_groups.add(Arc::from("u1"), Arc::from("g12"));
+        user_groups.add(Arc::from("u2"), Arc::from("g1"));
+        assert_eq!(user_groups.by_user_id("u1").map(|s| s.len()), Some(2));
+
+        user_groups.remove("u1", "g1");
+        assert_eq!(user_groups.by_user_id("u1").map(|s| s.len()), Some(1));
+        assert_eq!(
+            user_groups.group_id_user_map.get("g1").map(|s| s.len()),
+            Some(1)
+        );
+        user_groups.remove("u1", "g12");
+        user_groups.remove("u2", "g1");
+        // empty entries are removed
+        assert!(user_groups.user_id_group_map.is_empty());
+        assert!(user_groups.group_id_user_map.is_empty());
+        // removing unknown entries is a no-op
+        user_groups.remove("u1", "g1");
+    }
+
+    #[test]
+    fn update_test() -> anyhow::Result<()> {
+        let mut users = Users::default();
+        users.new_user(Arc::new(User {
+            id: Arc::from("u1"),
+            username: Arc::from("jane"),
+            email: Arc::from("jane@example.com"),
+            firstname: Arc::from("Jane"),
+            lastname: Arc::from("Doe"),
+            enabled: true,
+            created_at: None,
+        }));
+        let mut groups = Groups::default();
+        groups.new_group(
+            Arc::new(Group {
+                id: Arc::from("g1"),
+                parent_group: None,
+                name: Arc::from("admin"),
+            }),
+            Arc::from("app"),
+        );
+        let mut user_groups = UserGroups::default();
+
+        assert!(user_groups.update(&users, &groups, &payload("INSERT", "u1", "g1"))?);
+        assert!(user_groups.by_user_id("u1").unwrap().contains("g1"));
+        // unknown users and groups are ignored
+        assert!(!user_groups.update(&users, &groups, &payload("INSERT", "u2", "g1"))?);
+        assert!(!user_groups.update(&users, &groups, &payload("DELETE", "u1", "g12"))?);
+        assert!(!user_groups.update(&users, &groups, &payload("UPDATE", "u1", "g1"))?);
+        assert!(user_groups.by_user_id("u1").is_some());
+
+        assert!(user_groups.update(&users, &groups, &payload("DELETE", "u1", "g1"))?);
+        assert!(user_groups.by_user_id("u1").is_none());
+        assert!(user_groups.group_id_user_map.is_empty());
+        Ok(())
+    }
+}
@@ -10,6 +10,7 @@ use crate::query::fetch_user_roles;
 
 use super::{roles::Roles, users::Users};
 
+#[derive(Default)]
 pub struct UserRoles {
     user_id_role_map: UserRoleMap,
     role_id_user_map: UserRoleMap,
@@ -44,44 +45,124 @@ impl UserRoles {
         self.user_id_role_map.get(user_id)
     }
 
+    pub fn add(&mut self, user_id: Arc<str>, role_id: Arc<str>) {
+        self.user_id_role_map
+            .entry(user_id.clone())
+            .or_default()
+            .insert(role_id.clone());
+        self.role_id_user_map
+            .entry(role_id)
+            .or_default()
+            .insert(user_id);
+    }
+
+    pub fn remove(&mut self, user_id: &str, role_id: &str) {
+        if let Some(e) = self.user_id_role_map.get_mut(user_id) {
+            e.remove(role_id);
+            if e.is_empty() {
+                self.user_id_role_map.remove(user_id);
+            }
+        }
+        if let Some(e) = self.role_id_user_map.get_mut(role_id) {
+            e.remove(user_id);
+            if e.is_empty() {
+                self.role_id_user_map.remove(role_id);
+            }
+        }
+    }
+
     pub fn update(&mut self, users: &Users, roles: &Roles, payload: &str) -> anyhow::Result<bool> {
         let payload: Payload<UserRoleMappingUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
-            (Op::Insert, Some(new), None) => {
-                if users.contains(&new.user_id) && roles.contains(&new.role_id) {
-                    self.user_id_role_map
-                        .entry(new.user_id.clone())
-                        .or_default()
-                        .insert(new.role_id.clone());
-                    self.role_id_user_map
-                        .entry(new.role_id)
-                        .or_default()
-                        .insert(new.user_id);
-                    return Ok(true);
-                }
+            (Op::Insert, Some(new), None)
+                if users.contains(&new.user_id) && roles.contains(&new.role_id) =>
+            {
+                self.add(new.user_id, new.role_id);
+                return Ok(true);
             }
-            (Op::Delete, None, Some(old)) => {
-                if users.contains(&old.user_id) && roles.contains(&old.role_id) {
-                    let e = self
-                        .user_id_role_map
-                        .entry(old.user_id.clone())
-                        .or_default();
-                    e.remove(&old.role_id);
-                    if self.user_id_role_map.is_empty() {
-                        self.user_id_role_map.remove(&old.user_id);
-                    }
-                    self.role_id_user_map
-                        .entry(old.role_id.clone())
-                        .or_default()
-                        .insert(old.user_id);
-                    if self.role_id_user_map.is_empty() {
-                        self.role_id_user_map.remove(&old.role_id);
-                    }
-                    return Ok(true);
-                }
+            (Op::Delete, None, Some(old))
+                if users.contains(&old.user_id) && roles.contains(&old.role_id) =>
+            {
+                self.remove(&old.user_id, &old.role_id);
+                return Ok(true);
             }
             _ => {}
         }
         Ok(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use qm_keycloak::RoleRepresentation;
+
+    use super::{Roles, UserRoles, Users};
+    use crate::cache::User;
+
+    fn payload(op: &str, user_id: &str, role_id: &str) -> String {
+        let row = format!(r#"{{"user_id":"{user_id}","role_id":"{role_id}"}}"#);
+        match op {
+            "INSERT" => format!(r#"{{"op":"INSERT","new":{row},"old":null}}"#),
+            _ => format!(r#"{{"op":"{op}","new":null,"old":{row}}}"#),
+        }
+    }
+
+    #[test]
+    fn add_remove_test() {
+        let mut user_roles = UserRoles::default();
+        user_roles.add(Arc::from("u1"), Arc::from("r1"));
+        user_roles.add(Arc::from("u1"), Arc::from("r12"));
+        user_roles.add(Arc::from("u2"), Arc::from("r1"));
+        assert_eq!(user_roles.by_user_id("u1").map(|s| s.len()), Some(2));
+
+        user_roles.remove("u1", "r1");
+        assert_eq!(user_roles.by_user_id("u1").map(|s| s.len()), Some(1));
+        assert_eq!(
+            user_roles.role_id_user_map.get("r1").map(|s| s.len()),
+            Some(1)
+        );
+        user_roles.remove("u1", "r12");
+        user_roles.remove("u2", "r1");
+        // empty entries are removed
+        assert!(user_roles.user_id_role_map.is_empty());
+        assert!(user_roles.role_id_user_map.is_empty());
+        // removing unknown entries is a no-op
+        user_roles.remove("u1", "r1");
+    }
+
+    #[test]
+    fn update_test() -> anyhow::Result<()> {
+        let mut users = Users::default();
+        users.new_user(Arc::new(User {
+            id: Arc::from("u1"),
+            username: Arc::from("jane"),
+            email: Arc::from("jane@example.com"),
+            firstname: Arc::from("Jane"),
+            lastname: Arc::from("Doe"),
+            enabled: true,
+            created_at: None,
+        }));
+        let mut roles = Roles::default();
+        roles.new_roles(vec![RoleRepresentation {
+            id: Some("r1".to_string()),
+            name: Some("customer:access@1".to_string()),
+            ..Default::default()
+        }]);
+        let mut user_roles = UserRoles::default();
+
+        assert!(user_roles.update(&users, &roles, &payload("INSERT", "u1", "r1"))?);
+        assert!(user_roles.by_user_id("u1").unwrap().contains("r1"));
+        // unknown users and roles are ignored
+        assert!(!user_roles.update(&users, &roles, &payload("INSERT", "u2", "r1"))?);
+        assert!(!user_roles.update(&users, &roles, &payload("DELETE", "u1", "r12"))?);
+        assert!(!user_roles.update(&users, &roles, &payload("UPDATE", "u1", "r1"))?);
+        assert!(user_roles.by_user_id("u1").is_some());
+
+        assert!(user_roles.update(&users, &roles, &payload("DELETE", "u1", "r1"))?);
+        assert!(user_roles.by_user_id("u1").is_none());
+        assert!(user_roles.role_id_user_map.is_empty());
+        Ok(())
+    }
+}
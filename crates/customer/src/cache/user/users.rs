@@ -7,6 +7,7 @@ use crate::{
         update::{Op, Payload},
         User, UserEntityUpdate, UserMap,
     },
+    model::created_at_from_millis,
     query::fetch_users,
 };
 
@@ -38,6 +39,7 @@ impl Users {
                     firstname,
                     lastname,
                     enabled: row.enabled,
+                    created_at: created_at_from_millis(row.created_timestamp),
                 }));
                 state
             });
@@ -66,6 +68,15 @@ impl Users {
         self.user_email_map.insert(user.email.clone(), user);
     }
 
+    /// Replaces the user with the same id, also if username or email changed.
+    pub fn update_user(&mut self, user: Arc<User>) {
+        if let Some(old) = self.user_id_map.remove(&user.id) {
+            self.users.remove(&old.username);
+            self.user_email_map.remove(&old.email);
+        }
+        self.new_user(user);
+    }
+
     pub fn list(&self) -> Arc<[Arc<User>]> {
         self.user_id_map.values().cloned().collect()
     }
@@ -89,45 +100,44 @@ impl Users {
     pub fn update(&mut self, realm: &Realm, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<UserEntityUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
-            (Op::Insert, Some(new), None) => {
-                if realm.equals(new.realm_id.as_deref()) && new.has_all_fields() {
-                    let user = Arc::new(User {
-                        id: new.id,
-                        username: new.username,
-                        email: new.email.unwrap(),
-                        firstname: new.first_name.unwrap(),
-                        lastname: new.last_name.unwrap(),
-                        enabled: new.enabled,
-                    });
-                    self.new_user(user);
-                }
+            (Op::Insert, Some(new), None)
+                if realm.equals(new.realm_id.as_deref()) && new.has_all_fields() =>
+            {
+                let user = Arc::new(User {
+                    id: new.id,
+                    username: new.username,
+                    email: new.email.unwrap(),
+                    firstname: new.first_name.unwrap(),
+                    lastname: new.last_name.unwrap(),
+                    enabled: new.enabled,
+                    created_at: created_at_from_millis(new.created_timestamp),
+                });
+                self.new_user(user);
             }
-            (Op::Update, Some(new), Some(old)) => {
+            (Op::Update, Some(new), Some(old))
                 if realm.equals(new.realm_id.as_deref())
                     && realm.equals(old.realm_id.as_deref())
-                    && new.has_all_fields()
-                {
-                    let user = Arc::new(User {
-                        id: new.id,
-                        username: new.username,
-                        email: new.email.unwrap(),
-                        firstname: new.first_name.unwrap(),
-                        lastname: new.last_name.unwrap(),
-                        enabled: new.enabled,
-                    });
-                    self.user_id_map.remove(&user.id);
-                    self.users.remove(&user.username);
-                    self.user_email_map.remove(&user.email);
-                    self.new_user(user);
-                }
+                    && new.has_all_fields() =>
+            {
+                let user = Arc::new(User {
+                    id: new.id,
+                    username: new.username,
+                    email: new.email.unwrap(),
+                    firstname: new.first_name.unwrap(),
+                    lastname: new.last_name.unwrap(),
+                    enabled: new.enabled,
+                    created_at: created_at_from_millis(new.created_timestamp),
+                });
+                self.user_id_map.remove(&user.id);
+                self.users.remove(&user.username);
+                self.user_email_map.remove(&user.email);
+                self.new_user(user);
             }
-            (Op::Delete, None, Some(old)) => {
-                if realm.equals(old.realm_id.as_deref()) {
-                    self.user_id_map.remove(&old.id);
-                    self.users.remove(&old.username);
-                    if old.email.is_some() {
-                        self.user_email_map.remove(&old.email.unwrap());
-                    }
+            (Op::Delete, None, Some(old)) if realm.equals(old.realm_id.as_deref()) => {
+                self.user_id_map.remove(&old.id);
+                self.users.remove(&old.username);
+                if let Some(email) = old.email {
+                    self.user_email_map.remove(&email);
                 }
             }
             _ => {}
@@ -135,3 +145,56 @@ impl Users {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use time::macros::datetime;
+
+    use super::Users;
+    use crate::cache::User;
+    use crate::model::created_at_from_millis;
+
+    fn user(username: &str, email: &str) -> Arc<User> {
+        Arc::new(User {
+            id: Arc::from("1"),
+            username: Arc::from(username),
+            email: Arc::from(email),
+            firstname: Arc::from("Jane"),
+            lastname: Arc::from("Doe"),
+            enabled: true,
+            created_at: None,
+        })
+    }
+
+    #[test]
+    fn update_user_test() {
+        let mut users = Users::default();
+        users.new_user(user("jane", "jane@example.com"));
+        users.update_user(user("jane.doe", "jane.doe@example.com"));
+        assert_eq!(users.total(), 1);
+        assert!(users.by_username("jane").is_none());
+        assert!(users.by_email("jane@example.com").is_none());
+        assert_eq!(
+            users.get("1").map(|u| u.username.as_ref()),
+            Some("jane.doe")
+        );
+        assert_eq!(
+            users
+                .by_email("jane.doe@example.com")
+                .map(|u| u.id.as_ref()),
+            Some("1")
+        );
+        assert!(users.by_username("jane.doe").is_some());
+    }
+
+    #[test]
+    fn created_at_from_millis_test() {
+        assert_eq!(
+            created_at_from_millis(Some(1_704_067_200_500)),
+            Some(datetime!(2024-01-01 0:00:00.5))
+        );
+        assert_eq!(created_at_from_millis(None), None);
+    }
+}
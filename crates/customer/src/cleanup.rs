@@ -3,7 +3,9 @@ use futures::stream::FuturesUnordered;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 use std::sync::Arc;
+use std::time::Duration;
 use strum::AsRefStr;
+use time::PrimitiveDateTime;
 use tokio::sync::Semaphore;
 
 use qm_entity::ids::CustomerIds;
@@ -14,6 +16,23 @@ use qm_keycloak::Keycloak;
 use qm_keycloak::KeycloakError;
 use sqlx::types::Uuid;
 
+lazy_static::lazy_static! {
+    static ref RETENTION: Duration = {
+        std::env::var("CUSTOMER_DELETE_RETENTION_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(30 * 24 * 60 * 60))
+    };
+}
+
+/// Time until deleted entities are purged, 30 days by default.
+///
+/// Configured in seconds with `CUSTOMER_DELETE_RETENTION_SECS`.
+pub fn retention() -> Duration {
+    *RETENTION
+}
+
 #[derive(
     Default, AsRefStr, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
 )]
@@ -31,10 +50,26 @@ pub enum CleanupTaskType {
     None,
 }
 
+impl CleanupTaskType {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Customers(ids) => ids.is_empty(),
+            Self::Organizations(ids) => ids.is_empty(),
+            Self::Institutions(ids) => ids.is_empty(),
+            Self::OrganizationUnits(ids) => ids.is_empty(),
+            Self::None => true,
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct CleanupTask {
     pub id: Uuid,
     pub ty: CleanupTaskType,
+    /// Time of the soft delete, only the entities which are still deleted at
+    /// this time are purged. Without it the entities are already removed.
+    #[serde(default)]
+    pub deleted_at: Option<PrimitiveDateTime>,
 }
 
 impl CleanupTask {
@@ -42,6 +77,17 @@ impl CleanupTask {
         Self {
             id: Uuid::new_v4(),
             ty,
+            deleted_at: None,
+        }
+    }
+
+    /// Task which purges the entities deleted at `deleted_at`, it has to be
+    /// scheduled after the [retention] period.
+    pub fn purge(ty: CleanupTaskType, deleted_at: PrimitiveDateTime) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            ty,
+            deleted_at: Some(deleted_at),
         }
     }
 }
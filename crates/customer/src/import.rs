@@ -0,0 +1,255 @@
+use qm_redis::redis::AsyncCommands;
+use qm_redis::AsyncWorker;
+use qm_redis::Producer;
+use qm_redis::Redis;
+use qm_redis::Work;
+use qm_redis::WorkerContext;
+use qm_redis::Workers;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+
+use crate::context::RelatedStorage;
+use crate::model::{
+    CreateUserInput, CreateUserPayload, UserImportFormat, UserImportJob, UserImportRow,
+    UserImportRowStatus, UserImportStatus,
+};
+use crate::schema::user::create_user;
+
+lazy_static::lazy_static! {
+    static ref PREFIX: String = {
+        std::env::var("CUSTOMER_USER_IMPORT_TASK_PREFIX").unwrap_or("user_import_tasks".to_string())
+    };
+}
+
+/// Maximum number of rows of one import.
+pub const MAX_ROWS: usize = 1000;
+/// Seconds until the results of an import are removed.
+const JOB_TTL: u64 = 7 * 24 * 60 * 60;
+
+/// Validated row of an import which is created by the background job, the
+/// user has no password because the item is stored in the work queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserImportItem {
+    pub row: usize,
+    pub user: CreateUserInput,
+    pub group_id: Option<String>,
+    pub access: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserImportTask {
+    pub id: Uuid,
+    pub items: Vec<UserImportItem>,
+}
+
+pub fn parse_rows(format: UserImportFormat, data: &str) -> anyhow::Result<Vec<UserImportRow>> {
+    let rows: Vec<UserImportRow> = match format {
+        UserImportFormat::Csv => csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()?,
+        UserImportFormat::Json => serde_json::from_str(data)?,
+    };
+    if rows.len() > MAX_ROWS {
+        anyhow::bail!("import is limited to {MAX_ROWS} rows");
+    }
+    Ok(rows)
+}
+
+fn job_key(id: &Uuid) -> String {
+    format!("{}:jobs:{id}", PREFIX.as_str())
+}
+
+pub async fn save_job(redis: &Redis, job: &UserImportJob) -> anyhow::Result<()> {
+    let mut con = redis.connect().await?;
+    let _: () = con
+        .set_ex(job_key(&job.id), serde_json::to_string(job)?, JOB_TTL)
+        .await?;
+    Ok(())
+}
+
+pub async fn load_job(redis: &Redis, id: &Uuid) -> anyhow::Result<Option<UserImportJob>> {
+    let mut con = redis.connect().await?;
+    let job: Option<String> = con.get(job_key(id)).await?;
+    Ok(job.map(|job| serde_json::from_str(&job)).transpose()?)
+}
+
+/// Stores the job and queues the valid rows for the background job.
+pub async fn enqueue(
+    redis: &Redis,
+    job: &UserImportJob,
+    items: Vec<UserImportItem>,
+) -> anyhow::Result<()> {
+    save_job(redis, job).await?;
+    if !items.is_empty() {
+        Producer::new_with_client(redis.pool(), PREFIX.as_str())
+            .add_item(&UserImportTask { id: job.id, items })
+            .await?;
+    }
+    Ok(())
+}
+
+pub struct UserImportWorkerCtx<Store> {
+    pub store: Store,
+}
+
+impl<Store> UserImportWorkerCtx<Store> {
+    pub fn new(store: Store) -> Self {
+        Self { store }
+    }
+}
+
+impl<Store> Clone for UserImportWorkerCtx<Store>
+where
+    Store: RelatedStorage,
+{
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+        }
+    }
+}
+
+pub struct UserImportWorker;
+
+#[async_trait::async_trait]
+impl<Store> Work<UserImportWorkerCtx<Store>, UserImportTask> for UserImportWorker
+where
+    Store: RelatedStorage,
+{
+    async fn run(
+        &self,
+        ctx: WorkerContext<UserImportWorkerCtx<Store>>,
+        item: UserImportTask,
+    ) -> anyhow::Result<()> {
+        log::debug!("start user import task with id '{}'", item.id);
+        let store = &ctx.ctx().store;
+        let redis = store.redis();
+        let Some(mut job) = load_job(redis, &item.id).await? else {
+            log::warn!("user import job '{}' expired", item.id);
+            ctx.complete().await?;
+            return Ok(());
+        };
+        job.status = UserImportStatus::Running;
+        save_job(redis, &job).await?;
+        for import_item in item.items {
+            let row = import_item.row;
+            // Rows which were processed before a retry are skipped.
+            let is_pending = job
+                .results
+                .iter()
+                .any(|r| r.row == row && r.status == UserImportRowStatus::Pending);
+            if !is_pending {
+                continue;
+            }
+            let result = create_user(
+                store,
+                CreateUserPayload {
+                    user: import_item.user,
+                    group_id: import_item.group_id,
+                    access: Some(import_item.access),
+                    context: None,
+                },
+            )
+            .await;
+            if let Some(row_result) = job.results.iter_mut().find(|r| r.row == row) {
+                match result {
+                    Ok(created) => {
+                        row_result.status = UserImportRowStatus::Created;
+                        row_result.user_id = Some(created.user.id.to_string());
+                        if let Some(err) = created.invite_error {
+                            row_result.errors.push(err.message);
+                        }
+                    }
+                    Err(err) => {
+                        row_result.status = UserImportRowStatus::Failed;
+                        row_result.errors.push(err.message);
+                    }
+                }
+            }
+            save_job(redis, &job).await?;
+        }
+        job.status = UserImportStatus::Completed;
+        save_job(redis, &job).await?;
+        ctx.complete().await?;
+        log::debug!("finished user import task with id '{}'", item.id);
+        Ok(())
+    }
+}
+
+pub async fn run<Store>(
+    workers: &Workers,
+    ctx: UserImportWorkerCtx<Store>,
+    num_workers: usize,
+) -> anyhow::Result<()>
+where
+    Store: RelatedStorage,
+{
+    workers
+        .start(
+            ctx,
+            AsyncWorker::new(PREFIX.as_str())
+                .with_num_workers(num_workers)
+                .run(UserImportWorker),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_rows, MAX_ROWS};
+    use crate::model::UserImportFormat;
+
+    #[test]
+    fn parse_csv_rows_test() -> anyhow::Result<()> {
+        let data = "username,firstname,lastname,email,accessLevel,context,groupId\n\
+            jane , Jane, Doe, jane@example.com, customer, , \n\
+            john,John,Doe,john@example.com,customer,,g1\n";
+        let rows = parse_rows(UserImportFormat::Csv, data)?;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].username, "jane");
+        assert_eq!(rows[0].email, "jane@example.com");
+        assert_eq!(rows[0].access_level, "customer");
+        assert_eq!(rows[1].group_id.as_deref(), Some("g1"));
+        assert!(rows[1].password.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_json_rows_test() -> anyhow::Result<()> {
+        let data = r#"[{
+            "username": "jane",
+            "firstname": "Jane",
+            "lastname": "Doe",
+            "email": "jane@example.com",
+            "password": "secret",
+            "accessLevel": "customer",
+            "context": "C0000000000000001"
+        }]"#;
+        let rows = parse_rows(UserImportFormat::Json, data)?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].password.as_deref(), Some("secret"));
+        assert_eq!(rows[0].context.as_deref(), Some("C0000000000000001"));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invalid_rows_test() {
+        assert!(parse_rows(UserImportFormat::Json, "{}").is_err());
+        // required columns are missing
+        assert!(parse_rows(UserImportFormat::Csv, "username\njane\n").is_err());
+        let row = r#"{"username":"u","firstname":"f","lastname":"l","email":"e","accessLevel":"customer"}"#;
+        let data = format!("[{}]", vec![row; MAX_ROWS + 1].join(","));
+        let err = parse_rows(UserImportFormat::Json, &data).unwrap_err();
+        assert!(err.to_string().contains("limited to"));
+        let data = format!("[{}]", vec![row; MAX_ROWS].join(","));
+        assert_eq!(
+            parse_rows(UserImportFormat::Json, &data)
+                .map(|r| r.len())
+                .ok(),
+            Some(MAX_ROWS)
+        );
+    }
+}
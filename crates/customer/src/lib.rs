@@ -3,9 +3,11 @@ pub mod cleanup;
 pub mod config;
 pub mod context;
 pub mod groups;
+pub mod import;
 pub mod marker;
 pub mod model;
 pub mod mutation;
+pub mod outbox;
 pub mod query;
 pub mod roles;
 pub mod schema;
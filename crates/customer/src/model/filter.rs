@@ -0,0 +1,438 @@
+use async_graphql::{Enum, InputObject};
+use qm_entity::ids::InfraId;
+use sqlx::types::uuid::Uuid;
+use std::cmp::Ordering;
+use std::sync::Arc;
+use time::PrimitiveDateTime;
+
+use crate::model::{Customer, Institution, Organization, OrganizationUnit, UserDetails};
+
+#[derive(Debug, Default, Clone, Copy, Enum, Eq, PartialEq)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+}
+
+/// Common fields of customers, organizations, organization units and institutions.
+pub trait InfraItem {
+    fn id(&self) -> InfraId;
+    fn name(&self) -> &str;
+    fn ty(&self) -> &str;
+    fn created_by(&self) -> &Uuid;
+    fn created_at(&self) -> &PrimitiveDateTime;
+}
+
+macro_rules! impl_infra_item {
+    ($($t:ty),*) => {
+        $(
+            impl InfraItem for $t {
+                fn id(&self) -> InfraId {
+                    self.id
+                }
+
+                fn name(&self) -> &str {
+                    &self.name
+                }
+
+                fn ty(&self) -> &str {
+                    &self.ty
+                }
+
+                fn created_by(&self) -> &Uuid {
+                    &self.created_by
+                }
+
+                fn created_at(&self) -> &PrimitiveDateTime {
+                    &self.created_at
+                }
+            }
+        )*
+    };
+}
+
+impl_infra_item!(Customer, Organization, OrganizationUnit, Institution);
+
+/// Field filter of the customer, organization, organization unit and institution lists.
+#[derive(Debug, Default, Clone, InputObject)]
+pub struct InfraQuery {
+    /// Case insensitive part of the name.
+    pub name_contains: Option<String>,
+    pub ty: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_after: Option<PrimitiveDateTime>,
+    pub created_before: Option<PrimitiveDateTime>,
+}
+
+impl InfraQuery {
+    pub fn matches(&self, item: &impl InfraItem) -> bool {
+        self.name_contains.as_ref().map_or(true, |name| {
+            item.name().to_lowercase().contains(&name.to_lowercase())
+        }) && self.ty.as_ref().map_or(true, |ty| item.ty() == ty)
+            && self
+                .created_by
+                .as_ref()
+                .map_or(true, |created_by| item.created_by() == created_by)
+            && self
+                .created_after
+                .as_ref()
+                .map_or(true, |after| item.created_at() >= after)
+            && self
+                .created_before
+                .as_ref()
+                .map_or(true, |before| item.created_at() < before)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Enum, Eq, PartialEq)]
+pub enum InfraSortField {
+    #[default]
+    Name,
+    CreatedAt,
+}
+
+#[derive(Debug, Default, Clone, Copy, InputObject)]
+pub struct InfraSort {
+    #[graphql(default)]
+    pub field: InfraSortField,
+    #[graphql(default)]
+    pub order: SortOrder,
+}
+
+/// Compares like `name_contains` matches, names which differ in case only are equal.
+fn cmp_ignore_case(a: &str, b: &str) -> Ordering {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .cmp(b.chars().flat_map(char::to_lowercase))
+}
+
+/// Sorts the items by `sort`, or by id if no sort is given, to get a deterministic order.
+pub fn sort_infra_items<T: InfraItem>(items: &mut [Arc<T>], sort: Option<InfraSort>) {
+    items.sort_by(|a, b| {
+        let ordering = match sort {
+            Some(InfraSort {
+                field: InfraSortField::Name,
+                order,
+            }) => order.apply(cmp_ignore_case(a.name(), b.name())),
+            Some(InfraSort {
+                field: InfraSortField::CreatedAt,
+                order,
+            }) => order.apply(a.created_at().cmp(b.created_at())),
+            None => Ordering::Equal,
+        };
+        ordering.then_with(|| a.id().cmp(&b.id()))
+    });
+}
+
+/// Field filter of the user list.
+///
+/// Keycloak users have no type and no creator, so only the name, the state and the creation
+/// time can be filtered. Users without a creation time do not match a creation time range.
+#[derive(Debug, Default, Clone, InputObject)]
+pub struct UserQuery {
+    /// Case insensitive part of the username, email, firstname or lastname.
+    pub name_contains: Option<String>,
+    pub enabled: Option<bool>,
+    pub created_after: Option<PrimitiveDateTime>,
+    pub created_before: Option<PrimitiveDateTime>,
+}
+
+impl UserQuery {
+    pub fn matches(&self, item: &UserDetails) -> bool {
+        let user = &item.user;
+        self.name_contains.as_ref().map_or(true, |name| {
+            let name = name.to_lowercase();
+            [&user.username, &user.email, &user.firstname, &user.lastname]
+                .iter()
+                .any(|v| v.to_lowercase().contains(&name))
+        }) && self.enabled.map_or(true, |enabled| user.enabled == enabled)
+            && self.created_after.as_ref().map_or(true, |after| {
+                user.created_at.as_ref().is_some_and(|v| v >= after)
+            })
+            && self.created_before.as_ref().map_or(true, |before| {
+                user.created_at.as_ref().is_some_and(|v| v < before)
+            })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Enum, Eq, PartialEq)]
+pub enum UserSortField {
+    #[default]
+    Username,
+    Email,
+    Firstname,
+    Lastname,
+    CreatedAt,
+}
+
+#[derive(Debug, Default, Clone, Copy, InputObject)]
+pub struct UserSort {
+    #[graphql(default)]
+    pub field: UserSortField,
+    #[graphql(default)]
+    pub order: SortOrder,
+}
+
+/// Sorts the users by `sort`, or by id if no sort is given, to get a deterministic order.
+pub fn sort_users(items: &mut [UserDetails], sort: Option<UserSort>) {
+    items.sort_by(|a, b| {
+        let (a, b) = (&a.user, &b.user);
+        let ordering = match sort {
+            Some(UserSort { field, order }) => order.apply(match field {
+                UserSortField::Username => cmp_ignore_case(&a.username, &b.username),
+                UserSortField::Email => cmp_ignore_case(&a.email, &b.email),
+                UserSortField::Firstname => cmp_ignore_case(&a.firstname, &b.firstname),
+                UserSortField::Lastname => cmp_ignore_case(&a.lastname, &b.lastname),
+                UserSortField::CreatedAt => a.created_at.cmp(&b.created_at),
+            }),
+            None => Ordering::Equal,
+        };
+        ordering.then_with(|| a.id.cmp(&b.id))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sqlx::types::uuid::Uuid;
+    use time::macros::datetime;
+    use time::PrimitiveDateTime;
+
+    use super::*;
+    use crate::model::User;
+
+    fn customer(id: i64, name: &str, ty: &str, created_at: PrimitiveDateTime) -> Arc<Customer> {
+        Arc::new(Customer {
+            id: id.into(),
+            name: Arc::from(name),
+            ty: Arc::from(ty),
+            created_by: Uuid::from_u128(id as u128),
+            created_at,
+            updated_by: None,
+            updated_at: None,
+        })
+    }
+
+    fn customers() -> Vec<Arc<Customer>> {
+        vec![
+            customer(3, "Beta", "none", datetime!(2024-01-03 0:00)),
+            customer(1, "alpha", "clinic", datetime!(2024-01-01 0:00)),
+            customer(2, "Gamma", "none", datetime!(2024-01-02 0:00)),
+            customer(4, "Alphabet", "clinic", datetime!(2024-01-04 0:00)),
+        ]
+    }
+
+    fn ids<T: InfraItem>(items: &[Arc<T>]) -> Vec<i64> {
+        items.iter().map(|item| item.id().into()).collect()
+    }
+
+    fn filtered(query: InfraQuery) -> Vec<i64> {
+        let items: Vec<_> = customers()
+            .into_iter()
+            .filter(|item| query.matches(item.as_ref()))
+            .collect();
+        let mut ids = ids(&items);
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn infra_query_test() {
+        assert_eq!(filtered(InfraQuery::default()), vec![1, 2, 3, 4]);
+        let query = InfraQuery {
+            name_contains: Some("ALPHA".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(filtered(query), vec![1, 4]);
+        let query = InfraQuery {
+            ty: Some("none".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(filtered(query), vec![2, 3]);
+        let query = InfraQuery {
+            created_by: Some(Uuid::from_u128(2)),
+            ..Default::default()
+        };
+        assert_eq!(filtered(query), vec![2]);
+        // The range includes the start and excludes the end.
+        let query = InfraQuery {
+            created_after: Some(datetime!(2024-01-02 0:00)),
+            created_before: Some(datetime!(2024-01-04 0:00)),
+            ..Default::default()
+        };
+        assert_eq!(filtered(query), vec![2, 3]);
+        let query = InfraQuery {
+            name_contains: Some("alpha".to_string()),
+            ty: Some("none".to_string()),
+            ..Default::default()
+        };
+        assert!(filtered(query).is_empty());
+    }
+
+    #[test]
+    fn sort_infra_items_test() {
+        let mut items = customers();
+        sort_infra_items(&mut items, None);
+        assert_eq!(ids(&items), vec![1, 2, 3, 4]);
+        sort_infra_items(
+            &mut items,
+            Some(InfraSort {
+                field: InfraSortField::Name,
+                order: SortOrder::Asc,
+            }),
+        );
+        assert_eq!(ids(&items), vec![1, 4, 3, 2]);
+        sort_infra_items(
+            &mut items,
+            Some(InfraSort {
+                field: InfraSortField::CreatedAt,
+                order: SortOrder::Desc,
+            }),
+        );
+        assert_eq!(ids(&items), vec![4, 3, 2, 1]);
+        sort_infra_items(
+            &mut items,
+            Some(InfraSort {
+                field: InfraSortField::CreatedAt,
+                order: SortOrder::Asc,
+            }),
+        );
+        assert_eq!(ids(&items), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn sort_infra_items_ties_by_id_test() {
+        let mut items = vec![
+            customer(2, "same", "none", datetime!(2024-01-01 0:00)),
+            customer(1, "Same", "none", datetime!(2024-01-01 0:00)),
+        ];
+        sort_infra_items(
+            &mut items,
+            Some(InfraSort {
+                field: InfraSortField::Name,
+                order: SortOrder::Desc,
+            }),
+        );
+        assert_eq!(ids(&items), vec![1, 2]);
+    }
+
+    fn user(
+        id: &str,
+        username: &str,
+        lastname: &str,
+        enabled: bool,
+        created_at: Option<PrimitiveDateTime>,
+    ) -> UserDetails {
+        UserDetails {
+            user: Arc::new(User {
+                id: Arc::from(id),
+                username: Arc::from(username),
+                email: Arc::from(format!("{username}@example.com").as_str()),
+                firstname: Arc::from("Max"),
+                lastname: Arc::from(lastname),
+                enabled,
+                created_at,
+            }),
+            context: None,
+            access: None,
+            group: None,
+        }
+    }
+
+    fn users() -> Vec<UserDetails> {
+        vec![
+            user("b", "jdoe", "Doe", true, Some(datetime!(2024-01-02 0:00))),
+            user("a", "mmuster", "Mustermann", false, None),
+            user(
+                "c",
+                "asmith",
+                "Smith",
+                true,
+                Some(datetime!(2024-01-01 0:00)),
+            ),
+        ]
+    }
+
+    fn user_ids(items: &[UserDetails]) -> Vec<&str> {
+        items.iter().map(|item| item.user.id.as_ref()).collect()
+    }
+
+    #[test]
+    fn user_query_test() {
+        let matching = |query: UserQuery| -> Vec<String> {
+            users()
+                .iter()
+                .filter(|item| query.matches(item))
+                .map(|item| item.user.id.to_string())
+                .collect()
+        };
+        assert_eq!(matching(UserQuery::default()), vec!["b", "a", "c"]);
+        let query = UserQuery {
+            name_contains: Some("MUSTER".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(matching(query), vec!["a"]);
+        let query = UserQuery {
+            name_contains: Some("smith@example".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(matching(query), vec!["c"]);
+        let query = UserQuery {
+            enabled: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(matching(query), vec!["a"]);
+        let query = UserQuery {
+            created_after: Some(datetime!(2024-01-01 0:00)),
+            created_before: Some(datetime!(2024-01-02 0:00)),
+            ..Default::default()
+        };
+        assert_eq!(matching(query), vec!["c"]);
+        let query = UserQuery {
+            created_after: Some(datetime!(2024-01-01 0:00)),
+            ..Default::default()
+        };
+        assert_eq!(matching(query), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn sort_users_test() {
+        let mut items = users();
+        sort_users(&mut items, None);
+        assert_eq!(user_ids(&items), vec!["a", "b", "c"]);
+        sort_users(
+            &mut items,
+            Some(UserSort {
+                field: UserSortField::Username,
+                order: SortOrder::Asc,
+            }),
+        );
+        assert_eq!(user_ids(&items), vec!["c", "b", "a"]);
+        sort_users(
+            &mut items,
+            Some(UserSort {
+                field: UserSortField::Lastname,
+                order: SortOrder::Desc,
+            }),
+        );
+        assert_eq!(user_ids(&items), vec!["c", "a", "b"]);
+        sort_users(
+            &mut items,
+            Some(UserSort {
+                field: UserSortField::CreatedAt,
+                order: SortOrder::Asc,
+            }),
+        );
+        assert_eq!(user_ids(&items), vec!["a", "c", "b"]);
+    }
+}
@@ -47,6 +47,8 @@ pub struct InstitutionUpdate {
     pub created_at: String,
     pub updated_by: Option<Uuid>,
     pub updated_at: Option<String>,
+    pub deleted_by: Option<Uuid>,
+    pub deleted_at: Option<String>,
 }
 
 pub struct RemoveInstitutionPayload {
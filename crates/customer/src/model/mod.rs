@@ -1,4 +1,6 @@
 mod customer;
+pub mod filter;
+pub mod search;
 pub use customer::*;
 mod institution;
 pub use institution::*;
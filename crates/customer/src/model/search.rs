@@ -0,0 +1,125 @@
+use async_graphql::{Enum, SimpleObject};
+use qm_entity::ids::{CustomerId, InfraContext, InstitutionId, OrganizationId, OrganizationUnitId};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, Enum, Eq, PartialEq)]
+pub enum SearchHitType {
+    Customer,
+    Organization,
+    OrganizationUnit,
+    Institution,
+}
+
+impl SearchHitType {
+    /// Kind of the hit in the search query.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SearchHitType::Customer => "customer",
+            SearchHitType::Organization => "organization",
+            SearchHitType::OrganizationUnit => "organization_unit",
+            SearchHitType::Institution => "institution",
+        }
+    }
+}
+
+pub struct SearchHitQuery {
+    pub kind: String,
+    pub id: i64,
+    pub customer_id: i64,
+    pub organization_id: Option<i64>,
+    pub name: Arc<str>,
+    pub rank: f64,
+}
+
+/// Entity found by the name search.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct SearchHit {
+    pub ty: SearchHitType,
+    /// Id of the entity, e.g. a `CustomerId` or `InstitutionId`.
+    pub id: String,
+    pub name: Arc<str>,
+    pub rank: f64,
+}
+
+impl TryFrom<SearchHitQuery> for SearchHit {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SearchHitQuery) -> Result<Self, Self::Error> {
+        let (ty, context) = match (value.kind.as_str(), value.organization_id) {
+            ("customer", _) => (
+                SearchHitType::Customer,
+                InfraContext::Customer(CustomerId::from(value.id)),
+            ),
+            ("organization", _) => (
+                SearchHitType::Organization,
+                InfraContext::Organization(OrganizationId::from((value.customer_id, value.id))),
+            ),
+            ("organization_unit", Some(oid)) => (
+                SearchHitType::OrganizationUnit,
+                InfraContext::OrganizationUnit(OrganizationUnitId::from((
+                    value.customer_id,
+                    oid,
+                    value.id,
+                ))),
+            ),
+            ("organization_unit", None) => (
+                SearchHitType::OrganizationUnit,
+                InfraContext::OrganizationUnit(OrganizationUnitId::from((
+                    value.customer_id,
+                    value.id,
+                ))),
+            ),
+            ("institution", Some(oid)) => (
+                SearchHitType::Institution,
+                InfraContext::Institution(InstitutionId::from((value.customer_id, oid, value.id))),
+            ),
+            (kind, _) => anyhow::bail!("invalid search hit '{kind}'"),
+        };
+        Ok(Self {
+            ty,
+            id: context.to_string(),
+            name: value.name,
+            rank: value.rank,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{SearchHit, SearchHitQuery, SearchHitType};
+
+    fn hit(ty: SearchHitType, organization_id: Option<i64>) -> anyhow::Result<SearchHit> {
+        SearchHit::try_from(SearchHitQuery {
+            kind: ty.kind().to_string(),
+            id: 3,
+            customer_id: 1,
+            organization_id,
+            name: Arc::from("name"),
+            rank: 1.0,
+        })
+    }
+
+    #[test]
+    fn search_hit_kind_test() -> anyhow::Result<()> {
+        assert_eq!(
+            hit(SearchHitType::Customer, None)?.ty,
+            SearchHitType::Customer
+        );
+        assert_eq!(
+            hit(SearchHitType::Organization, None)?.ty,
+            SearchHitType::Organization
+        );
+        assert_eq!(
+            hit(SearchHitType::OrganizationUnit, None)?.ty,
+            SearchHitType::OrganizationUnit
+        );
+        assert_eq!(
+            hit(SearchHitType::Institution, Some(2))?.ty,
+            SearchHitType::Institution
+        );
+        assert!(hit(SearchHitType::Institution, None).is_err());
+        Ok(())
+    }
+}
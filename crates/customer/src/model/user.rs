@@ -5,6 +5,7 @@ use sqlx::types::Uuid;
 use sqlx::FromRow;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use time::{OffsetDateTime, PrimitiveDateTime};
 
 use super::GroupDetail;
 
@@ -17,6 +18,7 @@ pub struct UserEntityUpdate {
     pub last_name: Option<Arc<str>>,
     pub realm_id: Option<Arc<str>>,
     pub enabled: bool,
+    pub created_timestamp: Option<i64>,
 }
 
 impl UserEntityUpdate {
@@ -45,6 +47,7 @@ pub struct KcUserQuery {
     pub username: Option<String>,
     pub email: Option<String>,
     pub enabled: bool,
+    pub created_timestamp: Option<i64>,
 }
 
 impl KcUserQuery {
@@ -93,12 +96,21 @@ impl KcUserRoleQuery {
 pub enum RequiredUserAction {
     #[graphql(name = "UPDATE_PASSWORD")]
     UpdatePassword,
+    #[graphql(name = "VERIFY_EMAIL")]
+    VerifyEmail,
+    #[graphql(name = "UPDATE_PROFILE")]
+    UpdateProfile,
+    #[graphql(name = "CONFIGURE_TOTP")]
+    ConfigureTotp,
 }
 
 impl std::fmt::Display for RequiredUserAction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match self {
             RequiredUserAction::UpdatePassword => "UPDATE_PASSWORD",
+            RequiredUserAction::VerifyEmail => "VERIFY_EMAIL",
+            RequiredUserAction::UpdateProfile => "UPDATE_PROFILE",
+            RequiredUserAction::ConfigureTotp => "CONFIGURE_TOTP",
         }
         .to_string();
         write!(f, "{}", str)
@@ -111,7 +123,8 @@ pub struct CreateUserInput {
     pub username: String,
     pub firstname: String,
     pub lastname: String,
-    pub password: String,
+    /// Users without password get an invite email to set their password.
+    pub password: Option<String>,
     pub email: String,
     pub phone: Option<String>,
     pub salutation: Option<String>,
@@ -129,6 +142,101 @@ pub struct CreateUserPayload {
     pub context: Option<InfraContext>,
 }
 
+/// Changes of a user, fields which are not set stay unchanged. An empty
+/// attribute value removes the attribute.
+#[derive(Default, serde::Deserialize, serde::Serialize, Debug, Clone, InputObject)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateUserInput {
+    pub firstname: Option<String>,
+    pub lastname: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub salutation: Option<String>,
+    pub fax: Option<String>,
+    pub room_number: Option<String>,
+    pub job_title: Option<String>,
+    pub enabled: Option<bool>,
+    pub required_actions: Option<Vec<RequiredUserAction>>,
+}
+
+#[derive(Debug)]
+pub struct UpdateUserPayload {
+    pub id: String,
+    pub user: UpdateUserInput,
+    pub group_id: Option<String>,
+    pub access: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Enum, Eq, PartialEq)]
+pub enum UserImportFormat {
+    Csv,
+    Json,
+}
+
+/// Row of a user import, `accessLevel` (e.g. `customer_unit`) and `context`
+/// are parsed and validated per row so that invalid values are reported as
+/// result of the row.
+#[derive(Default, serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UserImportRow {
+    pub username: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub email: String,
+    /// Only read to reject rows with a password, imported users are always
+    /// invited and set their password on their own.
+    pub password: Option<String>,
+    pub phone: Option<String>,
+    pub salutation: Option<String>,
+    pub room_number: Option<String>,
+    pub job_title: Option<String>,
+    pub access_level: String,
+    pub context: Option<String>,
+    pub group_id: Option<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, Enum, Eq, PartialEq)]
+pub enum UserImportStatus {
+    Pending,
+    Running,
+    Completed,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, Enum, Eq, PartialEq)]
+pub enum UserImportRowStatus {
+    /// Row is valid and waits for the background job.
+    Pending,
+    /// Row was rejected by the validation, no user was created.
+    Invalid,
+    Created,
+    Failed,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, SimpleObject)]
+pub struct UserImportRowResult {
+    /// Index of the row in the imported data, starting at `1`.
+    pub row: usize,
+    pub username: String,
+    pub status: UserImportRowStatus,
+    pub user_id: Option<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, SimpleObject)]
+pub struct UserImportJob {
+    pub id: Uuid,
+    pub status: UserImportStatus,
+    pub results: Vec<UserImportRowResult>,
+    /// User who started the import.
+    #[graphql(skip)]
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+    /// Context of the user who started the import, `None` for admins.
+    #[graphql(skip)]
+    #[serde(default)]
+    pub context: Option<String>,
+}
+
 #[derive(Debug, Clone, SimpleObject)]
 pub struct User {
     pub id: Arc<str>,
@@ -137,6 +245,15 @@ pub struct User {
     pub firstname: Arc<str>,
     pub lastname: Arc<str>,
     pub enabled: bool,
+    /// Creation time in UTC, `None` for users Keycloak has no timestamp of.
+    pub created_at: Option<PrimitiveDateTime>,
+}
+
+/// Converts the `created_timestamp` of Keycloak, milliseconds since the epoch, to UTC.
+pub fn created_at_from_millis(millis: Option<i64>) -> Option<PrimitiveDateTime> {
+    let created_at =
+        OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis?) * 1_000_000).ok()?;
+    Some(PrimitiveDateTime::new(created_at.date(), created_at.time()))
 }
 
 pub type UserMap = HashMap<Arc<str>, Arc<User>>;
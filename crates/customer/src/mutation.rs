@@ -1,21 +1,62 @@
+use crate::cleanup::CleanupTask;
 use crate::model::*;
 use qm_entity::ids::{InfraId, InstitutionIds};
 use sqlx::types::Uuid;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgExecutor, PgPool};
 use std::sync::Arc;
+use time::PrimitiveDateTime;
 
 pub const DEFAULT_TYPE: &str = "none";
 
-pub async fn create_customer(
-    pool: &PgPool,
+/// Result of a soft delete.
+pub struct Removal {
+    pub count: u64,
+    /// Time of the deletion, also set on the deleted children.
+    pub deleted_at: PrimitiveDateTime,
+}
+
+impl Removal {
+    fn new(ids: &[i64], deleted_at: Option<PrimitiveDateTime>) -> Option<Self> {
+        deleted_at.map(|deleted_at| Self {
+            count: ids.len() as u64,
+            deleted_at,
+        })
+    }
+}
+
+async fn next_id<'e, E>(executor: E, sequence: &str) -> anyhow::Result<i64>
+where
+    E: PgExecutor<'e>,
+{
+    Ok(
+        sqlx::query_scalar!(r#"SELECT nextval($1::text::regclass) AS "id!""#, sequence)
+            .fetch_one(executor)
+            .await?,
+    )
+}
+
+/// Reserves the id of a new customer, so that its roles can be provisioned before the row is written.
+pub async fn next_customer_id<'e, E>(executor: E) -> anyhow::Result<i64>
+where
+    E: PgExecutor<'e>,
+{
+    next_id(executor, "customers_id_seq").await
+}
+
+pub async fn create_customer<'e, E>(
+    executor: E,
+    id: i64,
     name: &str,
     ty: Option<&str>,
     created_by: &Uuid,
-) -> anyhow::Result<Customer> {
+) -> anyhow::Result<Customer>
+where
+    E: PgExecutor<'e>,
+{
     let rec = sqlx::query!(
         r#"
-INSERT INTO customers ( name, ty, created_by )
-VALUES ( $1, $2, $3 )
+INSERT INTO customers ( id, name, ty, created_by )
+VALUES ( $1, $2, $3, $4 )
 RETURNING
     id,
     name,
@@ -25,11 +66,12 @@ RETURNING
     updated_by,
     updated_at
 "#,
+        id,
         name,
         ty.unwrap_or(DEFAULT_TYPE),
         created_by
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?;
 
     Ok(Customer {
@@ -90,28 +132,172 @@ pub async fn remove_customer(pool: &PgPool, id: InfraId) -> anyhow::Result<u64>
     )
 }
 
-pub async fn remove_customers(pool: &PgPool, ids: &[i64]) -> anyhow::Result<u64> {
+/// Marks the customers and all of their organizations, institutions and
+/// organization units as deleted.
+pub async fn remove_customers(
+    pool: &PgPool,
+    ids: &[i64],
+    deleted_by: &Uuid,
+) -> anyhow::Result<Option<Removal>> {
+    let mut tx = pool.begin().await?;
+    let recs = sqlx::query!(
+        r#"
+UPDATE customers
+SET deleted_by = $2, deleted_at = NOW()
+WHERE id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NULL
+RETURNING id, deleted_at AS "deleted_at!"
+"#,
+        &ids[..] as &[i64],
+        deleted_by
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let deleted_at = recs.first().map(|r| r.deleted_at);
+    let ids: Vec<i64> = recs.into_iter().map(|r| r.id).collect();
+    sqlx::query!(
+        "UPDATE organizations SET deleted_by = $2, deleted_at = NOW() WHERE customer_id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NULL",
+        &ids[..] as &[i64],
+        deleted_by
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE institutions SET deleted_by = $2, deleted_at = NOW() WHERE customer_id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NULL",
+        &ids[..] as &[i64],
+        deleted_by
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE organization_units SET deleted_by = $2, deleted_at = NOW() WHERE customer_id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NULL",
+        &ids[..] as &[i64],
+        deleted_by
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Removal::new(&ids, deleted_at))
+}
+
+/// Restores the customers and the children which were deleted together with them.
+pub async fn restore_customers(pool: &PgPool, ids: &[i64]) -> anyhow::Result<u64> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        "UPDATE organizations AS v SET deleted_by = NULL, deleted_at = NULL FROM customers AS p WHERE v.customer_id = p.id AND p.id IN (SELECT UNNEST($1::int8[])) AND v.deleted_at = p.deleted_at",
+        &ids[..] as &[i64]
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE institutions AS v SET deleted_by = NULL, deleted_at = NULL FROM customers AS p WHERE v.customer_id = p.id AND p.id IN (SELECT UNNEST($1::int8[])) AND v.deleted_at = p.deleted_at",
+        &ids[..] as &[i64]
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE organization_units AS v SET deleted_by = NULL, deleted_at = NULL FROM customers AS p WHERE v.customer_id = p.id AND p.id IN (SELECT UNNEST($1::int8[])) AND v.deleted_at = p.deleted_at",
+        &ids[..] as &[i64]
+    )
+    .execute(&mut *tx)
+    .await?;
     let result = sqlx::query!(
-        "DELETE FROM customers WHERE id IN (SELECT UNNEST($1::int8[]))",
+        "UPDATE customers SET deleted_by = NULL, deleted_at = NULL WHERE id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NOT NULL",
         &ids[..] as &[i64]
     )
-    .execute(pool)
+    .execute(&mut *tx)
     .await?
-    .rows_affected() as u64;
+    .rows_affected();
+    tx.commit().await?;
     Ok(result)
 }
 
-pub async fn create_organization(
-    pool: &PgPool,
+/// Deletes the customers which are still marked as deleted at `deleted_at`
+/// and returns their ids.
+pub async fn purge_customers<'e, E>(
+    executor: E,
+    ids: &[i64],
+    deleted_at: PrimitiveDateTime,
+) -> anyhow::Result<Vec<i64>>
+where
+    E: PgExecutor<'e>,
+{
+    Ok(sqlx::query_scalar!(
+        "DELETE FROM customers WHERE id IN (SELECT UNNEST($1::int8[])) AND deleted_at = $2 RETURNING id",
+        &ids[..] as &[i64],
+        deleted_at
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Stores the cleanup task of the entities purged by the task `id`, use the
+/// executor of the transaction that deletes the rows.
+pub async fn add_purged_cleanup_task<'e, E>(
+    executor: E,
+    id: &Uuid,
+    task: &CleanupTask,
+) -> anyhow::Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query!(
+        "INSERT INTO purged_cleanup_tasks ( id, task ) VALUES ( $1, $2 ) ON CONFLICT (id) DO NOTHING",
+        id,
+        serde_json::to_string(task)?
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Cleanup task of the entities purged by the task `id` which is not queued yet.
+pub async fn purged_cleanup_task<'e, E>(
+    executor: E,
+    id: &Uuid,
+) -> anyhow::Result<Option<CleanupTask>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_scalar!("SELECT task FROM purged_cleanup_tasks WHERE id = $1", id)
+        .fetch_optional(executor)
+        .await?
+        .map(|task| Ok(serde_json::from_str(&task)?))
+        .transpose()
+}
+
+pub async fn remove_purged_cleanup_task<'e, E>(executor: E, id: &Uuid) -> anyhow::Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query!("DELETE FROM purged_cleanup_tasks WHERE id = $1", id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Reserves the id of a new organization, so that its roles can be provisioned before the row is written.
+pub async fn next_organization_id<'e, E>(executor: E) -> anyhow::Result<i64>
+where
+    E: PgExecutor<'e>,
+{
+    next_id(executor, "organizations_id_seq").await
+}
+
+pub async fn create_organization<'e, E>(
+    executor: E,
+    id: i64,
     name: &str,
     ty: Option<&str>,
     customer_id: InfraId,
     created_by: &Uuid,
-) -> anyhow::Result<Organization> {
+) -> anyhow::Result<Organization>
+where
+    E: PgExecutor<'e>,
+{
     let rec = sqlx::query!(
         r#"
-INSERT INTO organizations ( name, ty, customer_id, created_by )
-VALUES ( $1, $2, $3, $4 )
+INSERT INTO organizations ( id, name, ty, customer_id, created_by )
+VALUES ( $1, $2, $3, $4, $5 )
 RETURNING
     id,
     customer_id,
@@ -122,12 +308,13 @@ RETURNING
     updated_by,
     updated_at
 "#,
+        id,
         name,
         ty.unwrap_or(DEFAULT_TYPE),
         customer_id.as_ref(),
         created_by
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?;
 
     Ok(Organization {
@@ -191,29 +378,116 @@ pub async fn remove_organization(pool: &PgPool, id: InfraId) -> anyhow::Result<u
     )
 }
 
-pub async fn remove_organizations(pool: &PgPool, ids: &[i64]) -> anyhow::Result<u64> {
+/// Marks the organizations and all of their institutions and organization
+/// units as deleted.
+pub async fn remove_organizations(
+    pool: &PgPool,
+    ids: &[i64],
+    deleted_by: &Uuid,
+) -> anyhow::Result<Option<Removal>> {
+    let mut tx = pool.begin().await?;
+    let recs = sqlx::query!(
+        r#"
+UPDATE organizations
+SET deleted_by = $2, deleted_at = NOW()
+WHERE id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NULL
+RETURNING id, deleted_at AS "deleted_at!"
+"#,
+        &ids[..] as &[i64],
+        deleted_by
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let deleted_at = recs.first().map(|r| r.deleted_at);
+    let ids: Vec<i64> = recs.into_iter().map(|r| r.id).collect();
+    sqlx::query!(
+        "UPDATE institutions SET deleted_by = $2, deleted_at = NOW() WHERE organization_id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NULL",
+        &ids[..] as &[i64],
+        deleted_by
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE organization_units SET deleted_by = $2, deleted_at = NOW() WHERE organization_id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NULL",
+        &ids[..] as &[i64],
+        deleted_by
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Removal::new(&ids, deleted_at))
+}
+
+/// Restores the organizations and the children which were deleted together
+/// with them, organizations of deleted customers are not restored.
+pub async fn restore_organizations(pool: &PgPool, ids: &[i64]) -> anyhow::Result<u64> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        "UPDATE institutions AS v SET deleted_by = NULL, deleted_at = NULL FROM organizations AS p JOIN customers AS c ON c.id = p.customer_id WHERE v.organization_id = p.id AND p.id IN (SELECT UNNEST($1::int8[])) AND v.deleted_at = p.deleted_at AND c.deleted_at IS NULL",
+        &ids[..] as &[i64]
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE organization_units AS v SET deleted_by = NULL, deleted_at = NULL FROM organizations AS p JOIN customers AS c ON c.id = p.customer_id WHERE v.organization_id = p.id AND p.id IN (SELECT UNNEST($1::int8[])) AND v.deleted_at = p.deleted_at AND c.deleted_at IS NULL",
+        &ids[..] as &[i64]
+    )
+    .execute(&mut *tx)
+    .await?;
     let result = sqlx::query!(
-        "DELETE FROM organizations WHERE id IN (SELECT UNNEST($1::int8[]))",
+        "UPDATE organizations AS v SET deleted_by = NULL, deleted_at = NULL FROM customers AS c WHERE c.id = v.customer_id AND v.id IN (SELECT UNNEST($1::int8[])) AND v.deleted_at IS NOT NULL AND c.deleted_at IS NULL",
         &ids[..] as &[i64]
     )
-    .execute(pool)
+    .execute(&mut *tx)
     .await?
-    .rows_affected() as u64;
+    .rows_affected();
+    tx.commit().await?;
     Ok(result)
 }
 
-pub async fn create_institution(
-    pool: &PgPool,
+/// Deletes the organizations which are still marked as deleted at
+/// `deleted_at` and returns their ids.
+pub async fn purge_organizations<'e, E>(
+    executor: E,
+    ids: &[i64],
+    deleted_at: PrimitiveDateTime,
+) -> anyhow::Result<Vec<i64>>
+where
+    E: PgExecutor<'e>,
+{
+    Ok(sqlx::query_scalar!(
+        "DELETE FROM organizations WHERE id IN (SELECT UNNEST($1::int8[])) AND deleted_at = $2 RETURNING id",
+        &ids[..] as &[i64],
+        deleted_at
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Reserves the id of a new institution, so that its roles can be provisioned before the row is written.
+pub async fn next_institution_id<'e, E>(executor: E) -> anyhow::Result<i64>
+where
+    E: PgExecutor<'e>,
+{
+    next_id(executor, "institutions_id_seq").await
+}
+
+pub async fn create_institution<'e, E>(
+    executor: E,
+    id: i64,
     name: &str,
     ty: Option<&str>,
     customer_id: InfraId,
     organization_id: InfraId,
     created_by: &Uuid,
-) -> anyhow::Result<Institution> {
+) -> anyhow::Result<Institution>
+where
+    E: PgExecutor<'e>,
+{
     let rec = sqlx::query!(
         r#"
-INSERT INTO institutions ( name, ty, customer_id, organization_id, created_by )
-VALUES ( $1, $2, $3, $4, $5 )
+INSERT INTO institutions ( id, name, ty, customer_id, organization_id, created_by )
+VALUES ( $1, $2, $3, $4, $5, $6 )
 RETURNING
     id,
     customer_id,
@@ -225,13 +499,14 @@ RETURNING
     updated_by,
     updated_at
 "#,
+        id,
         name,
         ty.unwrap_or(DEFAULT_TYPE),
         customer_id.as_ref(),
         organization_id.as_ref(),
         created_by
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?;
 
     Ok(Institution {
@@ -298,19 +573,71 @@ pub async fn remove_institution(pool: &PgPool, id: InfraId) -> anyhow::Result<u6
     )
 }
 
-pub async fn remove_institutions(pool: &PgPool, ids: &[i64]) -> anyhow::Result<u64> {
-    let result = sqlx::query!(
-        "DELETE FROM institutions WHERE id IN (SELECT UNNEST($1::int8[]))",
+/// Marks the institutions as deleted.
+pub async fn remove_institutions(
+    pool: &PgPool,
+    ids: &[i64],
+    deleted_by: &Uuid,
+) -> anyhow::Result<Option<Removal>> {
+    let recs = sqlx::query!(
+        r#"
+UPDATE institutions
+SET deleted_by = $2, deleted_at = NOW()
+WHERE id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NULL
+RETURNING id, deleted_at AS "deleted_at!"
+"#,
+        &ids[..] as &[i64],
+        deleted_by
+    )
+    .fetch_all(pool)
+    .await?;
+    let deleted_at = recs.first().map(|r| r.deleted_at);
+    let ids: Vec<i64> = recs.into_iter().map(|r| r.id).collect();
+    Ok(Removal::new(&ids, deleted_at))
+}
+
+/// Restores the institutions, institutions of deleted organizations are not restored.
+pub async fn restore_institutions(pool: &PgPool, ids: &[i64]) -> anyhow::Result<u64> {
+    Ok(sqlx::query!(
+        "UPDATE institutions AS v SET deleted_by = NULL, deleted_at = NULL FROM organizations AS o WHERE o.id = v.organization_id AND v.id IN (SELECT UNNEST($1::int8[])) AND v.deleted_at IS NOT NULL AND o.deleted_at IS NULL",
         &ids[..] as &[i64]
     )
     .execute(pool)
     .await?
-    .rows_affected() as u64;
-    Ok(result)
+    .rows_affected())
+}
+
+/// Deletes the institutions which are still marked as deleted at
+/// `deleted_at` and returns their ids.
+pub async fn purge_institutions<'e, E>(
+    executor: E,
+    ids: &[i64],
+    deleted_at: PrimitiveDateTime,
+) -> anyhow::Result<Vec<i64>>
+where
+    E: PgExecutor<'e>,
+{
+    Ok(sqlx::query_scalar!(
+        "DELETE FROM institutions WHERE id IN (SELECT UNNEST($1::int8[])) AND deleted_at = $2 RETURNING id",
+        &ids[..] as &[i64],
+        deleted_at
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Reserves the id of a new organization unit, so that its roles can be provisioned before the row is written.
+pub async fn next_organization_unit_id<'e, E>(executor: E) -> anyhow::Result<i64>
+where
+    E: PgExecutor<'e>,
+{
+    next_id(executor, "organization_units_id_seq").await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn create_organization_unit(
-    pool: &PgPool,
+    con: &mut PgConnection,
+    id: i64,
     name: &str,
     ty: Option<&str>,
     customer_id: InfraId,
@@ -320,8 +647,8 @@ pub async fn create_organization_unit(
 ) -> anyhow::Result<OrganizationUnit> {
     let rec = sqlx::query!(
         r#"
-INSERT INTO organization_units ( name, ty, customer_id, organization_id, created_by )
-VALUES ( $1, $2, $3, $4, $5 )
+INSERT INTO organization_units ( id, name, ty, customer_id, organization_id, created_by )
+VALUES ( $1, $2, $3, $4, $5, $6 )
 RETURNING
     id,
     customer_id,
@@ -333,13 +660,14 @@ RETURNING
     updated_by,
     updated_at
 "#,
+        id,
         name,
         ty.unwrap_or(DEFAULT_TYPE),
         customer_id.as_ref(),
         organization_id.as_deref(),
         created_by
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *con)
     .await?;
 
     let organization_unit_ids: Vec<i64> = (0..members.len()).map(|_| rec.id).collect();
@@ -356,7 +684,7 @@ RETURNING
         &organization_ids[..] as &[i64],
         &institution_ids[..] as &[i64],
     )
-        .execute(pool)
+        .execute(&mut *con)
         .await?;
 
     Ok(OrganizationUnit {
@@ -435,13 +763,230 @@ pub async fn remove_organization_unit(pool: &PgPool, id: InfraId) -> anyhow::Res
     )
 }
 
-pub async fn remove_organization_units(pool: &PgPool, ids: &[i64]) -> anyhow::Result<u64> {
-    let result = sqlx::query!(
-        "DELETE FROM organization_units WHERE id IN (SELECT UNNEST($1::int8[]))",
+/// Marks the organization units as deleted.
+pub async fn remove_organization_units(
+    pool: &PgPool,
+    ids: &[i64],
+    deleted_by: &Uuid,
+) -> anyhow::Result<Option<Removal>> {
+    let recs = sqlx::query!(
+        r#"
+UPDATE organization_units
+SET deleted_by = $2, deleted_at = NOW()
+WHERE id IN (SELECT UNNEST($1::int8[])) AND deleted_at IS NULL
+RETURNING id, deleted_at AS "deleted_at!"
+"#,
+        &ids[..] as &[i64],
+        deleted_by
+    )
+    .fetch_all(pool)
+    .await?;
+    let deleted_at = recs.first().map(|r| r.deleted_at);
+    let ids: Vec<i64> = recs.into_iter().map(|r| r.id).collect();
+    Ok(Removal::new(&ids, deleted_at))
+}
+
+/// Restores the organization units, units of deleted customers or
+/// organizations are not restored.
+pub async fn restore_organization_units(pool: &PgPool, ids: &[i64]) -> anyhow::Result<u64> {
+    Ok(sqlx::query!(
+        r#"
+UPDATE organization_units AS v
+SET deleted_by = NULL, deleted_at = NULL
+FROM customers AS c
+WHERE c.id = v.customer_id
+    AND v.id IN (SELECT UNNEST($1::int8[]))
+    AND v.deleted_at IS NOT NULL
+    AND c.deleted_at IS NULL
+    AND (v.organization_id IS NULL OR v.organization_id IN (
+        SELECT o.id FROM organizations o WHERE o.deleted_at IS NULL
+    ))
+"#,
         &ids[..] as &[i64]
     )
     .execute(pool)
     .await?
-    .rows_affected() as u64;
-    Ok(result)
+    .rows_affected())
+}
+
+/// Deletes the organization units which are still marked as deleted at
+/// `deleted_at` and returns their ids.
+pub async fn purge_organization_units<'e, E>(
+    executor: E,
+    ids: &[i64],
+    deleted_at: PrimitiveDateTime,
+) -> anyhow::Result<Vec<i64>>
+where
+    E: PgExecutor<'e>,
+{
+    Ok(sqlx::query_scalar!(
+        "DELETE FROM organization_units WHERE id IN (SELECT UNNEST($1::int8[])) AND deleted_at = $2 RETURNING id",
+        &ids[..] as &[i64],
+        deleted_at
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+/// Tests against a database created from the customer migrations, run them with
+/// `DATABASE_URL` set: `cargo test -p qm-customer -- --ignored`.
+#[cfg(test)]
+mod tests {
+    use qm_entity::ids::InstitutionIds;
+    use sqlx::types::Uuid;
+    use sqlx::PgPool;
+    use time::PrimitiveDateTime;
+
+    use super::*;
+    use crate::query::{deleted_customer_exists, deleted_organization_unit_exists};
+
+    async fn deleted_at(pool: &PgPool, table: &str, id: i64) -> Option<PrimitiveDateTime> {
+        sqlx::query_scalar(&format!("SELECT deleted_at FROM {table} WHERE id = $1"))
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn exists(pool: &PgPool, table: &str, id: i64) -> bool {
+        sqlx::query_scalar(&format!(
+            "SELECT EXISTS(SELECT 1 FROM {table} WHERE id = $1)"
+        ))
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    /// Customer with an organization and an institution, returns their ids.
+    async fn customer(pool: &PgPool, user: &Uuid) -> anyhow::Result<(i64, i64, i64)> {
+        let cid = next_customer_id(pool).await?;
+        create_customer(pool, cid, "Customer", None, user).await?;
+        let oid = next_organization_id(pool).await?;
+        create_organization(pool, oid, "Organization", None, cid.into(), user).await?;
+        let iid = next_institution_id(pool).await?;
+        create_institution(pool, iid, "Institution", None, cid.into(), oid.into(), user).await?;
+        Ok((cid, oid, iid))
+    }
+
+    #[sqlx::test(migrations = "./migrations/customer")]
+    #[ignore = "requires DATABASE_URL"]
+    async fn remove_restore_customers_test(pool: PgPool) -> anyhow::Result<()> {
+        let user = Uuid::new_v4();
+        let (cid, oid, iid) = customer(&pool, &user).await?;
+        let removal = remove_customers(&pool, &[cid], &user).await?.unwrap();
+        assert_eq!(removal.count, 1);
+        // children are deleted together with the customer
+        for (table, id) in [
+            ("customers", cid),
+            ("organizations", oid),
+            ("institutions", iid),
+        ] {
+            assert_eq!(deleted_at(&pool, table, id).await, Some(removal.deleted_at));
+        }
+        assert!(remove_customers(&pool, &[cid], &user).await?.is_none());
+        let db = qm_pg::DB::from(pool.clone());
+        assert!(deleted_customer_exists(&db, "Customer").await?);
+
+        assert_eq!(restore_customers(&pool, &[cid]).await?, 1);
+        for (table, id) in [
+            ("customers", cid),
+            ("organizations", oid),
+            ("institutions", iid),
+        ] {
+            assert_eq!(deleted_at(&pool, table, id).await, None);
+        }
+        assert!(!deleted_customer_exists(&db, "Customer").await?);
+        assert_eq!(restore_customers(&pool, &[cid]).await?, 0);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations/customer")]
+    #[ignore = "requires DATABASE_URL"]
+    async fn restore_keeps_children_deleted_before_test(pool: PgPool) -> anyhow::Result<()> {
+        let user = Uuid::new_v4();
+        let (cid, oid, iid) = customer(&pool, &user).await?;
+        let removal = remove_institutions(&pool, &[iid], &user).await?.unwrap();
+        remove_customers(&pool, &[cid], &user).await?;
+        restore_customers(&pool, &[cid]).await?;
+        assert_eq!(deleted_at(&pool, "organizations", oid).await, None);
+        assert_eq!(
+            deleted_at(&pool, "institutions", iid).await,
+            Some(removal.deleted_at)
+        );
+        // institutions of deleted organizations are not restored
+        remove_organizations(&pool, &[oid], &user).await?;
+        assert_eq!(restore_institutions(&pool, &[iid]).await?, 0);
+        restore_organizations(&pool, &[oid]).await?;
+        assert_eq!(restore_institutions(&pool, &[iid]).await?, 1);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations/customer")]
+    #[ignore = "requires DATABASE_URL"]
+    async fn purge_test(pool: PgPool) -> anyhow::Result<()> {
+        let user = Uuid::new_v4();
+        let (cid, oid, iid) = customer(&pool, &user).await?;
+        let removal = remove_organizations(&pool, &[oid], &user).await?.unwrap();
+        // only rows which are still deleted at the time of the removal are purged
+        let other = removal.deleted_at + time::Duration::seconds(1);
+        assert!(purge_organizations(&pool, &[oid], other).await?.is_empty());
+        assert_eq!(
+            purge_institutions(&pool, &[iid], removal.deleted_at).await?,
+            vec![iid]
+        );
+        assert_eq!(
+            purge_organizations(&pool, &[oid], removal.deleted_at).await?,
+            vec![oid]
+        );
+        assert!(!exists(&pool, "organizations", oid).await);
+        assert!(exists(&pool, "customers", cid).await);
+
+        let removal = remove_customers(&pool, &[cid], &user).await?.unwrap();
+        restore_customers(&pool, &[cid]).await?;
+        assert!(purge_customers(&pool, &[cid], removal.deleted_at)
+            .await?
+            .is_empty());
+        assert!(exists(&pool, "customers", cid).await);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = "./migrations/customer")]
+    #[ignore = "requires DATABASE_URL"]
+    async fn remove_restore_purge_organization_units_test(pool: PgPool) -> anyhow::Result<()> {
+        let user = Uuid::new_v4();
+        let (cid, oid, _) = customer(&pool, &user).await?;
+        let uid = next_organization_unit_id(&pool).await?;
+        let mut con = pool.acquire().await?;
+        create_organization_unit(
+            &mut con,
+            uid,
+            "Unit",
+            None,
+            cid.into(),
+            Some(oid.into()),
+            &user,
+            InstitutionIds::default(),
+        )
+        .await?;
+        drop(con);
+        let db = qm_pg::DB::from(pool.clone());
+        assert!(remove_organization_units(&pool, &[uid], &user)
+            .await?
+            .is_some());
+        assert!(deleted_organization_unit_exists(&db, cid, Some(oid), "Unit").await?);
+        assert!(!deleted_organization_unit_exists(&db, cid, None, "Unit").await?);
+        assert_eq!(restore_organization_units(&pool, &[uid]).await?, 1);
+        assert!(!deleted_organization_unit_exists(&db, cid, Some(oid), "Unit").await?);
+
+        let removal = remove_organization_units(&pool, &[uid], &user)
+            .await?
+            .unwrap();
+        assert_eq!(
+            purge_organization_units(&pool, &[uid], removal.deleted_at).await?,
+            vec![uid]
+        );
+        assert!(!exists(&pool, "organization_units", uid).await);
+        Ok(())
+    }
 }
@@ -0,0 +1,292 @@
+use qm_kafka::producer::{Event, EventNs, EventType, Producer};
+use sqlx::types::Uuid;
+use sqlx::{PgExecutor, PgPool};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+pub const DEFAULT_BATCH_SIZE: i64 = 100;
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Stores a mutation event in the outbox, use the executor of the transaction
+/// that writes the mutation so that both are committed or rolled back together.
+///
+/// Events are only stored if a `producer` is configured, without it no relay
+/// drains the outbox and `None` is returned.
+pub async fn add_event<'e, E, T>(
+    producer: Option<&Producer>,
+    executor: E,
+    event_ns: &EventNs,
+    event: EventType,
+    ty: &str,
+    object: &T,
+) -> anyhow::Result<Option<Uuid>>
+where
+    E: PgExecutor<'e>,
+    T: serde::Serialize,
+{
+    if producer.is_none() {
+        return Ok(None);
+    }
+    let id = Uuid::new_v4();
+    let object = serde_json::to_string(object)?;
+    sqlx::query!(
+        r#"
+INSERT INTO mutation_events_outbox ( id, event_ns, event, ty, object )
+VALUES ( $1, $2, $3, $4, $5 )
+"#,
+        id,
+        event_ns.as_ref(),
+        event.as_ref(),
+        ty,
+        object,
+    )
+    .execute(executor)
+    .await?;
+    Ok(Some(id))
+}
+
+/// Restores the event of an outbox row, the row id is used as event id.
+fn to_event(id: &Uuid, event: &str, ty: String, object: &str) -> anyhow::Result<Event> {
+    Ok(Event {
+        id: Some(id.to_string()),
+        event: EventType::from_str(event)?,
+        ty,
+        object: serde_json::from_str(object)?,
+    })
+}
+
+/// Publishes up to `batch_size` pending outbox events in order, returns the number of published events.
+///
+/// Only one relay publishes at a time, it holds a transaction level advisory lock so that
+/// concurrent relays cannot publish later events before earlier ones. Other relays skip the run.
+/// Delivery is at-least-once, consumers can use the `id` of the event for de-duplication.
+pub async fn relay(pool: &PgPool, producer: &Producer, batch_size: i64) -> anyhow::Result<usize> {
+    let mut tx = pool.begin().await?;
+    let locked = sqlx::query_scalar!(
+        r#"SELECT pg_try_advisory_xact_lock(hashtext('mutation_events_outbox')) AS "locked!""#
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if !locked {
+        return Ok(0);
+    }
+    let rows = sqlx::query!(
+        r#"
+SELECT seq, id, event_ns, event, ty, object
+FROM mutation_events_outbox
+ORDER BY seq
+LIMIT $1
+"#,
+        batch_size
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut published: Vec<i64> = Vec::with_capacity(rows.len());
+    for row in rows {
+        let result = async {
+            let event = to_event(&row.id, &row.event, row.ty, &row.object)?;
+            producer.send_event(&row.event_ns, &event).await
+        }
+        .await;
+        match result {
+            Ok(()) => published.push(row.seq),
+            Err(err) => {
+                log::error!("unable to publish outbox event {}: {err:#?}", row.id);
+                sqlx::query!(
+                    r#"
+UPDATE mutation_events_outbox
+SET attempts = attempts + 1, last_error = $2
+WHERE seq = $1
+"#,
+                    row.seq,
+                    err.to_string(),
+                )
+                .execute(&mut *tx)
+                .await?;
+                // Keep the order of the events, the remaining rows are retried in the next run.
+                break;
+            }
+        }
+    }
+    if !published.is_empty() {
+        sqlx::query!(
+            "DELETE FROM mutation_events_outbox WHERE seq = ANY($1)",
+            &published[..]
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(published.len())
+}
+
+struct Inner {
+    pool: PgPool,
+    producer: Producer,
+    batch_size: i64,
+    interval: Duration,
+}
+
+#[derive(Clone)]
+pub struct OutboxRelay {
+    inner: Arc<Inner>,
+}
+
+pub struct OutboxRelayBuilder {
+    pool: PgPool,
+    producer: Producer,
+    batch_size: i64,
+    interval: Duration,
+}
+
+impl OutboxRelayBuilder {
+    pub fn with_batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn build(self) -> OutboxRelay {
+        OutboxRelay {
+            inner: Arc::new(Inner {
+                pool: self.pool,
+                producer: self.producer,
+                batch_size: self.batch_size,
+                interval: self.interval,
+            }),
+        }
+    }
+}
+
+impl OutboxRelay {
+    pub fn builder(pool: PgPool, producer: Producer) -> OutboxRelayBuilder {
+        OutboxRelayBuilder {
+            pool,
+            producer,
+            batch_size: DEFAULT_BATCH_SIZE,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    pub async fn run_once(&self) -> anyhow::Result<usize> {
+        relay(
+            &self.inner.pool,
+            &self.inner.producer,
+            self.inner.batch_size,
+        )
+        .await
+    }
+
+    pub fn start(&self) -> RunningOutboxRelay {
+        let (tx, mut rx) = watch::channel(false);
+        let relay = self.clone();
+        let handle = tokio::spawn(async move {
+            log::info!("start mutation events outbox relay");
+            loop {
+                let count = match relay.run_once().await {
+                    Ok(count) => count,
+                    Err(err) => {
+                        log::error!("unable to relay mutation events: {err:#?}");
+                        0
+                    }
+                };
+                if count as i64 >= relay.inner.batch_size && !*rx.borrow() {
+                    continue;
+                }
+                tokio::select! {
+                    _ = rx.changed() => break,
+                    _ = tokio::time::sleep(relay.inner.interval) => {},
+                }
+            }
+            log::info!("stopped mutation events outbox relay");
+            Ok(())
+        });
+        RunningOutboxRelay { tx, handle }
+    }
+}
+
+pub struct RunningOutboxRelay {
+    tx: watch::Sender<bool>,
+    handle: JoinHandle<anyhow::Result<()>>,
+}
+
+impl RunningOutboxRelay {
+    pub async fn terminate(self) -> anyhow::Result<()> {
+        self.tx.send(true).ok();
+        self.handle.await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_event_test() -> anyhow::Result<()> {
+        let id = Uuid::new_v4();
+        let ids: Vec<i64> = vec![1, 2, 3];
+        // Same encoding as `add_event`
+        let object = serde_json::to_string(&ids)?;
+        for ty in [
+            EventType::Create,
+            EventType::Update,
+            EventType::Delete,
+            EventType::Link,
+        ] {
+            let event = to_event(&id, ty.as_ref(), "customer".to_string(), &object)?;
+            assert_eq!(event.id, Some(id.to_string()));
+            assert_eq!(event.event, ty);
+            assert_eq!(event.ty, "customer");
+            assert_eq!(event.object, serde_json::json!([1, 2, 3]));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn to_event_invalid_test() {
+        let id = Uuid::new_v4();
+        assert!(to_event(&id, "remove", "customer".to_string(), "[]").is_err());
+        assert!(to_event(&id, "delete", "customer".to_string(), "[1,").is_err());
+    }
+
+    /// Runs against a database created from the customer migrations, see `mutation::tests`.
+    #[sqlx::test(migrations = "./migrations/customer")]
+    #[ignore = "requires DATABASE_URL"]
+    async fn relay_lock_test(pool: PgPool) -> anyhow::Result<()> {
+        let producer = Producer::new()?;
+        let ids: Vec<i64> = vec![1];
+        let ns = EventNs::Customer;
+        // Without producer there is no relay, so nothing is stored.
+        let id = add_event(None, &pool, &ns, EventType::Create, "customer", &ids).await?;
+        assert!(id.is_none());
+        let id = add_event(
+            Some(&producer),
+            &pool,
+            &ns,
+            EventType::Create,
+            "customer",
+            &ids,
+        )
+        .await?;
+        assert!(id.is_some());
+        // Another relay holds the lock, the event is kept for it.
+        let mut tx = pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('mutation_events_outbox'))")
+            .execute(&mut *tx)
+            .await?;
+        assert_eq!(relay(&pool, &producer, DEFAULT_BATCH_SIZE).await?, 0);
+        tx.rollback().await?;
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM mutation_events_outbox")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count, 1);
+        Ok(())
+    }
+}
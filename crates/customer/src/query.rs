@@ -1,4 +1,6 @@
+use crate::model::search::{SearchHit, SearchHitQuery, SearchHitType};
 use crate::model::*;
+use qm_entity::ids::InfraContext;
 use qm_pg::DB;
 use sqlx::query_as;
 
@@ -12,7 +14,8 @@ SELECT
     u.last_name AS lastname,
     u.username AS username,
     u.email AS email,
-    u.enabled AS enabled
+    u.enabled AS enabled,
+    u.created_timestamp AS created_timestamp
 FROM realm re
     JOIN user_entity u on re.id = u.realm_id
 WHERE re.name = $1;"#,
@@ -187,7 +190,8 @@ SELECT
     created_at,
     updated_by,
     updated_at
-FROM customers;"#
+FROM customers
+WHERE deleted_at IS NULL;"#
     )
     .fetch_all(db.pool())
     .await?)
@@ -206,7 +210,8 @@ SELECT
     created_at,
     updated_by,
     updated_at
-FROM organizations;"#
+FROM organizations
+WHERE deleted_at IS NULL;"#
     )
     .fetch_all(db.pool())
     .await?)
@@ -226,7 +231,8 @@ SELECT
     created_at,
     updated_by,
     updated_at
-FROM institutions;"#
+FROM institutions
+WHERE deleted_at IS NULL;"#
     )
     .fetch_all(db.pool())
     .await?)
@@ -246,7 +252,8 @@ SELECT
     v.created_at as created_at,
     v.updated_by as updated_by,
     v.updated_at as updated_at
-FROM organization_units v"#
+FROM organization_units v
+WHERE v.deleted_at IS NULL"#
     )
     .fetch_all(db.pool())
     .await?
@@ -254,3 +261,139 @@ FROM organization_units v"#
     .map(Into::into)
     .collect())
 }
+
+/// Prefix search on the names of customers, organizations, organization units and institutions.
+///
+/// Uses the `edge_gram_tsvector(name)` indexes, so every word of `text` can be the start of a
+/// word of the name. Hits are restricted to `context` and ordered by rank, names starting with
+/// `text` first and shorter names before longer ones.
+pub async fn search(
+    db: &DB,
+    text: &str,
+    context: Option<&InfraContext>,
+    types: &[SearchHitType],
+    limit: i64,
+) -> anyhow::Result<Vec<SearchHit>> {
+    let kinds: Vec<&str> = types.iter().map(SearchHitType::kind).collect();
+    let cid = context.map(|c| *c.customer_id());
+    let oid = context.and_then(|c| c.organization_id()).map(|v| *v);
+    let iid = context.and_then(|c| c.institution_id()).map(|v| *v);
+    let uid = context.and_then(|c| c.organization_unit_id()).map(|v| *v);
+    query_as!(
+        SearchHitQuery,
+        r#"
+WITH search AS (SELECT plainto_tsquery($1) AS q)
+SELECT
+    r.kind AS "kind!",
+    r.id AS "id!",
+    r.customer_id AS "customer_id!",
+    r.organization_id,
+    r.name AS "name!",
+    r.rank AS "rank!"
+FROM (
+    SELECT 'customer' AS kind, c.id, c.id AS customer_id, NULL::bigint AS organization_id, c.name,
+        ts_rank(edge_gram_tsvector(c.name), s.q) + starts_with(lower(c.name), lower($1))::int AS rank
+    FROM customers c, search s
+    WHERE edge_gram_tsvector(c.name) @@ s.q
+        AND c.deleted_at IS NULL
+        AND ($2::bigint IS NULL OR c.id = $2)
+        AND $3::bigint IS NULL AND $4::bigint IS NULL AND $5::bigint IS NULL
+    UNION ALL
+    SELECT 'organization', o.id, o.customer_id, o.id, o.name,
+        ts_rank(edge_gram_tsvector(o.name), s.q) + starts_with(lower(o.name), lower($1))::int
+    FROM organizations o, search s
+    WHERE edge_gram_tsvector(o.name) @@ s.q
+        AND o.deleted_at IS NULL
+        AND ($2::bigint IS NULL OR o.customer_id = $2)
+        AND ($3::bigint IS NULL OR o.id = $3)
+        AND $4::bigint IS NULL AND $5::bigint IS NULL
+    UNION ALL
+    SELECT 'organization_unit', u.id, u.customer_id, u.organization_id, u.name,
+        ts_rank(edge_gram_tsvector(u.name), s.q) + starts_with(lower(u.name), lower($1))::int
+    FROM organization_units u, search s
+    WHERE edge_gram_tsvector(u.name) @@ s.q
+        AND u.deleted_at IS NULL
+        AND ($2::bigint IS NULL OR u.customer_id = $2)
+        AND ($3::bigint IS NULL OR u.organization_id = $3)
+        AND $4::bigint IS NULL
+        AND ($5::bigint IS NULL OR u.id = $5)
+    UNION ALL
+    SELECT 'institution', i.id, i.customer_id, i.organization_id, i.name,
+        ts_rank(edge_gram_tsvector(i.name), s.q) + starts_with(lower(i.name), lower($1))::int
+    FROM institutions i, search s
+    WHERE edge_gram_tsvector(i.name) @@ s.q
+        AND i.deleted_at IS NULL
+        AND ($2::bigint IS NULL OR i.customer_id = $2)
+        AND ($3::bigint IS NULL OR i.organization_id = $3)
+        AND ($4::bigint IS NULL OR i.id = $4)
+        AND ($5::bigint IS NULL OR i.id IN (
+            SELECT m.institution_id FROM organization_unit_members m WHERE m.organization_unit_id = $5
+        ))
+) r
+WHERE r.kind = ANY($7)
+ORDER BY r.rank DESC, length(r.name), r.name
+LIMIT $6;"#,
+        text,
+        cid,
+        oid,
+        iid,
+        uid,
+        limit,
+        &kinds[..] as &[&str],
+    )
+    .fetch_all(db.pool())
+    .await?
+    .into_iter()
+    .map(TryInto::try_into)
+    .collect()
+}
+
+/// Whether a deleted customer keeps `name`, deleted rows hold their name until they are purged.
+pub async fn deleted_customer_exists(db: &DB, name: &str) -> anyhow::Result<bool> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM customers WHERE name = $1 AND deleted_at IS NOT NULL) AS "exists!""#,
+        name
+    )
+    .fetch_one(db.pool())
+    .await?)
+}
+
+/// Whether a deleted organization of the customer keeps `name`.
+pub async fn deleted_organization_exists(db: &DB, cid: i64, name: &str) -> anyhow::Result<bool> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM organizations WHERE customer_id = $1 AND name = $2 AND deleted_at IS NOT NULL) AS "exists!""#,
+        cid,
+        name
+    )
+    .fetch_one(db.pool())
+    .await?)
+}
+
+/// Whether a deleted institution of the organization keeps `name`.
+pub async fn deleted_institution_exists(db: &DB, oid: i64, name: &str) -> anyhow::Result<bool> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM institutions WHERE organization_id = $1 AND name = $2 AND deleted_at IS NOT NULL) AS "exists!""#,
+        oid,
+        name
+    )
+    .fetch_one(db.pool())
+    .await?)
+}
+
+/// Whether a deleted organization unit keeps `name`, units of customers have no organization
+/// and do not share the unique constraint.
+pub async fn deleted_organization_unit_exists(
+    db: &DB,
+    cid: i64,
+    oid: Option<i64>,
+    name: &str,
+) -> anyhow::Result<bool> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM organization_units WHERE customer_id = $1 AND organization_id = $2 AND name = $3 AND deleted_at IS NOT NULL) AS "exists!""#,
+        cid,
+        oid,
+        name
+    )
+    .fetch_one(db.pool())
+    .await?)
+}
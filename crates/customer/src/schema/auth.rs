@@ -10,7 +10,9 @@ use qm_entity::ids::CustomerOrOrganization;
 use qm_entity::ids::InfraContext;
 use qm_entity::ids::InstitutionId;
 use qm_entity::ids::OrganizationId;
+use qm_mongodb::bson::doc;
 use qm_mongodb::bson::Document;
+use qm_mongodb::bson::Uuid;
 use qm_role::AccessLevel;
 
 use std::sync::Arc;
@@ -19,6 +21,8 @@ use tokio::sync::RwLock;
 use qm_entity::err;
 use qm_entity::error::EntityError;
 use qm_entity::ids::OrganizationUnitId;
+use qm_entity::repository::Repository;
+use qm_entity::Collection;
 
 use crate::context::RelatedAuth;
 use crate::context::RelatedPermission;
@@ -321,11 +325,56 @@ where
         Err(EntityError::unauthorized(&self.auth))
     }
 
+    /// Builds a filter on the `owner` field of an object restricting a query to the
+    /// context of the user, narrowed down to the requested `context` if the user has access to it.
+    ///
+    /// Admins get the requested `context` only or an empty filter.
     pub async fn build_context_query(
         &self,
-        _context: Option<&InfraContext>,
+        context: Option<&InfraContext>,
     ) -> EntityResult<Document> {
-        unimplemented!()
+        let Some(context) = self.enforce_current_context(context.copied()).await? else {
+            return Ok(Document::new());
+        };
+        let InfraContext::OrganizationUnit(id) = context else {
+            return Ok(context.owner_filter());
+        };
+        // Institutions which are members of the organization unit are part of its context.
+        let organization_unit = self
+            .store
+            .cache_db()
+            .organization_unit_by_id(&id.into())
+            .await
+            .ok_or(EntityError::internal())?;
+        if organization_unit.members.is_empty() {
+            return Ok(context.owner_filter());
+        }
+        let mut filters = vec![context.owner_filter()];
+        filters.extend(
+            organization_unit
+                .members
+                .iter()
+                .map(|id| InfraContext::Institution(*id).owner_filter()),
+        );
+        Ok(doc! { "$or": filters })
+    }
+
+    /// Repository of `collection` scoped to the context of the user, see [Self::build_context_query].
+    ///
+    /// The unique name index of the collection is created with the first repository.
+    pub async fn repository<T>(
+        &self,
+        collection: Collection<T>,
+        context: Option<&InfraContext>,
+    ) -> EntityResult<Repository<T>> {
+        let owner = self.enforce_current_context(context.copied()).await?;
+        let filter = self.build_context_query(context).await?;
+        let mut repository = Repository::new(collection, owner).with_filter(filter);
+        if let Some(user_id) = self.auth.user_id() {
+            repository = repository.with_user_id(Uuid::from_bytes(*user_id.as_bytes()));
+        }
+        repository.ensure_name_index().await?;
+        Ok(repository)
     }
 
     async fn ensure_user_context(&self) -> EntityResult<()> {
@@ -13,14 +13,15 @@ use qm_entity::ids::InfraId;
 use qm_entity::model::ListFilter;
 use qm_mongodb::bson::doc;
 use qm_role::AccessLevel;
-use sqlx::types::Uuid;
 
+use crate::cleanup::retention;
 use crate::cleanup::CleanupTask;
 use crate::cleanup::CleanupTaskType;
 use crate::context::RelatedStorage;
 use crate::context::{RelatedAuth, RelatedPermission, RelatedResource};
 use crate::groups::RelatedBuiltInGroup;
 use crate::marker::Marker;
+use crate::model::filter::{InfraQuery, InfraSort};
 use crate::model::CreateCustomerInput;
 use crate::model::CreateUserPayload;
 use crate::model::Customer;
@@ -28,6 +29,7 @@ use crate::model::CustomerData;
 use crate::model::CustomerList;
 use crate::model::UpdateCustomerInput;
 use crate::mutation::remove_customers;
+use crate::mutation::restore_customers;
 use crate::mutation::update_customer;
 use crate::roles;
 use crate::schema::auth::AuthCtx;
@@ -59,8 +61,15 @@ where
         &self,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> async_graphql::FieldResult<CustomerList> {
-        Ok(self.0.store.cache_db().customer_list(filter, ty).await)
+        Ok(self
+            .0
+            .store
+            .cache_db()
+            .customer_list(filter, ty, query, sort)
+            .await)
     }
 
     pub async fn by_id(&self, id: CustomerId) -> Option<Arc<Customer>> {
@@ -71,36 +80,52 @@ where
         let user_id = self.0.auth.user_id().unwrap();
         let name = customer.0.clone();
         let ty = customer.1;
+        // Deleted customers keep the unique name until they are purged.
+        if crate::query::deleted_customer_exists(self.0.store.customer_db(), &name).await? {
+            return err!(name_conflict::<Customer>(name));
+        }
         let lock_key = format!("v1_customer_lock_{name}");
-        let lock = self.0.store.redis().lock(&lock_key, 5000, 20, 250).await?;
+        let lock = self
+            .0
+            .store
+            .redis()
+            .lock_guard(&lock_key, 5000, 20, 250)
+            .await?;
         let (result, exists) = async {
             EntityResult::Ok(
                 if let Some(item) = self.0.store.cache_db().customer_by_name(&customer.0).await {
                     (item, true)
                 } else {
+                    // Provision the roles before the transaction, so that it is not held open
+                    // across the Keycloak request.
+                    let pool = self.0.store.customer_db().pool();
+                    let new_id = crate::mutation::next_customer_id(pool).await?;
+                    let id: CustomerId = new_id.into();
+                    let access = qm_role::Access::new(AccessLevel::Customer)
+                        .with_fmt_id(Some(&id))
+                        .to_string();
+                    let roles =
+                        roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
+                    let mut tx = pool.begin().await?;
                     let result = crate::mutation::create_customer(
-                        self.0.store.customer_db().pool(),
+                        &mut *tx,
+                        new_id,
                         &name,
                         ty.as_deref(),
                         user_id,
                     )
                     .await?;
-                    let id: CustomerId = (&result).into();
-                    let access = qm_role::Access::new(AccessLevel::Customer)
-                        .with_fmt_id(Some(&id))
-                        .to_string();
-                    let roles =
-                        roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
+                    crate::outbox::add_event(
+                        self.0.store.mutation_event_producer(),
+                        &mut *tx,
+                        &qm_kafka::producer::EventNs::Customer,
+                        qm_kafka::producer::EventType::Create,
+                        "customer",
+                        &result,
+                    )
+                    .await?;
+                    tx.commit().await?;
                     self.0.store.cache_db().user().new_roles(roles).await;
-                    if let Some(producer) = self.0.store.mutation_event_producer() {
-                        producer
-                            .create_event(
-                                &qm_kafka::producer::EventNs::Customer,
-                                "customer",
-                                &result,
-                            )
-                            .await?;
-                    }
                     let customer = Arc::new(result);
                     self.0
                         .store
@@ -113,7 +138,7 @@ where
             )
         }
         .await?;
-        self.0.store.redis().unlock(&lock_key, &lock.id).await?;
+        lock.release().await?;
         if exists {
             return err!(name_conflict::<Customer>(name));
         }
@@ -142,22 +167,26 @@ where
     }
 
     pub async fn remove(&self, ids: CustomerIds) -> EntityResult<u64> {
+        let user_id = self.0.auth.user_id().unwrap();
         let v: Vec<i64> = ids.iter().map(CustomerId::unzip).collect();
-        let delete_count = remove_customers(self.0.store.customer_db().pool(), &v).await?;
-        if delete_count != 0 {
-            let id = Uuid::new_v4();
-            self.0
-                .store
-                .cleanup_task_producer()
-                .add_item(&CleanupTask {
-                    id,
-                    ty: CleanupTaskType::Customers(ids),
-                })
-                .await?;
-            log::debug!("emit cleanup task {}", id.to_string());
-            return Ok(delete_count);
-        }
-        Ok(0)
+        let Some(removal) =
+            remove_customers(self.0.store.customer_db().pool(), &v, user_id).await?
+        else {
+            return Ok(0);
+        };
+        let task = CleanupTask::purge(CleanupTaskType::Customers(ids), removal.deleted_at);
+        self.0
+            .store
+            .cleanup_task_producer()
+            .add_item_delayed(&task, retention())
+            .await?;
+        log::debug!("emit cleanup task {}", task.id.to_string());
+        Ok(removal.count)
+    }
+
+    pub async fn restore(&self, ids: CustomerIds) -> EntityResult<u64> {
+        let v: Vec<i64> = ids.iter().map(CustomerId::unzip).collect();
+        Ok(restore_customers(self.0.store.customer_db().pool(), &v).await?)
     }
 }
 
@@ -207,6 +236,8 @@ where
         ctx: &Context<'_>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> async_graphql::FieldResult<CustomerList> {
         Ctx(
             &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
@@ -215,7 +246,7 @@ where
             )
             .await?,
         )
-        .list(filter, ty)
+        .list(filter, ty, query, sort)
         .await
         .extend()
     }
@@ -328,4 +359,22 @@ where
         .await
         .extend()
     }
+
+    /// Restores customers which were removed within the retention period.
+    async fn restore_customers(
+        &self,
+        ctx: &Context<'_>,
+        ids: CustomerIds,
+    ) -> async_graphql::FieldResult<u64> {
+        Ctx(
+            &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+                ctx,
+                (Resource::customer(), Permission::delete()),
+            )
+            .await?,
+        )
+        .restore(ids)
+        .await
+        .extend()
+    }
 }
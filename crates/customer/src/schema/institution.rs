@@ -13,24 +13,24 @@ use qm_entity::model::ListFilter;
 use qm_entity::{err, exerr};
 use qm_mongodb::bson::doc;
 use qm_role::AccessLevel;
-use sqlx::types::Uuid;
 
 use crate::cache::CacheDB;
 
-use crate::cleanup::{CleanupTask, CleanupTaskType};
+use crate::cleanup::{retention, CleanupTask, CleanupTaskType};
 use crate::context::RelatedAuth;
 use crate::context::RelatedPermission;
 use crate::context::RelatedResource;
 use crate::context::RelatedStorage;
 use crate::groups::RelatedBuiltInGroup;
 use crate::marker::Marker;
+use crate::model::filter::{InfraQuery, InfraSort};
 use crate::model::CreateUserPayload;
 use crate::model::Customer;
 use crate::model::Institution;
 use crate::model::Organization;
 use crate::model::{CreateInstitutionInput, UpdateInstitutionInput};
 use crate::model::{InstitutionData, InstitutionList};
-use crate::mutation::{remove_institutions, update_institution};
+use crate::mutation::{remove_institutions, restore_institutions, update_institution};
 use crate::roles;
 use crate::schema::auth::AuthCtx;
 
@@ -81,6 +81,8 @@ where
         mut context: Option<CustomerOrOrganization>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> async_graphql::FieldResult<InstitutionList> {
         context = self
             .0
@@ -91,7 +93,7 @@ where
             .0
             .store
             .cache_db()
-            .institution_list(context, filter, ty)
+            .institution_list(context, filter, ty, query, sort)
             .await)
     }
 
@@ -104,8 +106,16 @@ where
         let (cid, oid) = institution.0.unzip();
         let name: Arc<str> = Arc::from(institution.1.clone());
         let ty = institution.2;
+        if crate::query::deleted_institution_exists(self.0.store.customer_db(), oid, &name).await? {
+            return err!(name_conflict::<Institution>(name.to_string()));
+        }
         let lock_key = format!("v1_institution_lock_{cid:X}_{oid:X}_{name}",);
-        let lock = self.0.store.redis().lock(&lock_key, 5000, 20, 250).await?;
+        let lock = self
+            .0
+            .store
+            .redis()
+            .lock_guard(&lock_key, 5000, 20, 250)
+            .await?;
         let (result, exists) = async {
             EntityResult::Ok(
                 if let Some(item) = self
@@ -117,8 +127,18 @@ where
                 {
                     (item, true)
                 } else {
+                    let pool = self.0.store.customer_db().pool();
+                    let new_id = crate::mutation::next_institution_id(pool).await?;
+                    let id: InstitutionId = (cid, oid, new_id).into();
+                    let access = qm_role::Access::new(AccessLevel::Institution)
+                        .with_fmt_id(Some(&id))
+                        .to_string();
+                    let roles =
+                        roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
+                    let mut tx = pool.begin().await?;
                     let result = crate::mutation::create_institution(
-                        self.0.store.customer_db().pool(),
+                        &mut *tx,
+                        new_id,
                         &name,
                         ty.as_deref(),
                         cid.into(),
@@ -126,22 +146,17 @@ where
                         user_id,
                     )
                     .await?;
-                    let id: InstitutionId = (&result).into();
-                    let access = qm_role::Access::new(AccessLevel::Institution)
-                        .with_fmt_id(Some(&id))
-                        .to_string();
-                    let roles =
-                        roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
+                    crate::outbox::add_event(
+                        self.0.store.mutation_event_producer(),
+                        &mut *tx,
+                        &qm_kafka::producer::EventNs::Institution,
+                        qm_kafka::producer::EventType::Create,
+                        "institution",
+                        &result,
+                    )
+                    .await?;
+                    tx.commit().await?;
                     self.0.store.cache_db().user().new_roles(roles).await;
-                    if let Some(producer) = self.0.store.mutation_event_producer() {
-                        producer
-                            .create_event(
-                                &qm_kafka::producer::EventNs::Institution,
-                                "institution",
-                                &result,
-                            )
-                            .await?;
-                    }
                     let institution = Arc::new(result);
                     self.0
                         .store
@@ -154,7 +169,7 @@ where
             )
         }
         .await?;
-        self.0.store.redis().unlock(&lock_key, &lock.id).await?;
+        lock.release().await?;
         if exists {
             return err!(name_conflict::<Institution>(name.to_string()));
         }
@@ -180,22 +195,26 @@ where
     }
 
     pub async fn remove(&self, ids: InstitutionIds) -> EntityResult<u64> {
+        let user_id = self.0.auth.user_id().unwrap();
         let v: Vec<i64> = ids.iter().map(InstitutionId::id).collect();
-        let delete_count = remove_institutions(self.0.store.customer_db().pool(), &v).await?;
-        if delete_count != 0 {
-            let id = Uuid::new_v4();
-            self.0
-                .store
-                .cleanup_task_producer()
-                .add_item(&CleanupTask {
-                    id,
-                    ty: CleanupTaskType::Institutions(ids),
-                })
-                .await?;
-            log::debug!("emit cleanup task {}", id.to_string());
-            return Ok(delete_count);
-        }
-        Ok(0)
+        let Some(removal) =
+            remove_institutions(self.0.store.customer_db().pool(), &v, user_id).await?
+        else {
+            return Ok(0);
+        };
+        let task = CleanupTask::purge(CleanupTaskType::Institutions(ids), removal.deleted_at);
+        self.0
+            .store
+            .cleanup_task_producer()
+            .add_item_delayed(&task, retention())
+            .await?;
+        log::debug!("emit cleanup task {}", task.id.to_string());
+        Ok(removal.count)
+    }
+
+    pub async fn restore(&self, ids: InstitutionIds) -> EntityResult<u64> {
+        let v: Vec<i64> = ids.iter().map(InstitutionId::id).collect();
+        Ok(restore_institutions(self.0.store.customer_db().pool(), &v).await?)
     }
 }
 
@@ -246,6 +265,8 @@ where
         context: Option<CustomerOrOrganization>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> async_graphql::FieldResult<InstitutionList> {
         Ctx(
             &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
@@ -254,7 +275,7 @@ where
             )
             .await?,
         )
-        .list(context, filter, ty)
+        .list(context, filter, ty, query, sort)
         .await
         .extend()
     }
@@ -371,4 +392,23 @@ where
         }
         Ctx(&auth_ctx).remove(ids).await.extend()
     }
+
+    /// Restores institutions which were removed within the retention period.
+    async fn restore_institutions(
+        &self,
+        ctx: &Context<'_>,
+        ids: InstitutionIds,
+    ) -> async_graphql::FieldResult<u64> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            (Resource::institution(), Permission::delete()),
+        )
+        .await?;
+        // Removed entities are not cached, the owner is taken from the ids.
+        for id in ids.iter() {
+            let object_owner = InfraContext::Organization(id.parent());
+            auth_ctx.can_mutate(Some(&object_owner)).await.extend()?;
+        }
+        Ctx(&auth_ctx).restore(ids).await.extend()
+    }
 }
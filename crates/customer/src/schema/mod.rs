@@ -6,6 +6,8 @@ pub mod groups;
 pub mod institution;
 pub mod organization;
 pub mod organization_unit;
+pub mod realm;
+pub mod search;
 pub mod user;
 
 use crate::context::RelatedAuth;
@@ -22,6 +24,8 @@ pub struct QmCustomerQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>(
     institution::InstitutionQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
     user::UserQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
     groups::GroupQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    search::SearchQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    realm::RealmQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
 )
 where
     Auth: RelatedAuth<Resource, Permission>,
@@ -47,6 +51,8 @@ where
             institution::InstitutionQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
             user::UserQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
             groups::GroupQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            search::SearchQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            realm::RealmQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
         )
     }
 }
@@ -65,6 +71,7 @@ pub struct QmCustomerMutationRoot<Auth, Store, Resource, Permission, BuiltInGrou
     institution::InstitutionMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
     user::UserMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
     groups::GroupMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    realm::RealmMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
 )
 where
     Auth: RelatedAuth<Resource, Permission>,
@@ -90,6 +97,7 @@ where
             institution::InstitutionMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
             user::UserMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
             groups::GroupMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            realm::RealmMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
         )
     }
 }
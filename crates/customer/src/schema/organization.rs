@@ -18,10 +18,10 @@ use qm_entity::err;
 use qm_entity::model::ListFilter;
 use qm_mongodb::bson::doc;
 use qm_role::AccessLevel;
-use sqlx::types::Uuid;
 
 use crate::cache::CacheDB;
 
+use crate::cleanup::retention;
 use crate::cleanup::CleanupTask;
 use crate::cleanup::CleanupTaskType;
 use crate::context::RelatedAuth;
@@ -30,6 +30,7 @@ use crate::context::RelatedResource;
 use crate::context::RelatedStorage;
 use crate::groups::RelatedBuiltInGroup;
 use crate::marker::Marker;
+use crate::model::filter::{InfraQuery, InfraSort};
 use crate::model::CreateOrganizationInput;
 use crate::model::CreateUserPayload;
 use crate::model::Customer;
@@ -38,6 +39,7 @@ use crate::model::OrganizationData;
 use crate::model::OrganizationList;
 use crate::model::UpdateOrganizationInput;
 use crate::mutation::remove_organizations;
+use crate::mutation::restore_organizations;
 use crate::mutation::update_organization;
 use crate::roles;
 use crate::schema::auth::AuthCtx;
@@ -79,13 +81,15 @@ where
         mut context: Option<CustomerId>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> async_graphql::FieldResult<OrganizationList> {
         context = self.0.enforce_customer_context(context).await.extend()?;
         Ok(self
             .0
             .store
             .cache_db()
-            .organization_list(context, filter, ty)
+            .organization_list(context, filter, ty, query, sort)
             .await)
     }
 
@@ -98,8 +102,22 @@ where
         let cid = organization.0;
         let name: Arc<str> = Arc::from(organization.1.clone());
         let ty = organization.2;
+        if crate::query::deleted_organization_exists(
+            self.0.store.customer_db(),
+            *cid.as_ref(),
+            &name,
+        )
+        .await?
+        {
+            return err!(name_conflict::<Organization>(name.to_string()));
+        }
         let lock_key = format!("v1_organization_lock_{:X}_{name}", cid.as_ref());
-        let lock = self.0.store.redis().lock(&lock_key, 5000, 20, 250).await?;
+        let lock = self
+            .0
+            .store
+            .redis()
+            .lock_guard(&lock_key, 5000, 20, 250)
+            .await?;
         let (result, exists) = async {
             EntityResult::Ok(
                 if let Some(item) = self
@@ -111,30 +129,35 @@ where
                 {
                     (item, true)
                 } else {
+                    let pool = self.0.store.customer_db().pool();
+                    let new_id = crate::mutation::next_organization_id(pool).await?;
+                    let id: OrganizationId = (*cid.as_ref(), new_id).into();
+                    let access = qm_role::Access::new(AccessLevel::Organization)
+                        .with_fmt_id(Some(&id))
+                        .to_string();
+                    let roles =
+                        roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
+                    let mut tx = pool.begin().await?;
                     let result = crate::mutation::create_organization(
-                        self.0.store.customer_db().pool(),
+                        &mut *tx,
+                        new_id,
                         &name,
                         ty.as_deref(),
                         cid,
                         user_id,
                     )
                     .await?;
-                    let id: OrganizationId = (&result).into();
-                    let access = qm_role::Access::new(AccessLevel::Organization)
-                        .with_fmt_id(Some(&id))
-                        .to_string();
-                    let roles =
-                        roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
+                    crate::outbox::add_event(
+                        self.0.store.mutation_event_producer(),
+                        &mut *tx,
+                        &qm_kafka::producer::EventNs::Organization,
+                        qm_kafka::producer::EventType::Create,
+                        "organization",
+                        &result,
+                    )
+                    .await?;
+                    tx.commit().await?;
                     self.0.store.cache_db().user().new_roles(roles).await;
-                    if let Some(producer) = self.0.store.mutation_event_producer() {
-                        producer
-                            .create_event(
-                                &qm_kafka::producer::EventNs::Organization,
-                                "organization",
-                                &result,
-                            )
-                            .await?;
-                    }
                     let organization = Arc::new(result);
                     self.0
                         .store
@@ -147,7 +170,7 @@ where
             )
         }
         .await?;
-        self.0.store.redis().unlock(&lock_key, &lock.id).await?;
+        lock.release().await?;
         if exists {
             return err!(name_conflict::<Organization>(name.to_string()));
         }
@@ -183,22 +206,26 @@ where
     }
 
     pub async fn remove(&self, ids: OrganizationIds) -> EntityResult<u64> {
+        let user_id = self.0.auth.user_id().unwrap();
         let v: Vec<i64> = ids.iter().map(OrganizationId::id).collect();
-        let delete_count = remove_organizations(self.0.store.customer_db().pool(), &v).await?;
-        if delete_count != 0 {
-            let id = Uuid::new_v4();
-            self.0
-                .store
-                .cleanup_task_producer()
-                .add_item(&CleanupTask {
-                    id,
-                    ty: CleanupTaskType::Organizations(ids),
-                })
-                .await?;
-            log::debug!("emit cleanup task {}", id.to_string());
-            return Ok(delete_count);
-        }
-        Ok(0)
+        let Some(removal) =
+            remove_organizations(self.0.store.customer_db().pool(), &v, user_id).await?
+        else {
+            return Ok(0);
+        };
+        let task = CleanupTask::purge(CleanupTaskType::Organizations(ids), removal.deleted_at);
+        self.0
+            .store
+            .cleanup_task_producer()
+            .add_item_delayed(&task, retention())
+            .await?;
+        log::debug!("emit cleanup task {}", task.id.to_string());
+        Ok(removal.count)
+    }
+
+    pub async fn restore(&self, ids: OrganizationIds) -> EntityResult<u64> {
+        let v: Vec<i64> = ids.iter().map(OrganizationId::id).collect();
+        Ok(restore_organizations(self.0.store.customer_db().pool(), &v).await?)
     }
 }
 
@@ -249,6 +276,8 @@ where
         context: Option<CustomerId>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> async_graphql::FieldResult<OrganizationList> {
         Ctx(
             &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
@@ -257,7 +286,7 @@ where
             )
             .await?,
         )
-        .list(context, filter, ty)
+        .list(context, filter, ty, query, sort)
         .await
         .extend()
     }
@@ -380,4 +409,23 @@ where
         }
         Ctx(&auth_ctx).remove(ids).await.extend()
     }
+
+    /// Restores organizations which were removed within the retention period.
+    async fn restore_organizations(
+        &self,
+        ctx: &Context<'_>,
+        ids: OrganizationIds,
+    ) -> async_graphql::FieldResult<u64> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            (Resource::organization(), Permission::delete()),
+        )
+        .await?;
+        // Removed entities are not cached, the owner is taken from the ids.
+        for id in ids.iter() {
+            let object_owner = InfraContext::Customer(id.parent());
+            auth_ctx.can_mutate(Some(&object_owner)).await.extend()?;
+        }
+        Ctx(&auth_ctx).restore(ids).await.extend()
+    }
 }
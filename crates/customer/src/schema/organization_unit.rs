@@ -18,9 +18,9 @@ use qm_entity::ids::OrganizationUnitIds;
 use qm_entity::model::ListFilter;
 use qm_mongodb::bson::doc;
 use qm_role::AccessLevel;
-use sqlx::types::Uuid;
 
 use crate::cache::CacheDB;
+use crate::cleanup::retention;
 use crate::cleanup::CleanupTask;
 use crate::cleanup::CleanupTaskType;
 use crate::context::RelatedAuth;
@@ -29,6 +29,7 @@ use crate::context::RelatedResource;
 use crate::context::RelatedStorage;
 use crate::groups::RelatedBuiltInGroup;
 use crate::marker::Marker;
+use crate::model::filter::{InfraQuery, InfraSort};
 use crate::model::CreateOrganizationUnitInput;
 use crate::model::CreateUserPayload;
 use crate::model::Institution;
@@ -37,6 +38,7 @@ use crate::model::OrganizationUnitData;
 use crate::model::OrganizationUnitList;
 use crate::model::UpdateOrganizationUnitInput;
 use crate::mutation::remove_organization_units;
+use crate::mutation::restore_organization_units;
 use crate::mutation::update_organization_unit;
 use crate::roles;
 use crate::schema::auth::AuthCtx;
@@ -82,6 +84,8 @@ where
         mut context: Option<CustomerOrOrganization>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> async_graphql::FieldResult<OrganizationUnitList> {
         context = self
             .0
@@ -91,7 +95,7 @@ where
             .0
             .store
             .cache_db()
-            .organization_unit_list(context, filter, ty)
+            .organization_unit_list(context, filter, ty, query, sort)
             .await)
     }
 
@@ -113,8 +117,23 @@ where
         let oid = organization_unit.oid;
         let name: Arc<str> = Arc::from(organization_unit.name.clone());
         let ty = organization_unit.ty;
+        if crate::query::deleted_organization_unit_exists(
+            self.0.store.customer_db(),
+            *cid.as_ref(),
+            oid.map(|oid| *oid.as_ref()),
+            &name,
+        )
+        .await?
+        {
+            return err!(name_conflict::<OrganizationUnit>(name.to_string()));
+        }
         let lock_key = format!("v1_organization_unit_lock_{:X}_{name}", cid.as_ref());
-        let lock = self.0.store.redis().lock(&lock_key, 5000, 20, 250).await?;
+        let lock = self
+            .0
+            .store
+            .redis()
+            .lock_guard(&lock_key, 5000, 20, 250)
+            .await?;
         let (result, exists) = async {
             EntityResult::Ok(
                 if let Some(item) = self
@@ -126,8 +145,21 @@ where
                 {
                     (item, true)
                 } else {
+                    let pool = self.0.store.customer_db().pool();
+                    let new_id = crate::mutation::next_organization_unit_id(pool).await?;
+                    let id: OrganizationUnitId = match oid {
+                        Some(oid) => (*cid.as_ref(), *oid.as_ref(), new_id).into(),
+                        None => (*cid.as_ref(), new_id).into(),
+                    };
+                    let access = qm_role::Access::new(access_level)
+                        .with_fmt_id(Some(&id))
+                        .to_string();
+                    let roles =
+                        roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
+                    let mut tx = pool.begin().await?;
                     let result = crate::mutation::create_organization_unit(
-                        self.0.store.customer_db().pool(),
+                        &mut tx,
+                        new_id,
                         &name,
                         ty.as_deref(),
                         cid,
@@ -136,22 +168,17 @@ where
                         organization_unit.members,
                     )
                     .await?;
-                    let id: OrganizationUnitId = (&result).into();
-                    let access = qm_role::Access::new(access_level)
-                        .with_fmt_id(Some(&id))
-                        .to_string();
-                    let roles =
-                        roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
+                    crate::outbox::add_event(
+                        self.0.store.mutation_event_producer(),
+                        &mut *tx,
+                        &qm_kafka::producer::EventNs::OrganizationUnit,
+                        qm_kafka::producer::EventType::Create,
+                        "organization_unit",
+                        &result,
+                    )
+                    .await?;
+                    tx.commit().await?;
                     self.0.store.cache_db().user().new_roles(roles).await;
-                    if let Some(producer) = self.0.store.mutation_event_producer() {
-                        producer
-                            .create_event(
-                                &qm_kafka::producer::EventNs::OrganizationUnit,
-                                "organization_unit",
-                                &result,
-                            )
-                            .await?;
-                    }
                     let organization_unit = Arc::new(result);
                     self.0
                         .store
@@ -164,7 +191,7 @@ where
             )
         }
         .await?;
-        self.0.store.redis().unlock(&lock_key, &lock.id).await?;
+        lock.release().await?;
         if exists {
             return err!(name_conflict::<OrganizationUnit>(name.to_string()));
         }
@@ -200,22 +227,26 @@ where
     }
 
     pub async fn remove(&self, ids: OrganizationUnitIds) -> EntityResult<u64> {
+        let user_id = self.0.auth.user_id().unwrap();
         let v: Vec<i64> = ids.iter().map(OrganizationUnitId::id).collect();
-        let delete_count = remove_organization_units(self.0.store.customer_db().pool(), &v).await?;
-        if delete_count != 0 {
-            let id = Uuid::new_v4();
-            self.0
-                .store
-                .cleanup_task_producer()
-                .add_item(&CleanupTask {
-                    id,
-                    ty: CleanupTaskType::OrganizationUnits(ids),
-                })
-                .await?;
-            log::debug!("emit cleanup task {}", id.to_string());
-            return Ok(delete_count);
-        }
-        Ok(0)
+        let Some(removal) =
+            remove_organization_units(self.0.store.customer_db().pool(), &v, user_id).await?
+        else {
+            return Ok(0);
+        };
+        let task = CleanupTask::purge(CleanupTaskType::OrganizationUnits(ids), removal.deleted_at);
+        self.0
+            .store
+            .cleanup_task_producer()
+            .add_item_delayed(&task, retention())
+            .await?;
+        log::debug!("emit cleanup task {}", task.id.to_string());
+        Ok(removal.count)
+    }
+
+    pub async fn restore(&self, ids: OrganizationUnitIds) -> EntityResult<u64> {
+        let v: Vec<i64> = ids.iter().map(OrganizationUnitId::id).collect();
+        Ok(restore_organization_units(self.0.store.customer_db().pool(), &v).await?)
     }
 }
 
@@ -266,6 +297,8 @@ where
         context: Option<CustomerOrOrganization>,
         filter: Option<ListFilter>,
         ty: Option<String>,
+        query: Option<InfraQuery>,
+        sort: Option<InfraSort>,
     ) -> async_graphql::FieldResult<OrganizationUnitList> {
         Ctx(
             &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
@@ -274,7 +307,7 @@ where
             )
             .await?,
         )
-        .list(context, filter, ty)
+        .list(context, filter, ty, query, sort)
         .await
         .extend()
     }
@@ -443,4 +476,23 @@ where
         }
         Ctx(&auth_ctx).remove(ids).await.extend()
     }
+
+    /// Restores organization units which were removed within the retention period.
+    async fn restore_organization_units(
+        &self,
+        ctx: &Context<'_>,
+        ids: OrganizationUnitIds,
+    ) -> async_graphql::FieldResult<u64> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            (Resource::organization_unit(), Permission::delete()),
+        )
+        .await?;
+        // Removed entities are not cached, the owner is taken from the ids.
+        for id in ids.iter() {
+            let object_owner = id.parent();
+            auth_ctx.can_mutate(Some(&object_owner)).await.extend()?;
+        }
+        Ctx(&auth_ctx).restore(ids).await.extend()
+    }
 }
@@ -0,0 +1,96 @@
+use async_graphql::{Context, ErrorExtensions, Object};
+use qm_entity::exerr;
+use qm_keycloak::validation::report::{Fix, RealmValidationReport};
+
+use crate::context::RelatedStorage;
+use crate::context::{RelatedAuth, RelatedPermission, RelatedResource};
+use crate::groups::RelatedBuiltInGroup;
+use crate::marker::Marker;
+use crate::schema::auth::AuthCtx;
+
+async fn admin_ctx<'ctx, Auth, Store, Resource, Permission>(
+    ctx: &'ctx Context<'_>,
+) -> async_graphql::FieldResult<AuthCtx<'ctx, Auth, Store, Resource, Permission>>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?;
+    if !auth_ctx.is_admin {
+        return exerr!(unauthorized(&auth_ctx.auth));
+    }
+    Ok(auth_ctx)
+}
+
+pub struct RealmQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for RealmQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    RealmQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    /// Validates the Keycloak realm configuration, only available for admins.
+    async fn validate_realm(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::FieldResult<RealmValidationReport> {
+        let auth_ctx = admin_ctx::<Auth, Store, Resource, Permission>(ctx).await?;
+        Ok(qm_keycloak::realm::validate_realm_report(auth_ctx.store.keycloak(), Fix::None).await?)
+    }
+}
+
+pub struct RealmMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for RealmMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    RealmMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    /// Fixes the realm validation errors with the given ids, or all fixable errors
+    /// if no ids are given, and returns the report of the revalidated realm.
+    async fn fix_realm(
+        &self,
+        ctx: &Context<'_>,
+        ids: Option<Vec<String>>,
+    ) -> async_graphql::FieldResult<RealmValidationReport> {
+        let auth_ctx = admin_ctx::<Auth, Store, Resource, Permission>(ctx).await?;
+        let fix = ids.map(Fix::Only).unwrap_or(Fix::All);
+        Ok(qm_keycloak::realm::validate_realm_report(auth_ctx.store.keycloak(), fix).await?)
+    }
+}
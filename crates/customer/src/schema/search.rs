@@ -0,0 +1,183 @@
+use async_graphql::{Context, Object, ResultExt};
+use qm_entity::err;
+use qm_entity::ids::InfraContext;
+use qm_entity::QueryPermissions;
+
+use crate::context::RelatedStorage;
+use crate::context::{
+    CustomerResource, InstitutionResource, OrganizationResource, OrganizationUnitResource,
+    RelatedAuth, RelatedPermission, RelatedResource,
+};
+use crate::groups::RelatedBuiltInGroup;
+use crate::marker::Marker;
+use crate::model::search::{SearchHit, SearchHitType};
+use crate::schema::auth::AuthCtx;
+
+pub const DEFAULT_SEARCH_LIMIT: i64 = 20;
+pub const MAX_SEARCH_LIMIT: i64 = 100;
+
+/// Types of the search hits which the user is allowed to list.
+fn searchable_types<Resource, Permission>(
+    can: impl Fn(&Resource, &Permission) -> bool,
+) -> Vec<SearchHitType>
+where
+    Resource:
+        CustomerResource + OrganizationResource + OrganizationUnitResource + InstitutionResource,
+    Permission: QueryPermissions,
+{
+    [
+        (SearchHitType::Customer, Resource::customer()),
+        (SearchHitType::Organization, Resource::organization()),
+        (
+            SearchHitType::OrganizationUnit,
+            Resource::organization_unit(),
+        ),
+        (SearchHitType::Institution, Resource::institution()),
+    ]
+    .into_iter()
+    .filter(|(_, resource)| can(resource, &Permission::list()))
+    .map(|(ty, _)| ty)
+    .collect()
+}
+
+pub struct SearchQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for SearchQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    SearchQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    /// Typeahead search for customers, organizations, organization units and institutions by name.
+    async fn search(
+        &self,
+        ctx: &Context<'_>,
+        text: String,
+        context: Option<InfraContext>,
+        limit: Option<i64>,
+    ) -> async_graphql::FieldResult<Vec<SearchHit>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?;
+        let types = searchable_types::<Resource, Permission>(|resource, permission| {
+            auth_ctx.is_admin || auth_ctx.auth.has_role(resource, permission)
+        });
+        if types.is_empty() {
+            return err!(unauthorized(&auth_ctx.auth)).extend();
+        }
+        let context = auth_ctx.enforce_current_context(context).await.extend()?;
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(vec![]);
+        }
+        let limit = limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT);
+        Ok(crate::query::search(
+            auth_ctx.store.customer_db(),
+            text,
+            context.as_ref(),
+            &types,
+            limit,
+        )
+        .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::searchable_types;
+    use crate::context::{
+        CustomerResource, InstitutionResource, OrganizationResource, OrganizationUnitResource,
+    };
+    use crate::model::search::SearchHitType;
+    use qm_entity::QueryPermissions;
+
+    #[derive(Debug, PartialEq)]
+    enum Resource {
+        Customer,
+        Organization,
+        OrganizationUnit,
+        Institution,
+    }
+
+    impl CustomerResource for Resource {
+        fn customer() -> Self {
+            Self::Customer
+        }
+    }
+
+    impl OrganizationResource for Resource {
+        fn organization() -> Self {
+            Self::Organization
+        }
+    }
+
+    impl OrganizationUnitResource for Resource {
+        fn organization_unit() -> Self {
+            Self::OrganizationUnit
+        }
+    }
+
+    impl InstitutionResource for Resource {
+        fn institution() -> Self {
+            Self::Institution
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Permission {
+        List,
+        View,
+    }
+
+    impl QueryPermissions for Permission {
+        fn list() -> Self {
+            Self::List
+        }
+
+        fn view() -> Self {
+            Self::View
+        }
+    }
+
+    #[test]
+    fn searchable_types_test() {
+        assert_eq!(
+            searchable_types::<Resource, Permission>(|_, _| true),
+            vec![
+                SearchHitType::Customer,
+                SearchHitType::Organization,
+                SearchHitType::OrganizationUnit,
+                SearchHitType::Institution,
+            ]
+        );
+        assert!(searchable_types::<Resource, Permission>(|_, _| false).is_empty());
+        // Only the list permission allows to search a type.
+        assert!(searchable_types::<Resource, Permission>(|_, permission| {
+            *permission == Permission::View
+        })
+        .is_empty());
+        assert_eq!(
+            searchable_types::<Resource, Permission>(|resource, permission| {
+                *permission == Permission::List
+                    && matches!(resource, Resource::Institution | Resource::OrganizationUnit)
+            }),
+            vec![SearchHitType::OrganizationUnit, SearchHitType::Institution]
+        );
+    }
+}
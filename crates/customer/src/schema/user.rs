@@ -3,22 +3,27 @@ use async_graphql::{Context, ErrorExtensions, FieldResult, Object, ResultExt};
 use qm_entity::exerr;
 use qm_entity::ids::InfraContext;
 
-use qm_entity::model::ListFilter;
+use qm_entity::connection::KeyConnection;
+use qm_entity::model::{ConnectionFilter, ListFilter};
 use qm_keycloak::RoleRepresentation;
 use qm_role::{Access, AccessLevel};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::cache::CacheDB;
 use crate::config::SchemaConfig;
 use crate::groups::RelatedBuiltInGroup;
+use crate::import::UserImportItem;
 use crate::marker::Marker;
-use crate::model::User;
 use crate::model::UserList;
+use crate::model::{created_at_from_millis, User};
 use crate::model::{CreateUserInput, Customer};
 use crate::model::{CreateUserPayload, Institution, Organization, OrganizationUnit, UserDetails};
 use crate::model::{Group, RequiredUserAction, Role, UserGroup};
+use crate::model::{UpdateUserInput, UpdateUserPayload};
+use crate::model::{UserImportFormat, UserImportJob, UserImportRow};
+use crate::model::{UserImportRowResult, UserImportRowStatus, UserImportStatus};
 use qm_entity::err;
 use qm_entity::error::EntityError;
 use qm_entity::error::EntityResult;
@@ -29,6 +34,7 @@ use qm_keycloak::KeycloakError;
 use qm_keycloak::UserRepresentation;
 use sqlx::types::Uuid;
 
+use crate::model::filter::{UserQuery, UserSort};
 use crate::schema::auth::AuthCtx;
 use crate::schema::RelatedAuth;
 use crate::schema::RelatedPermission;
@@ -68,11 +74,86 @@ fn set_attributes(attributes: HashMap<&str, Option<String>>, u: &mut UserReprese
     }
 }
 
+/// Applies the set fields of `user` to the Keycloak representation `u`.
+fn apply_user_input(user: UpdateUserInput, u: &mut UserRepresentation) {
+    if let Some(firstname) = user.firstname {
+        u.first_name = Some(firstname);
+    }
+    if let Some(lastname) = user.lastname {
+        u.last_name = Some(lastname);
+    }
+    if let Some(email) = user.email {
+        u.email = Some(email);
+    }
+    if let Some(enabled) = user.enabled {
+        u.enabled = Some(enabled);
+    }
+    if let Some(required_actions) = user.required_actions {
+        u.required_actions = Some(
+            required_actions
+                .iter()
+                .map(|action| action.to_string())
+                .collect(),
+        );
+    }
+    let attributes: HashMap<&str, Option<String>> = [
+        ("phone", user.phone),
+        ("salutation", user.salutation),
+        ("fax", user.fax),
+        ("room-number", user.room_number),
+        ("job-title", user.job_title),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| value.map(|v| (key, Some(v).filter(|v| !v.trim().is_empty()))))
+    .collect();
+    if !attributes.is_empty() {
+        set_attributes(attributes, u);
+    }
+}
+
+/// Required actions of the invite email, users without password are invited and set their
+/// password on their own. Returns `None` for users with password.
+fn invite_actions(user: &CreateUserInput) -> Option<Vec<RequiredUserAction>> {
+    if user.password.is_some() {
+        return None;
+    }
+    let mut actions = user.required_actions.clone().unwrap_or_default();
+    if !actions.contains(&RequiredUserAction::UpdatePassword) {
+        actions.push(RequiredUserAction::UpdatePassword);
+    }
+    Some(actions)
+}
+
+/// Id of a user returned by Keycloak.
+fn keycloak_user_id(user: &UserRepresentation) -> FieldResult<&str> {
+    user.id.as_deref().ok_or_else(|| {
+        log::error!("Keycloak user {:?} has no id", user.username);
+        EntityError::Internal.extend()
+    })
+}
+
+/// Error of an invite email which could not be sent, the `userId` extension refers to the
+/// created user so the invite can be sent again with `sendUserActionsEmail`.
+fn invite_failed_error(username: &str, user_id: &str) -> async_graphql::Error {
+    let user_id = user_id.to_string();
+    EntityError::bad_request(
+        "Invite",
+        format!("user '{username}' was created, but the invite email could not be sent"),
+    )
+    .extend()
+    .extend_with(|_, e| e.set("userId", user_id))
+}
+
+/// Creates the user in Keycloak, users without password get the required actions of
+/// [invite_actions], the invite email is sent by [create_user].
 pub async fn create_keycloak_user(
     realm: &str,
     keycloak: &Keycloak,
-    user: CreateUserInput,
+    mut user: CreateUserInput,
 ) -> FieldResult<UserRepresentation> {
+    if let Some(actions) = invite_actions(&user) {
+        user.required_actions = Some(actions);
+    }
     let username = user.username;
     let email = Some(user.email);
     let first_name = Some(user.firstname);
@@ -121,21 +202,23 @@ pub async fn create_keycloak_user(
     );
 
     // Set the credential
-    keycloak_user.credentials = Some(vec![CredentialRepresentation {
-        created_date: None,
-        credential_data: None,
-        id: None,
-        priority: None,
-        secret_data: None,
-        temporary: user
-            .required_actions
-            .as_ref()
-            .map(|actions| actions.contains(&RequiredUserAction::UpdatePassword)),
-        type_: Some("password".to_string()),
-        user_label: None,
-        value: Some(user.password),
-        ..Default::default()
-    }]);
+    if let Some(password) = user.password {
+        keycloak_user.credentials = Some(vec![CredentialRepresentation {
+            created_date: None,
+            credential_data: None,
+            id: None,
+            priority: None,
+            secret_data: None,
+            temporary: user
+                .required_actions
+                .as_ref()
+                .map(|actions| actions.contains(&RequiredUserAction::UpdatePassword)),
+            type_: Some("password".to_string()),
+            user_label: None,
+            value: Some(password),
+            ..Default::default()
+        }]);
+    }
 
     let result = keycloak.create_user(realm, keycloak_user).await;
     let exists = match result {
@@ -199,13 +282,156 @@ pub async fn create_keycloak_user(
         return Err(err.extend());
     }
 
-    keycloak
+    let k_user = keycloak
         .user_by_username(realm, username.clone())
         .await?
         .ok_or(EntityError::not_found_by_field::<User>(
             "username", &username,
         ))
-        .extend()
+        .extend()?;
+    keycloak_user_id(&k_user)?;
+    Ok(k_user)
+}
+
+/// Sends an email with links to execute the `actions` to the user with `user_id`.
+pub async fn send_actions_email(
+    keycloak: &Keycloak,
+    user_id: &str,
+    actions: &[RequiredUserAction],
+    lifespan: Option<i32>,
+) -> FieldResult<bool> {
+    if actions.is_empty() {
+        return err!(bad_request("RequiredUserAction", "no actions specified").extend());
+    }
+    let realm = keycloak.config().realm();
+    let actions = actions.iter().map(|action| action.to_string()).collect();
+    match keycloak
+        .execute_actions_email(realm, user_id, actions, lifespan)
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(KeycloakError::HttpFailure {
+            status: 400 | 500,
+            body,
+            ..
+        }) => {
+            log::error!("unable to send actions email to user '{user_id}': {body:#?}");
+            err!(bad_request(
+                "RequiredUserAction",
+                "unable to send email, check the email address of the user and the SMTP settings of the realm"
+            )
+            .extend())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// User created by [create_user].
+pub struct CreatedUser {
+    pub user: Arc<User>,
+    /// Error of the invite email, the user exists and the invite can be sent again.
+    pub invite_error: Option<async_graphql::Error>,
+}
+
+/// Creates the user in Keycloak, assigns the group and access, adds the user to the cache
+/// and sends the invite email to users without password.
+pub async fn create_user<Store>(store: &Store, input: CreateUserPayload) -> FieldResult<CreatedUser>
+where
+    Store: RelatedStorage,
+{
+    let CreateUserPayload {
+        user: mut user_input,
+        access,
+        group_id,
+        context: _,
+    } = input;
+    let mut conflict_fields = Vec::new();
+    let user_exists_by_username = store
+        .cache_db()
+        .user_by_username(&user_input.username)
+        .await;
+    if user_exists_by_username.is_some() {
+        conflict_fields.push("username");
+    }
+    let user_exists_by_email = store.cache_db().user_by_email(&user_input.email).await;
+    if user_exists_by_email.is_some() {
+        conflict_fields.push("email");
+    }
+
+    if !conflict_fields.is_empty() {
+        return err!(
+            fields_conflict::<User>(user_input.username.as_str(), &conflict_fields[..]).extend()
+        );
+    }
+
+    if user_input.enabled.is_none() {
+        user_input.enabled = Some(true);
+    }
+
+    let keycloak = store.keycloak();
+    let realm = keycloak.config().realm();
+    let invite = invite_actions(&user_input);
+    let k_user = create_keycloak_user(realm, keycloak, user_input.clone()).await?;
+    let user_id = keycloak_user_id(&k_user)?.to_string();
+    let user_uuid = Uuid::parse_str(&user_id).map_err(|err| {
+        log::error!("Unable to parse user id to Uuid: {err:#?}");
+        EntityError::Internal
+    })?;
+    let mut user_groups = vec![];
+    let cache = store.cache_db();
+    if let Some(group_id) = group_id.as_ref() {
+        if let Some(group) = cache.group_by_id(group_id).await {
+            log::info!(
+                "add user {} to group {group:#?}",
+                user_input.username.as_str()
+            );
+            keycloak
+                .add_user_to_group(realm, &user_id, &group.id)
+                .await?;
+            user_groups.push(group);
+        }
+    }
+    let mut user_roles = vec![];
+    if let Some(access) = access.as_ref() {
+        if let Some(role) = cache.role_by_name(access).await {
+            keycloak
+                .add_user_role(
+                    realm,
+                    &user_id,
+                    RoleRepresentation {
+                        id: Some(role.id.to_string()),
+                        name: Some(role.name.to_string()),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            user_roles.push(role);
+        }
+    }
+    let user = Arc::new(User {
+        id: Arc::from(user_uuid.to_string()),
+        username: Arc::from(user_input.username),
+        firstname: Arc::from(user_input.firstname),
+        lastname: Arc::from(user_input.lastname),
+        email: Arc::from(user_input.email),
+        enabled: user_input.enabled.unwrap(),
+        created_at: created_at_from_millis(k_user.created_timestamp),
+    });
+    cache.user().new_user(user.clone()).await;
+    let invite_error = match invite {
+        Some(actions) => send_actions_email(keycloak, &user_id, &actions, None)
+            .await
+            .err()
+            .map(|err| {
+                log::error!(
+                    "unable to send invite to user '{}': {err:#?}",
+                    user.username
+                );
+                invite_failed_error(&user.username, &user.id)
+            }),
+        None => None,
+    };
+    Ok(CreatedUser { user, invite_error })
 }
 
 #[ComplexObject]
@@ -312,83 +538,119 @@ where
         &self,
         mut context: Option<InfraContext>,
         filter: Option<ListFilter>,
+        query: Option<UserQuery>,
+        sort: Option<UserSort>,
     ) -> async_graphql::FieldResult<UserList> {
         context = self.0.enforce_current_context(context).await?;
-        Ok(self.0.store.cache_db().user_list(context, filter).await)
+        Ok(self
+            .0
+            .store
+            .cache_db()
+            .user_list(context, filter, query, sort)
+            .await)
     }
 
     pub async fn by_id(&self, id: &str) -> Option<UserDetails> {
         self.0.store.cache_db().user_details_by_id(id).await
     }
 
+    /// Creates the user, a failed invite is returned as error with the id of the created user.
     pub async fn create(&self, input: CreateUserPayload) -> FieldResult<Arc<User>> {
-        let CreateUserPayload {
-            user: mut user_input,
-            access,
-            group_id,
-            context: _,
-        } = input;
-        let mut conflict_fields = Vec::new();
-        let user_exists_by_username = self
-            .0
-            .store
-            .cache_db()
-            .user_by_username(&user_input.username)
-            .await;
-        if user_exists_by_username.is_some() {
-            conflict_fields.push("username");
-        }
-        let user_exists_by_email = self
-            .0
-            .store
-            .cache_db()
-            .user_by_email(&user_input.username)
-            .await;
-        if user_exists_by_email.is_some() {
-            conflict_fields.push("email");
-        }
-
-        if !conflict_fields.is_empty() {
-            return err!(fields_conflict::<User>(
-                user_input.username.as_str(),
-                &conflict_fields[..]
-            )
-            .extend());
+        let created = create_user(self.0.store, input).await?;
+        match created.invite_error {
+            Some(err) => Err(err),
+            None => Ok(created.user),
         }
+    }
 
-        if user_input.enabled.is_none() {
-            user_input.enabled = Some(true);
+    pub async fn update(&self, input: UpdateUserPayload) -> FieldResult<Arc<User>> {
+        let UpdateUserPayload {
+            id,
+            user: user_input,
+            group_id,
+            access,
+        } = input;
+        let cache = self.0.store.cache_db();
+        if let Some(email) = user_input.email.as_deref() {
+            if cache
+                .user_by_email(email)
+                .await
+                .map(|u| u.id.as_ref() != id)
+                .unwrap_or(false)
+            {
+                return err!(fields_conflict::<User>(email, &["email"][..]).extend());
+            }
         }
 
         let keycloak = self.0.store.keycloak();
         let realm = keycloak.config().realm();
-        let k_user = create_keycloak_user(realm, keycloak, user_input.clone()).await?;
-        let user_id = k_user.id.as_ref().unwrap().clone();
-        let user_uuid = Uuid::parse_str(&user_id).map_err(|err| {
-            log::error!("Unable to parse user id to Uuid: {err:#?}");
-            EntityError::Internal
-        })?;
-        let mut user_groups = vec![];
-        let cache = self.0.store.cache_db();
+        let mut k_user = keycloak
+            .user_by_id(realm, &id)
+            .await?
+            .ok_or(EntityError::not_found_by_id::<User>(&id))
+            .extend()?;
+        apply_user_input(user_input, &mut k_user);
+        keycloak.update_user(realm, &id, &k_user).await?;
+
+        let user_id: Arc<str> = Arc::from(id.as_str());
         if let Some(group_id) = group_id.as_ref() {
-            if let Some(group) = cache.group_by_id(group_id).await {
-                log::info!(
-                    "add user {} to group {group:#?}",
-                    user_input.username.as_str()
-                );
-                keycloak
-                    .add_user_to_group(realm, &user_id, &group.id)
-                    .await?;
-                user_groups.push(group);
+            let group = cache
+                .group_by_id(group_id)
+                .await
+                .ok_or(EntityError::not_found_by_id::<Group>(group_id))
+                .extend()?;
+            let user_groups = cache.groups_by_user_id(&id).await.unwrap_or_default();
+            for user_group in user_groups.iter() {
+                if user_group.group_id.as_ref() != group.id.as_ref() {
+                    log::info!("remove user {id} from group {}", user_group.group_id);
+                    keycloak
+                        .remove_user_from_group(realm, &id, &user_group.group_id)
+                        .await?;
+                    cache
+                        .user()
+                        .remove_user_group(&id, &user_group.group_id)
+                        .await;
+                }
+            }
+            if !user_groups.iter().any(|g| g.group_id == group.id) {
+                log::info!("add user {id} to group {group:#?}");
+                keycloak.add_user_to_group(realm, &id, &group.id).await?;
+                cache
+                    .user()
+                    .add_user_group(user_id.clone(), group.id.clone())
+                    .await;
             }
         }
-        let mut user_roles = vec![];
         if let Some(access) = access.as_ref() {
-            if let Some(role) = cache.role_by_name(access).await {
+            let role = cache
+                .role_by_name(access)
+                .await
+                .ok_or(EntityError::not_found_by_field::<Role>("name", access))
+                .extend()?;
+            let user_roles = cache.roles_by_user_id(&id).await.unwrap_or_default();
+            for user_role in user_roles.iter() {
+                if user_role.id != role.id
+                    && qm_role::Access::from_str(user_role.name.as_ref()).is_ok()
+                {
+                    keycloak
+                        .remove_user_role(
+                            realm,
+                            &id,
+                            RoleRepresentation {
+                                id: Some(user_role.id.to_string()),
+                                name: Some(user_role.name.to_string()),
+                                ..Default::default()
+                            },
+                        )
+                        .await?;
+                    cache.user().remove_user_role(&id, &user_role.id).await;
+                }
+            }
+            if !user_roles.iter().any(|r| r.id == role.id) {
                 keycloak
                     .add_user_role(
                         realm,
-                        &user_id,
+                        &id,
                         RoleRepresentation {
                             id: Some(role.id.to_string()),
                             name: Some(role.name.to_string()),
@@ -396,18 +658,23 @@ where
                         },
                     )
                     .await?;
-                user_roles.push(role);
+                cache
+                    .user()
+                    .add_user_role(user_id.clone(), role.id.clone())
+                    .await;
             }
         }
+
         let user = Arc::new(User {
-            id: Arc::from(user_uuid.to_string()),
-            username: Arc::from(user_input.username),
-            firstname: Arc::from(user_input.firstname),
-            lastname: Arc::from(user_input.lastname),
-            email: Arc::from(user_input.email),
-            enabled: user_input.enabled.unwrap(),
+            id: user_id,
+            username: Arc::from(k_user.username.unwrap_or_default()),
+            firstname: Arc::from(k_user.first_name.unwrap_or_default()),
+            lastname: Arc::from(k_user.last_name.unwrap_or_default()),
+            email: Arc::from(k_user.email.unwrap_or_default()),
+            enabled: k_user.enabled.unwrap_or(false),
+            created_at: created_at_from_millis(k_user.created_timestamp),
         });
-        cache.user().new_user(user.clone()).await;
+        cache.user().update_user(user.clone()).await;
         Ok(user)
     }
 
@@ -432,6 +699,268 @@ where
     }
 }
 
+/// User with `id` if the current user is allowed to change it.
+async fn mutable_user<Auth, Store, Resource, Permission>(
+    auth_ctx: &AuthCtx<'_, Auth, Store, Resource, Permission>,
+    id: &str,
+) -> FieldResult<UserDetails>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    let user = auth_ctx
+        .store
+        .cache_db()
+        .user_details_by_id(id)
+        .await
+        .ok_or(EntityError::not_found_by_id::<User>(id))
+        .extend()?;
+    if user.is_admin() && !auth_ctx.is_admin {
+        return exerr!(unauthorized(&auth_ctx.auth));
+    }
+    auth_ctx.can_mutate(user.context.as_ref()).await.extend()?;
+    Ok(user)
+}
+
+/// Checks that `group_id` can be assigned by the current user to a user with `access_level`.
+async fn check_group<Auth, Store, Resource, Permission>(
+    auth_ctx: &AuthCtx<'_, Auth, Store, Resource, Permission>,
+    group_id: &str,
+    access_level: &AccessLevel,
+) -> FieldResult<()>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    let group = auth_ctx
+        .store
+        .cache_db()
+        .group_detail_by_id(group_id)
+        .await
+        .ok_or(EntityError::not_found_by_id::<Group>(group_id))
+        .extend()?;
+    if group
+        .allowed_access_levels
+        .as_ref()
+        .map(|lvls| !lvls.iter().any(|l| l == access_level))
+        .unwrap_or(false)
+    {
+        return err!(not_allowed("invalid access level for selected group").extend());
+    }
+
+    let group_roles = auth_ctx
+        .store
+        .cache_db()
+        .roles_by_group_id(group_id)
+        .await
+        .ok_or(EntityError::not_found_by_id::<Group>(group_id))
+        .extend()?;
+
+    for role in group_roles.iter() {
+        if let Ok(role) = qm_role::Role::<Resource, Permission>::from_str(role.name.as_ref()) {
+            if role.ty.is_admin() {
+                return err!(not_allowed("invalid group selected").extend());
+            }
+            if !auth_ctx.is_admin && !auth_ctx.auth.has_role_object(&role) {
+                return err!(not_allowed("invalid group selected").extend());
+            }
+        } else {
+            return err!(internal().extend());
+        }
+    }
+    Ok(())
+}
+
+/// Access of a user with `access_level` in `context`, which the current user is allowed to grant.
+fn user_access<Auth, Store, Resource, Permission>(
+    auth_ctx: &AuthCtx<'_, Auth, Store, Resource, Permission>,
+    access_level: AccessLevel,
+    context: Option<&InfraContext>,
+) -> FieldResult<Access>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    let user_access_level_u32 = auth_ctx.auth.as_number();
+    let access_level_u32 = access_level.as_number();
+    if let Some(context) = context {
+        let access = Access::new(access_level).with_fmt_id(Some(context));
+        if (user_access_level_u32 < access_level_u32)
+            || (user_access_level_u32 == access_level_u32 && !auth_ctx.auth.has_access(&access))
+        {
+            return err!(unauthorized(&auth_ctx.auth).extend());
+        }
+        Ok(access)
+    } else {
+        let own_access_level_id = auth_ctx
+            .auth
+            .session_access()
+            .ok_or(EntityError::unauthorized(&auth_ctx.auth))?;
+        if own_access_level_id.id().is_some() {
+            return err!(unauthorized(&auth_ctx.auth).extend());
+        }
+        if access_level.id_required() {
+            return err!(bad_request(
+                "InfraContext",
+                "'context' is required for specified access level"
+            )
+            .extend());
+        }
+        Ok(Access::new(access_level))
+    }
+}
+
+/// Access level of an updated user, a `context` without access level keeps the current one.
+fn updated_access_level(
+    access_level: Option<AccessLevel>,
+    context: Option<&InfraContext>,
+    current: Option<&Access>,
+) -> EntityResult<Option<AccessLevel>> {
+    match (access_level, context) {
+        (None, Some(_)) => {
+            current
+                .map(|access| Some(*access.ty()))
+                .ok_or(EntityError::bad_request(
+                    "AccessLevel",
+                    "'accessLevel' is required to set the context of users without access level",
+                ))
+        }
+        (access_level, _) => Ok(access_level),
+    }
+}
+
+/// Fields of a user import row which are checked without the cache.
+#[derive(Debug, Default)]
+struct ImportRowFields {
+    context: Option<InfraContext>,
+    group_id: Option<String>,
+    access_level: Option<AccessLevel>,
+    errors: Vec<String>,
+}
+
+/// Checks the required fields, duplicates within the import and parses context and access level.
+fn parse_import_row(
+    input: &UserImportRow,
+    allow_multiple_admin_users: bool,
+    seen: &mut HashSet<String>,
+) -> ImportRowFields {
+    let mut fields = ImportRowFields::default();
+    let errors = &mut fields.errors;
+    if input.username.is_empty() {
+        errors.push("username is required".to_string());
+    } else if !seen.insert(format!("username:{}", input.username)) {
+        errors.push(format!("username '{}' already exists", input.username));
+    }
+    if input.email.is_empty() {
+        errors.push("email is required".to_string());
+    } else if !seen.insert(format!("email:{}", input.email)) {
+        errors.push(format!("email '{}' already exists", input.email));
+    }
+    if input.password.as_deref().is_some_and(|v| !v.is_empty()) {
+        errors.push("password is not supported, imported users are invited".to_string());
+    }
+    fields.context = match input.context.as_deref().filter(|v| !v.is_empty()) {
+        Some(context) => match InfraContext::parse(context) {
+            Ok(context) => Some(context),
+            Err(_) => {
+                errors.push(format!("invalid context '{context}'"));
+                None
+            }
+        },
+        None => None,
+    };
+    fields.group_id = input.group_id.clone().filter(|v| !v.is_empty());
+    fields.access_level = match AccessLevel::from_str(&input.access_level) {
+        Ok(access_level) if access_level.is_admin() && !allow_multiple_admin_users => {
+            errors.push("creating multiple admin users is not allowed".to_string());
+            None
+        }
+        Ok(access_level) => Some(access_level),
+        Err(_) => {
+            errors.push(format!("invalid access level '{}'", input.access_level));
+            None
+        }
+    };
+    fields
+}
+
+/// Validates a row of a user import, valid rows are created by the background job.
+async fn validate_import_row<Auth, Store, Resource, Permission>(
+    auth_ctx: &AuthCtx<'_, Auth, Store, Resource, Permission>,
+    row: usize,
+    input: UserImportRow,
+    allow_multiple_admin_users: bool,
+    seen: &mut HashSet<String>,
+) -> Result<UserImportItem, Vec<String>>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    let cache = auth_ctx.store.cache_db();
+    let ImportRowFields {
+        context,
+        group_id,
+        access_level,
+        mut errors,
+    } = parse_import_row(&input, allow_multiple_admin_users, seen);
+    let mut push_error = |error: String| {
+        if !errors.contains(&error) {
+            errors.push(error);
+        }
+    };
+    if !input.username.is_empty() && cache.user_by_username(&input.username).await.is_some() {
+        push_error(format!("username '{}' already exists", input.username));
+    }
+    if !input.email.is_empty() && cache.user_by_email(&input.email).await.is_some() {
+        push_error(format!("email '{}' already exists", input.email));
+    }
+    let mut access = None;
+    if let Some(access_level) = access_level {
+        if let Some(group_id) = group_id.as_ref() {
+            if let Err(err) = check_group(auth_ctx, group_id, &access_level).await {
+                push_error(err.message);
+            }
+        }
+        match user_access(auth_ctx, access_level, context.as_ref()) {
+            Ok(v) => access = Some(v),
+            Err(err) => push_error(err.message),
+        }
+    }
+    match access {
+        Some(access) if errors.is_empty() => Ok(UserImportItem {
+            row,
+            user: CreateUserInput {
+                username: input.username,
+                firstname: input.firstname,
+                lastname: input.lastname,
+                password: None,
+                email: input.email,
+                phone: input.phone,
+                salutation: input.salutation,
+                room_number: input.room_number,
+                job_title: input.job_title,
+                enabled: Some(true),
+                required_actions: None,
+            },
+            group_id,
+            access: access.to_string(),
+        }),
+        _ => Err(errors),
+    }
+}
+
 pub struct UserQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
     _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
 }
@@ -487,6 +1016,8 @@ where
         ctx: &Context<'_>,
         context: Option<InfraContext>,
         filter: Option<ListFilter>,
+        query: Option<UserQuery>,
+        sort: Option<UserSort>,
     ) -> async_graphql::FieldResult<UserList> {
         Ctx(
             &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
@@ -495,10 +1026,57 @@ where
             )
             .await?,
         )
-        .list(context, filter)
+        .list(context, filter, query, sort)
         .await
         .extend()
     }
+
+    /// Users of `context` as connection, the pages can be fetched one after
+    /// another to export all users.
+    async fn export_users(
+        &self,
+        ctx: &Context<'_>,
+        context: Option<InfraContext>,
+        filter: Option<ConnectionFilter>,
+    ) -> async_graphql::FieldResult<KeyConnection<String, UserDetails>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            (Resource::user(), Permission::list()),
+        )
+        .await?;
+        let context = auth_ctx.enforce_current_context(context).await.extend()?;
+        auth_ctx
+            .store
+            .cache_db()
+            .user_connection(context, filter)
+            .await
+            .extend()
+    }
+
+    /// State and per row results of a user import.
+    async fn user_import_job(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> async_graphql::FieldResult<Option<UserImportJob>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            (Resource::user(), Permission::create()),
+        )
+        .await?;
+        let Some(job) = crate::import::load_job(auth_ctx.store.redis(), &id).await? else {
+            return Ok(None);
+        };
+        // Jobs are visible to the user who started them and to users of the same context.
+        if !auth_ctx.is_admin && job.created_by.as_ref() != auth_ctx.auth.user_id() {
+            let context = job
+                .context
+                .as_deref()
+                .and_then(|context| InfraContext::parse(context).ok());
+            auth_ctx.can_mutate(context.as_ref()).await.extend()?;
+        }
+        Ok(Some(job))
+    }
 }
 
 pub struct UserMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
@@ -543,93 +1121,208 @@ where
             return err!(not_allowed("creating multiple admin users").extend());
         }
         if let Some(group_id) = group_id.as_ref() {
-            let group = auth_ctx
-                .store
-                .cache_db()
-                .group_detail_by_id(group_id)
-                .await
-                .ok_or(EntityError::not_found_by_id::<Group>(group_id))
-                .extend()?;
-            if group
-                .allowed_access_levels
-                .as_ref()
-                .map(|lvls| !lvls.iter().any(|l| l == &access_level))
-                .unwrap_or(false)
-            {
-                return err!(not_allowed("invalid access level for selected group").extend());
-            }
-
-            let group_roles = auth_ctx
-                .store
-                .cache_db()
-                .roles_by_group_id(group_id)
-                .await
-                .ok_or(EntityError::not_found_by_id::<Group>(group_id))
-                .extend()?;
+            check_group(&auth_ctx, group_id, &access_level).await?;
+        }
+        let access = user_access(&auth_ctx, access_level, context.as_ref())?;
+        Ctx(&auth_ctx)
+            .create(CreateUserPayload {
+                access: Some(access.to_string()),
+                user: input,
+                group_id,
+                context,
+            })
+            .await
+            .extend()
+    }
 
-            for role in group_roles.iter() {
-                if let Ok(role) =
-                    qm_role::Role::<Resource, Permission>::from_str(role.name.as_ref())
-                {
-                    if role.ty.is_admin() {
-                        return err!(not_allowed("invalid group selected").extend());
-                    }
-                    if !auth_ctx.is_admin && !auth_ctx.auth.has_role_object(&role) {
-                        return err!(not_allowed("invalid group selected").extend());
-                    }
-                } else {
-                    return err!(internal().extend());
-                }
+    async fn update_user(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        input: UpdateUserInput,
+        access_level: Option<AccessLevel>,
+        group_id: Option<String>,
+        context: Option<InfraContext>,
+    ) -> async_graphql::FieldResult<Arc<User>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            (Resource::user(), Permission::update()),
+        )
+        .await?;
+        let id = id.to_string();
+        let user = mutable_user(&auth_ctx, &id).await?;
+        let access_level =
+            updated_access_level(access_level, context.as_ref(), user.access.as_ref()).extend()?;
+        if access_level.is_some() || group_id.is_some() {
+            let active_user_id = auth_ctx
+                .auth
+                .user_id()
+                .ok_or(EntityError::unauthorized(&auth_ctx.auth))?;
+            if active_user_id.to_string() == id {
+                return exerr!(bad_request(
+                    "User",
+                    "User cannot change their own group or access level"
+                ));
             }
         }
-        let user_access_level_u32 = auth_ctx.auth.as_number();
-        let access_level_u32 = access_level.as_number();
-        let access = if let Some(context) = context.as_ref() {
-            let access = Access::new(access_level).with_fmt_id(Some(&context));
-            if (user_access_level_u32 < access_level_u32)
-                || (user_access_level_u32 == access_level_u32 && !auth_ctx.auth.has_access(&access))
+        if let Some(access_level) = access_level.as_ref() {
+            if !SchemaConfig::new(ctx).allow_multiple_admin_users()
+                && access_level.is_admin()
+                && !user.is_admin()
             {
-                return err!(unauthorized(&auth_ctx.auth).extend());
-            }
-            access
-        } else {
-            let own_access_level_id = auth_ctx
-                .auth
-                .session_access()
-                .ok_or(EntityError::unauthorized(&auth_ctx.auth))?;
-            if own_access_level_id.id().is_some() {
-                return err!(unauthorized(&auth_ctx.auth).extend());
+                return err!(not_allowed("creating multiple admin users").extend());
             }
-            if access_level.id_required() {
-                return err!(bad_request(
-                    "InfraContext",
-                    "'context' is required for specified access level"
-                )
-                .extend());
+        }
+        if let Some(group_id) = group_id.as_ref() {
+            let access_level = access_level
+                .or_else(|| user.access.as_ref().map(|a| *a.ty()))
+                .ok_or(EntityError::bad_request(
+                    "AccessLevel",
+                    "'accessLevel' is required for users without access level",
+                ))
+                .extend()?;
+            check_group(&auth_ctx, group_id, &access_level).await?;
+        }
+        let access = match access_level {
+            Some(access_level) => {
+                // keep the context of the user if the access level requires one
+                let context = context
+                    .as_ref()
+                    .or(user.context.as_ref().filter(|_| access_level.id_required()));
+                Some(user_access(&auth_ctx, access_level, context)?)
             }
-            Access::new(access_level)
+            None => None,
         };
         Ctx(&auth_ctx)
-            .create(CreateUserPayload {
-                access: Some(access.to_string()),
+            .update(UpdateUserPayload {
+                id,
                 user: input,
                 group_id,
-                context,
+                access: access.map(|a| a.to_string()),
             })
             .await
             .extend()
     }
 
-    async fn update_user(
+    /// Validates the rows of `data` and creates the users of the valid rows in
+    /// a background job. Imported users get an invite email, rows with a
+    /// password are rejected so that passwords are never queued.
+    async fn import_users(
         &self,
-        _ctx: &Context<'_>,
-        _input: String,
-    ) -> async_graphql::FieldResult<Option<Arc<User>>> {
-        // Ok(InstitutionCtx::<Auth, Store>::from_graphql(ctx)
-        //     .await?
-        //     .update(&input)
-        //     .await?)
-        unimplemented!()
+        ctx: &Context<'_>,
+        format: UserImportFormat,
+        data: String,
+    ) -> async_graphql::FieldResult<UserImportJob> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            (Resource::user(), Permission::create()),
+        )
+        .await?;
+        let rows = crate::import::parse_rows(format, &data)
+            .map_err(|err| EntityError::bad_request("UserImport", err.to_string()))
+            .extend()?;
+        let allow_multiple_admin_users = SchemaConfig::new(ctx).allow_multiple_admin_users();
+        let mut seen = HashSet::new();
+        let mut items = vec![];
+        let mut results = vec![];
+        for (idx, input) in rows.into_iter().enumerate() {
+            let row = idx + 1;
+            let username = input.username.clone();
+            match validate_import_row(&auth_ctx, row, input, allow_multiple_admin_users, &mut seen)
+                .await
+            {
+                Ok(item) => {
+                    items.push(item);
+                    results.push(UserImportRowResult {
+                        row,
+                        username,
+                        status: UserImportRowStatus::Pending,
+                        user_id: None,
+                        errors: vec![],
+                    });
+                }
+                Err(errors) => results.push(UserImportRowResult {
+                    row,
+                    username,
+                    status: UserImportRowStatus::Invalid,
+                    user_id: None,
+                    errors,
+                }),
+            }
+        }
+        let job = UserImportJob {
+            id: Uuid::new_v4(),
+            status: if items.is_empty() {
+                UserImportStatus::Completed
+            } else {
+                UserImportStatus::Pending
+            },
+            results,
+            created_by: auth_ctx.auth.user_id().copied(),
+            context: auth_ctx
+                .enforce_current_context(None)
+                .await
+                .extend()?
+                .map(|context| context.to_string()),
+        };
+        crate::import::enqueue(auth_ctx.store.redis(), &job, items).await?;
+        Ok(job)
+    }
+
+    /// Sends an email to the user with a link to set a new password.
+    async fn reset_user_password(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        lifespan: Option<i32>,
+    ) -> async_graphql::FieldResult<bool> {
+        self.send_user_actions_email(ctx, id, vec![RequiredUserAction::UpdatePassword], lifespan)
+            .await
+    }
+
+    /// Sends an email to the user with a link to execute the `actions`, e.g.
+    /// to invite the user again.
+    async fn send_user_actions_email(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        actions: Vec<RequiredUserAction>,
+        lifespan: Option<i32>,
+    ) -> async_graphql::FieldResult<bool> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            (Resource::user(), Permission::update()),
+        )
+        .await?;
+        let user = mutable_user(&auth_ctx, &id.to_string()).await?;
+        send_actions_email(auth_ctx.store.keycloak(), &user.user.id, &actions, lifespan).await
+    }
+
+    /// Sets the required actions of the user, an empty list clears them.
+    async fn set_user_required_actions(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        actions: Vec<RequiredUserAction>,
+    ) -> async_graphql::FieldResult<Arc<User>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            (Resource::user(), Permission::update()),
+        )
+        .await?;
+        let user = mutable_user(&auth_ctx, &id.to_string()).await?;
+        Ctx(&auth_ctx)
+            .update(UpdateUserPayload {
+                id: user.user.id.to_string(),
+                user: UpdateUserInput {
+                    required_actions: Some(actions),
+                    ..Default::default()
+                },
+                group_id: None,
+                access: None,
+            })
+            .await
+            .extend()
     }
 
     async fn remove_users(
@@ -647,7 +1340,7 @@ where
             .user_id()
             .ok_or(EntityError::unauthorized(&auth_ctx.auth))?;
         if ids.iter().any(|id| id.as_ref() == active_user_id) {
-            return exerr!(bad_request("User", "User cannot remove himself"));
+            return exerr!(bad_request("User", "User cannot remove themselves"));
         }
         let cache = auth_ctx.store.cache_db();
         let mut user_ids = Vec::with_capacity(ids.len());
@@ -667,3 +1360,232 @@ where
         Ctx(&auth_ctx).remove(Arc::from(user_ids)).await.extend()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use qm_keycloak::UserRepresentation;
+
+    use std::collections::HashSet;
+
+    use qm_entity::ids::{CustomerId, InfraContext};
+    use qm_role::{Access, AccessLevel};
+
+    use super::{
+        apply_user_input, invite_actions, invite_failed_error, keycloak_user_id, parse_import_row,
+        updated_access_level,
+    };
+    use crate::model::{CreateUserInput, RequiredUserAction, UpdateUserInput, UserImportRow};
+
+    fn attributes(pairs: &[(&str, &[&str])]) -> Option<HashMap<String, Vec<String>>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.iter().map(|v| v.to_string()).collect()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn apply_user_input_test() {
+        let mut u = UserRepresentation {
+            first_name: Some("Jane".to_string()),
+            last_name: Some("Doe".to_string()),
+            email: Some("jane@example.com".to_string()),
+            enabled: Some(true),
+            attributes: attributes(&[("phone", &["123"]), ("fax", &["456"])]),
+            ..Default::default()
+        };
+        apply_user_input(
+            UpdateUserInput {
+                lastname: Some("Smith".to_string()),
+                enabled: Some(false),
+                required_actions: Some(vec![RequiredUserAction::VerifyEmail]),
+                job_title: Some("Engineer, Lead".to_string()),
+                // empty values remove the attribute
+                fax: Some(" ".to_string()),
+                ..Default::default()
+            },
+            &mut u,
+        );
+        assert_eq!(u.first_name.as_deref(), Some("Jane"));
+        assert_eq!(u.last_name.as_deref(), Some("Smith"));
+        assert_eq!(u.email.as_deref(), Some("jane@example.com"));
+        assert_eq!(u.enabled, Some(false));
+        assert_eq!(u.required_actions, Some(vec!["VERIFY_EMAIL".to_string()]));
+        assert_eq!(
+            u.attributes,
+            attributes(&[("phone", &["123"]), ("job-title", &["Engineer", "Lead"])])
+        );
+    }
+
+    #[test]
+    fn apply_empty_user_input_test() {
+        let mut u = UserRepresentation {
+            first_name: Some("Jane".to_string()),
+            required_actions: Some(vec!["UPDATE_PASSWORD".to_string()]),
+            ..Default::default()
+        };
+        apply_user_input(UpdateUserInput::default(), &mut u);
+        assert_eq!(u.first_name.as_deref(), Some("Jane"));
+        assert_eq!(
+            u.required_actions,
+            Some(vec!["UPDATE_PASSWORD".to_string()])
+        );
+        // attributes are only touched if one is set
+        assert!(u.attributes.is_none());
+
+        apply_user_input(
+            UpdateUserInput {
+                required_actions: Some(vec![]),
+                ..Default::default()
+            },
+            &mut u,
+        );
+        assert_eq!(u.required_actions, Some(vec![]));
+    }
+
+    #[test]
+    fn invite_actions_test() {
+        let mut user = CreateUserInput {
+            password: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(invite_actions(&user), None);
+
+        user.password = None;
+        assert_eq!(
+            invite_actions(&user),
+            Some(vec![RequiredUserAction::UpdatePassword])
+        );
+        user.required_actions = Some(vec![
+            RequiredUserAction::UpdatePassword,
+            RequiredUserAction::VerifyEmail,
+        ]);
+        assert_eq!(
+            invite_actions(&user),
+            Some(vec![
+                RequiredUserAction::UpdatePassword,
+                RequiredUserAction::VerifyEmail
+            ])
+        );
+    }
+
+    #[test]
+    fn keycloak_user_id_test() {
+        let mut user = UserRepresentation::default();
+        assert!(keycloak_user_id(&user).is_err());
+        user.id = Some("1".to_string());
+        assert_eq!(keycloak_user_id(&user).ok(), Some("1"));
+    }
+
+    #[test]
+    fn invite_failed_error_test() {
+        let err = invite_failed_error("jane", "1");
+        assert!(err.message.contains("'jane' was created"));
+        let extensions = serde_json::to_value(err.extensions).unwrap();
+        assert_eq!(extensions["code"], 400);
+        assert_eq!(extensions["userId"], "1");
+    }
+
+    fn import_row(username: &str, email: &str, access_level: &str) -> UserImportRow {
+        UserImportRow {
+            username: username.to_string(),
+            email: email.to_string(),
+            access_level: access_level.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_import_row_test() {
+        let context = InfraContext::Customer(CustomerId::from(1)).to_string();
+        let mut seen = HashSet::new();
+        let fields = parse_import_row(
+            &UserImportRow {
+                context: Some(context.clone()),
+                group_id: Some("g1".to_string()),
+                ..import_row("jane", "jane@example.com", "customer")
+            },
+            false,
+            &mut seen,
+        );
+        assert!(fields.errors.is_empty());
+        assert_eq!(fields.context.map(|c| c.to_string()), Some(context));
+        assert_eq!(fields.group_id.as_deref(), Some("g1"));
+        assert_eq!(fields.access_level, Some(AccessLevel::Customer));
+
+        // duplicates within the import
+        let fields = parse_import_row(
+            &import_row("jane", "jane@example.com", "customer"),
+            false,
+            &mut seen,
+        );
+        assert_eq!(
+            fields.errors,
+            vec![
+                "username 'jane' already exists",
+                "email 'jane@example.com' already exists"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_import_row_test() {
+        let mut seen = HashSet::new();
+        let fields = parse_import_row(
+            &UserImportRow {
+                password: Some("secret".to_string()),
+                context: Some("invalid".to_string()),
+                group_id: Some(String::new()),
+                ..import_row("", "", "unknown")
+            },
+            false,
+            &mut seen,
+        );
+        assert_eq!(
+            fields.errors,
+            vec![
+                "username is required",
+                "email is required",
+                "password is not supported, imported users are invited",
+                "invalid context 'invalid'",
+                "invalid access level 'unknown'"
+            ]
+        );
+        assert!(fields.context.is_none());
+        assert!(fields.group_id.is_none());
+        assert!(fields.access_level.is_none());
+
+        let admin = import_row("admin", "admin@example.com", "admin");
+        let fields = parse_import_row(&admin, false, &mut HashSet::new());
+        assert_eq!(
+            fields.errors,
+            vec!["creating multiple admin users is not allowed"]
+        );
+        let fields = parse_import_row(&admin, true, &mut HashSet::new());
+        assert!(fields.errors.is_empty());
+        assert_eq!(fields.access_level, Some(AccessLevel::Admin));
+    }
+
+    #[test]
+    fn updated_access_level_test() {
+        let context = InfraContext::Customer(CustomerId::from(1));
+        let current = Access::new(AccessLevel::Customer).with_fmt_id(Some(&context));
+        assert_eq!(
+            updated_access_level(Some(AccessLevel::Admin), None, Some(&current)).unwrap(),
+            Some(AccessLevel::Admin)
+        );
+        assert_eq!(
+            updated_access_level(None, None, Some(&current)).unwrap(),
+            None
+        );
+        // A context alone is applied with the current access level.
+        assert_eq!(
+            updated_access_level(None, Some(&context), Some(&current)).unwrap(),
+            Some(AccessLevel::Customer)
+        );
+        assert!(updated_access_level(None, Some(&context), None).is_err());
+    }
+}
@@ -26,15 +26,25 @@ use qm_entity::ids::CUSTOMER_UNIT_ID_PREFIX;
 use qm_entity::ids::INSTITUTION_ID_PREFIX;
 use qm_entity::ids::INSTITUTION_UNIT_ID_PREFIX;
 use qm_entity::ids::ORGANIZATION_ID_PREFIX;
-use qm_kafka::producer::EventNs;
+use qm_kafka::producer::{EventNs, EventType};
 use qm_mongodb::bson::doc;
 
 use qm_mongodb::bson::Document;
 use qm_mongodb::ClientSession;
 use qm_mongodb::DB;
 use qm_role::AccessLevel;
+use serde::Serialize;
 use sqlx::types::Uuid;
+use sqlx::PgConnection;
+use time::PrimitiveDateTime;
 
+use crate::mutation::add_purged_cleanup_task;
+use crate::mutation::purge_customers;
+use crate::mutation::purge_institutions;
+use crate::mutation::purge_organization_units;
+use crate::mutation::purge_organizations;
+use crate::mutation::purged_cleanup_task;
+use crate::mutation::remove_purged_cleanup_task;
 use qm_redis::AsyncWorker;
 pub use qm_redis::Producer;
 use qm_redis::Work;
@@ -70,8 +80,163 @@ impl AsRef<Producer> for CleanupProducer {
     }
 }
 
+/// Field paths of the owner ids in the documents of a collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerFields {
+    pub cid: String,
+    pub oid: String,
+    pub iid: String,
+    pub uid: String,
+}
+
+impl Default for OwnerFields {
+    fn default() -> Self {
+        Self::with_prefix("owner.entityId")
+    }
+}
+
+impl OwnerFields {
+    /// Owner fields below `prefix`, e.g. `owner.entityId.cid`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            cid: format!("{prefix}.cid"),
+            oid: format!("{prefix}.oid"),
+            iid: format!("{prefix}.iid"),
+            uid: format!("{prefix}.uid"),
+        }
+    }
+
+    fn query(&self, owners: &Owners) -> Document {
+        match owners {
+            Owners::Customers(cids) => doc! {
+                &self.cid: { "$in": cids },
+            },
+            Owners::Organizations(cids, oids) => doc! {
+                &self.cid: { "$in": cids },
+                &self.oid: { "$in": oids },
+            },
+            Owners::Institutions(cids, oids, iids) => doc! {
+                &self.cid: { "$in": cids },
+                &self.oid: { "$in": oids },
+                &self.iid: { "$in": iids },
+            },
+            Owners::OrganizationUnits(cids, uids) => doc! {
+                &self.cid: { "$in": cids },
+                &self.uid: { "$in": uids },
+            },
+        }
+    }
+}
+
+/// Hook of a registered collection, called before the documents of removed
+/// owners are deleted, e.g. to remove attached files.
+#[async_trait::async_trait]
+pub trait CleanupHook: Send + Sync {
+    async fn before_remove(
+        &self,
+        db: &DB,
+        collection: &str,
+        query: &Document,
+    ) -> anyhow::Result<()>;
+}
+
+/// Collection with documents owned by customers, organizations, institutions
+/// or organization units which are removed together with their owner.
+#[derive(Clone)]
+pub struct OwnedCollection {
+    name: Arc<str>,
+    fields: OwnerFields,
+    hook: Option<Arc<dyn CleanupHook>>,
+}
+
+impl OwnedCollection {
+    pub fn new(name: impl Into<Arc<str>>) -> Self {
+        Self {
+            name: name.into(),
+            fields: OwnerFields::default(),
+            hook: None,
+        }
+    }
+
+    pub fn with_owner_fields(mut self, fields: OwnerFields) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    pub fn with_hook(mut self, hook: impl CleanupHook + 'static) -> Self {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Owner ids of the removed entities.
+#[derive(Debug, PartialEq, Eq)]
+enum Owners {
+    Customers(Vec<i64>),
+    Organizations(Vec<i64>, Vec<i64>),
+    Institutions(Vec<i64>, Vec<i64>, Vec<i64>),
+    OrganizationUnits(Vec<i64>, Vec<i64>),
+}
+
+impl Owners {
+    fn new(ty: &CleanupTaskType) -> Option<Self> {
+        Some(match ty {
+            CleanupTaskType::Customers(ids) => {
+                Owners::Customers(ids.iter().map(CustomerId::unzip).collect())
+            }
+            CleanupTaskType::Organizations(ids) => {
+                let (cids, oids) = ids.iter().map(OrganizationId::unzip).unzip();
+                Owners::Organizations(cids, oids)
+            }
+            CleanupTaskType::Institutions(ids) => {
+                let (cids, (oids, iids)) = ids.iter().map(InstitutionId::untuple).unzip();
+                Owners::Institutions(cids, oids, iids)
+            }
+            CleanupTaskType::OrganizationUnits(ids) => {
+                let (cids, uids) = ids.iter().map(OrganizationUnitId::untuple).unzip();
+                Owners::OrganizationUnits(cids, uids)
+            }
+            CleanupTaskType::None => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionReport {
+    pub collection: String,
+    pub documents: u64,
+}
+
+/// Number of documents per registered collection which are removed by a
+/// cleanup task.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CleanupReport {
+    pub collections: Vec<CollectionReport>,
+}
+
+impl CleanupReport {
+    pub fn total(&self) -> u64 {
+        self.collections.iter().map(|c| c.documents).sum()
+    }
+}
+
+impl std::fmt::Display for CleanupReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} document(s)", self.total())?;
+        for c in self.collections.iter() {
+            write!(f, "\n  {}: {}", c.collection, c.documents)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct CleanupWorkerCtx<Auth, Store, Resource, Permission> {
     pub store: Store,
+    collections: Arc<[OwnedCollection]>,
     _marker: Marker<Auth, Store, Resource, Permission, ()>,
 }
 
@@ -79,9 +244,31 @@ impl<Auth, Store, Resource, Permission> CleanupWorkerCtx<Auth, Store, Resource,
     pub fn new(store: Store) -> Self {
         Self {
             store,
+            collections: Arc::from([]),
             _marker: std::marker::PhantomData,
         }
     }
+
+    /// Registers a collection whose documents are removed with their owner.
+    pub fn with_collection(mut self, collection: OwnedCollection) -> Self {
+        self.collections = self
+            .collections
+            .iter()
+            .cloned()
+            .chain(std::iter::once(collection))
+            .collect();
+        self
+    }
+
+    pub fn with_collections(self, collections: impl IntoIterator<Item = OwnedCollection>) -> Self {
+        collections
+            .into_iter()
+            .fold(self, |ctx, collection| ctx.with_collection(collection))
+    }
+
+    pub fn collections(&self) -> &[OwnedCollection] {
+        &self.collections
+    }
 }
 
 impl<Auth, Store, Resource, Permission> Clone
@@ -92,6 +279,7 @@ where
     fn clone(&self) -> Self {
         Self {
             store: self.store.clone(),
+            collections: self.collections.clone(),
             _marker: self._marker,
         }
     }
@@ -111,6 +299,63 @@ async fn remove_documents(
     Ok(result.deleted_count)
 }
 
+/// Removes the documents of the owners from the registered collections.
+async fn remove_owned_documents<Auth, Store, Resource, Permission>(
+    ctx: &CleanupWorkerCtx<Auth, Store, Resource, Permission>,
+    owners: &Owners,
+) -> anyhow::Result<CleanupReport>
+where
+    Store: RelatedStorage,
+{
+    if ctx.collections.is_empty() {
+        anyhow::bail!("no owned collections registered, documents cannot be removed");
+    }
+    let db: &DB = ctx.store.as_ref();
+    let mut session = db.session().await?;
+    let mut report = CleanupReport::default();
+    for collection in ctx.collections.iter() {
+        let query = collection.fields.query(owners);
+        if let Some(hook) = collection.hook.as_ref() {
+            hook.before_remove(db, &collection.name, &query).await?;
+        }
+        log::debug!("remove owned documents from db {}", collection.name);
+        let documents = remove_documents(db, &mut session, &collection.name, &query).await?;
+        report.collections.push(CollectionReport {
+            collection: collection.name.to_string(),
+            documents,
+        });
+    }
+    Ok(report)
+}
+
+/// Counts the documents which would be removed by a cleanup task of `ty`
+/// without removing anything, the queue is not touched.
+pub async fn dry_run<Auth, Store, Resource, Permission>(
+    ctx: &CleanupWorkerCtx<Auth, Store, Resource, Permission>,
+    ty: &CleanupTaskType,
+) -> anyhow::Result<CleanupReport>
+where
+    Store: RelatedStorage,
+{
+    let mut report = CleanupReport::default();
+    let Some(owners) = Owners::new(ty) else {
+        return Ok(report);
+    };
+    let db: &DB = ctx.store.as_ref();
+    for collection in ctx.collections.iter() {
+        let documents = db
+            .get()
+            .collection::<Document>(&collection.name)
+            .count_documents(collection.fields.query(&owners), None)
+            .await?;
+        report.collections.push(CollectionReport {
+            collection: collection.name.to_string(),
+            documents,
+        });
+    }
+    Ok(report)
+}
+
 async fn cleanup_customers<Auth, Store, Resource, Permission>(
     worker_ctx: WorkerContext<CleanupWorkerCtx<Auth, Store, Resource, Permission>>,
     ty: &str,
@@ -124,8 +369,6 @@ where
     Permission: RelatedPermission,
 {
     let store: &Store = &worker_ctx.ctx().store;
-    let db: &DB = store.as_ref();
-    let mut session = db.session().await?;
     let mut roles = BTreeSet::new();
     let existing_roles = store.cache_db().roles().await;
     let access_roles: Vec<&str> = existing_roles
@@ -152,27 +395,20 @@ where
         );
     }
     let cids: Vec<i64> = cids.iter().map(CustomerId::unzip).collect();
-    let query = doc! {
-        "owner.entityId.cid": {
-            "$in": &cids
-        },
-    };
-    for collection in db
-        .get()
-        .list_collection_names_with_session(None, &mut session)
-        .await?
-    {
-        log::debug!("remove all organization related resources from db {collection}");
-        remove_documents(db, &mut session, &collection, &query).await?;
-    }
+    let report = remove_owned_documents(worker_ctx.ctx(), &Owners::Customers(cids.clone())).await?;
+    log::debug!("removed {report}");
     log::debug!("cleanup roles");
     cleanup_roles(store.keycloak(), roles).await?;
-    // Emit the Kafka event
-    if let Some(producer) = store.mutation_event_producer() {
-        producer
-            .delete_event(&EventNs::Customer, "customer", cids)
-            .await?;
-    }
+    // Store the delete event, the outbox relay publishes it to Kafka
+    crate::outbox::add_event(
+        store.mutation_event_producer(),
+        store.customer_db().pool(),
+        &EventNs::Customer,
+        EventType::Delete,
+        "customer",
+        &cids,
+    )
+    .await?;
     worker_ctx.complete().await?;
     log::debug!("finished cleanup task '{ty}' with id '{id}'");
     Ok(())
@@ -211,8 +447,6 @@ where
     Permission: RelatedPermission,
 {
     let store: &Store = &worker_ctx.ctx().store;
-    let db: &DB = store.as_ref();
-    let mut session = db.session().await?;
     let mut roles = BTreeSet::new();
     let existing_roles = store.cache_db().roles().await;
     let access_roles: Vec<&str> = existing_roles
@@ -234,30 +468,22 @@ where
         );
     }
     let (cids, oids): (Vec<i64>, Vec<i64>) = strict_oids.iter().map(OrganizationId::unzip).unzip();
-    let query = doc! {
-        "owner.entityId.cid": {
-            "$in": &cids
-        },
-        "owner.entityId.oid": {
-            "$in": &oids
-        }
-    };
-    for collection in db
-        .get()
-        .list_collection_names_with_session(None, &mut session)
-        .await?
-    {
-        log::debug!("remove all organization related resources from db {collection}");
-        remove_documents(db, &mut session, &collection, &query).await?;
-    }
+    let report =
+        remove_owned_documents(worker_ctx.ctx(), &Owners::Organizations(cids.clone(), oids))
+            .await?;
+    log::debug!("removed {report}");
     log::debug!("cleanup roles");
     cleanup_roles(store.keycloak(), roles).await?;
-    // // Emit the Kafka event
-    if let Some(producer) = store.mutation_event_producer() {
-        producer
-            .delete_event(&EventNs::Organization, "organization", cids)
-            .await?;
-    }
+    // Store the delete event, the outbox relay publishes it to Kafka
+    crate::outbox::add_event(
+        store.mutation_event_producer(),
+        store.customer_db().pool(),
+        &EventNs::Organization,
+        EventType::Delete,
+        "organization",
+        &cids,
+    )
+    .await?;
     worker_ctx.complete().await?;
     log::debug!("finished cleanup task '{ty}' with id '{id}'");
     Ok(())
@@ -276,8 +502,6 @@ where
     Permission: RelatedPermission,
 {
     let store: &Store = &worker_ctx.ctx().store;
-    let db = store.as_ref();
-    let mut session = db.session().await?;
     let mut roles = BTreeSet::new();
     for id in strict_iids.iter() {
         roles.insert(
@@ -288,33 +512,21 @@ where
     }
     let (cids, (oids, iids)): (Vec<i64>, (Vec<i64>, Vec<i64>)) =
         strict_iids.iter().map(InstitutionId::untuple).unzip();
-    let query = doc! {
-        "owner.entityId.cid": {
-            "$in": &cids
-        },
-        "owner.entityId.oid": {
-            "$in": &oids
-        },
-        "owner.entityId.iid": {
-            "$in": &iids
-        }
-    };
-    for collection in db
-        .get()
-        .list_collection_names_with_session(None, &mut session)
-        .await?
-    {
-        log::debug!("remove all organization related resources from db {collection}");
-        remove_documents(db, &mut session, &collection, &query).await?;
-    }
+    let report =
+        remove_owned_documents(worker_ctx.ctx(), &Owners::Institutions(cids, oids, iids)).await?;
+    log::debug!("removed {report}");
     log::debug!("cleanup roles");
     cleanup_roles(store.keycloak(), roles).await?;
-    // // Emit the Kafka event
-    if let Some(producer) = store.mutation_event_producer() {
-        producer
-            .delete_event(&EventNs::Institution, "institution", strict_iids)
-            .await?;
-    }
+    // Store the delete event, the outbox relay publishes it to Kafka
+    crate::outbox::add_event(
+        store.mutation_event_producer(),
+        store.customer_db().pool(),
+        &EventNs::Institution,
+        EventType::Delete,
+        "institution",
+        &strict_iids,
+    )
+    .await?;
     worker_ctx.complete().await?;
     log::debug!("finished cleanup task '{ty}' with id '{id}'");
     Ok(())
@@ -333,8 +545,6 @@ where
     Permission: RelatedPermission,
 {
     let store: &Store = &worker_ctx.ctx().store;
-    let db: &DB = store.as_ref();
-    let mut session = db.session().await?;
     let mut roles = BTreeSet::new();
     for id in strict_uids.iter() {
         match id {
@@ -356,35 +566,110 @@ where
     }
     let (cids, uids): (Vec<i64>, Vec<i64>) =
         strict_uids.iter().map(OrganizationUnitId::untuple).unzip();
-    let query = doc! {
-        "owner.entityId.cid": {
-            "$in": &cids
-        },
-        "owner.entityId.uid": {
-            "$in": &uids
-        }
-    };
-    for collection in db
-        .get()
-        .list_collection_names_with_session(None, &mut session)
-        .await?
-    {
-        log::debug!("remove all organization unit related resources from db {collection}");
-        remove_documents(db, &mut session, &collection, &query).await?;
-    }
+    let report =
+        remove_owned_documents(worker_ctx.ctx(), &Owners::OrganizationUnits(cids, uids)).await?;
+    log::debug!("removed {report}");
     log::debug!("cleanup roles");
     cleanup_roles(store.keycloak(), roles).await?;
-    // Emit the Kafka event
-    if let Some(producer) = store.mutation_event_producer() {
-        producer
-            .delete_event(&EventNs::OrganizationUnit, "organization_unit", strict_uids)
-            .await?;
-    }
+    // Store the delete event, the outbox relay publishes it to Kafka
+    crate::outbox::add_event(
+        store.mutation_event_producer(),
+        store.customer_db().pool(),
+        &EventNs::OrganizationUnit,
+        EventType::Delete,
+        "organization_unit",
+        &strict_uids,
+    )
+    .await?;
     worker_ctx.complete().await?;
     log::debug!("finished cleanup task '{ty}' with id '{id}'");
     Ok(())
 }
 
+/// Deletes the rows which were not restored since `deleted_at` and stores the
+/// cleanup task of the purged entities with the same connection, so both are
+/// part of the transaction of the purge task `id`.
+async fn purge_entities(
+    con: &mut PgConnection,
+    id: &Uuid,
+    ty: &CleanupTaskType,
+    deleted_at: PrimitiveDateTime,
+) -> anyhow::Result<()> {
+    let purged = match ty {
+        CleanupTaskType::Customers(ids) => {
+            let v: Vec<i64> = ids.iter().map(CustomerId::unzip).collect();
+            let purged = purge_customers(&mut *con, &v, deleted_at).await?;
+            CleanupTaskType::Customers(
+                ids.iter()
+                    .filter(|id| purged.contains(&id.unzip()))
+                    .cloned()
+                    .collect(),
+            )
+        }
+        CleanupTaskType::Organizations(ids) => {
+            let v: Vec<i64> = ids.iter().map(OrganizationId::id).collect();
+            let purged = purge_organizations(&mut *con, &v, deleted_at).await?;
+            CleanupTaskType::Organizations(
+                ids.iter()
+                    .filter(|id| purged.contains(&id.id()))
+                    .cloned()
+                    .collect(),
+            )
+        }
+        CleanupTaskType::Institutions(ids) => {
+            let v: Vec<i64> = ids.iter().map(InstitutionId::id).collect();
+            let purged = purge_institutions(&mut *con, &v, deleted_at).await?;
+            CleanupTaskType::Institutions(
+                ids.iter()
+                    .filter(|id| purged.contains(&id.id()))
+                    .cloned()
+                    .collect(),
+            )
+        }
+        CleanupTaskType::OrganizationUnits(ids) => {
+            let v: Vec<i64> = ids.iter().map(OrganizationUnitId::id).collect();
+            let purged = purge_organization_units(&mut *con, &v, deleted_at).await?;
+            CleanupTaskType::OrganizationUnits(
+                ids.iter()
+                    .filter(|id| purged.contains(&id.id()))
+                    .cloned()
+                    .collect(),
+            )
+        }
+        CleanupTaskType::None => CleanupTaskType::None,
+    };
+    if !purged.is_empty() {
+        add_purged_cleanup_task(con, id, &CleanupTask::new(purged)).await?;
+    }
+    Ok(())
+}
+
+/// Purges the entities and queues the stored cleanup task once the purge is
+/// committed, returns `false` if all entities were restored.
+///
+/// The stored task is only removed after it was queued, a retry of the purge
+/// task queues it again if the rows are already gone.
+async fn purge<Store>(
+    store: &Store,
+    id: &Uuid,
+    ty: &CleanupTaskType,
+    deleted_at: PrimitiveDateTime,
+) -> anyhow::Result<bool>
+where
+    Store: RelatedStorage,
+{
+    let pool = store.customer_db().pool();
+    let mut tx = pool.begin().await?;
+    purge_entities(&mut tx, id, ty, deleted_at).await?;
+    tx.commit().await?;
+    let Some(task) = purged_cleanup_task(pool, id).await? else {
+        return Ok(false);
+    };
+    store.cleanup_task_producer().add_item(&task).await?;
+    remove_purged_cleanup_task(pool, id).await?;
+    Ok(true)
+}
+
 pub struct CleanupWorker;
 
 #[async_trait::async_trait]
@@ -406,18 +691,39 @@ where
             item.ty.as_ref(),
             item.id
         );
-        match &item.ty {
+        let ty = match item.deleted_at {
+            Some(deleted_at) => {
+                let queued = purge(&ctx.ctx().store, &item.id, &item.ty, deleted_at).await?;
+                if !queued {
+                    log::debug!(
+                        "skip cleanup task '{}' with id '{}', all entities were restored",
+                        item.ty.as_ref(),
+                        item.id
+                    );
+                } else {
+                    log::debug!(
+                        "purged entities of cleanup task '{}' with id '{}', queued their cleanup",
+                        item.ty.as_ref(),
+                        item.id
+                    );
+                }
+                ctx.complete().await?;
+                return Ok(());
+            }
+            None => item.ty,
+        };
+        match &ty {
             CleanupTaskType::Customers(ids) => {
-                cleanup_customers(ctx, item.ty.as_ref(), item.id, ids).await?;
+                cleanup_customers(ctx, ty.as_ref(), item.id, ids).await?;
             }
             CleanupTaskType::Organizations(ids) => {
-                cleanup_organizations(ctx, item.ty.as_ref(), item.id, ids).await?;
+                cleanup_organizations(ctx, ty.as_ref(), item.id, ids).await?;
             }
             CleanupTaskType::Institutions(ids) => {
-                cleanup_institutions(ctx, item.ty.as_ref(), item.id, ids).await?;
+                cleanup_institutions(ctx, ty.as_ref(), item.id, ids).await?;
             }
             CleanupTaskType::OrganizationUnits(ids) => {
-                cleanup_organization_units(ctx, item.ty.as_ref(), item.id, ids).await?;
+                cleanup_organization_units(ctx, ty.as_ref(), item.id, ids).await?;
             }
             CleanupTaskType::None => {
                 ctx.complete().await?;
@@ -438,6 +744,9 @@ where
     Resource: RelatedResource,
     Permission: RelatedPermission,
 {
+    if ctx.collections.is_empty() {
+        anyhow::bail!("cleanup worker requires at least one owned collection");
+    }
     workers
         .start(
             ctx,
@@ -448,3 +757,114 @@ where
         .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owners_test() {
+        let cids: CustomerIds = Arc::from([CustomerId::from(1), CustomerId::from(2)]);
+        assert_eq!(
+            Owners::new(&CleanupTaskType::Customers(cids)),
+            Some(Owners::Customers(vec![1, 2]))
+        );
+        let oids: OrganizationIds = Arc::from([OrganizationId::from((1, 2))]);
+        assert_eq!(
+            Owners::new(&CleanupTaskType::Organizations(oids)),
+            Some(Owners::Organizations(vec![1], vec![2]))
+        );
+        let iids: InstitutionIds = Arc::from([
+            InstitutionId::from((1, 2, 3)),
+            InstitutionId::from((1, 4, 5)),
+        ]);
+        assert_eq!(
+            Owners::new(&CleanupTaskType::Institutions(iids)),
+            Some(Owners::Institutions(vec![1, 1], vec![2, 4], vec![3, 5]))
+        );
+        let uids: OrganizationUnitIds = Arc::from([
+            OrganizationUnitId::from((1, 2)),
+            OrganizationUnitId::from((3, 4, 5)),
+        ]);
+        assert_eq!(
+            Owners::new(&CleanupTaskType::OrganizationUnits(uids)),
+            Some(Owners::OrganizationUnits(vec![1, 3], vec![2, 5]))
+        );
+        assert_eq!(Owners::new(&CleanupTaskType::None), None);
+    }
+
+    #[test]
+    fn owner_fields_query_test() {
+        let fields = OwnerFields::default();
+        assert_eq!(
+            fields.query(&Owners::Customers(vec![1, 2])),
+            doc! { "owner.entityId.cid": { "$in": [1_i64, 2_i64] } }
+        );
+        assert_eq!(
+            fields.query(&Owners::Organizations(vec![1], vec![2])),
+            doc! {
+                "owner.entityId.cid": { "$in": [1_i64] },
+                "owner.entityId.oid": { "$in": [2_i64] },
+            }
+        );
+        assert_eq!(
+            fields.query(&Owners::Institutions(vec![1], vec![2], vec![3])),
+            doc! {
+                "owner.entityId.cid": { "$in": [1_i64] },
+                "owner.entityId.oid": { "$in": [2_i64] },
+                "owner.entityId.iid": { "$in": [3_i64] },
+            }
+        );
+        assert_eq!(
+            OwnerFields::with_prefix("owner").query(&Owners::OrganizationUnits(vec![1], vec![2])),
+            doc! {
+                "owner.cid": { "$in": [1_i64] },
+                "owner.uid": { "$in": [2_i64] },
+            }
+        );
+    }
+
+    /// Runs against a database created from the customer migrations, see `mutation::tests`.
+    #[sqlx::test(migrations = "./migrations/customer")]
+    #[ignore = "requires DATABASE_URL"]
+    async fn purge_entities_test(pool: sqlx::PgPool) -> anyhow::Result<()> {
+        use crate::mutation::{
+            create_customer, next_customer_id, remove_customers, restore_customers,
+        };
+
+        let user = Uuid::new_v4();
+        let cid = next_customer_id(&pool).await?;
+        create_customer(&pool, cid, "Customer", None, &user).await?;
+        let id = Uuid::new_v4();
+        let ty = CleanupTaskType::Customers(Arc::from([CustomerId::from(cid)]));
+
+        // a failed commit keeps the rows and stores no cleanup task
+        let removal = remove_customers(&pool, &[cid], &user).await?.unwrap();
+        let mut tx = pool.begin().await?;
+        purge_entities(&mut tx, &id, &ty, removal.deleted_at).await?;
+        tx.rollback().await?;
+        assert!(purged_cleanup_task(&pool, &id).await?.is_none());
+        assert_eq!(restore_customers(&pool, &[cid]).await?, 1);
+
+        let removal = remove_customers(&pool, &[cid], &user).await?.unwrap();
+        let mut tx = pool.begin().await?;
+        purge_entities(&mut tx, &id, &ty, removal.deleted_at).await?;
+        tx.commit().await?;
+        let task = purged_cleanup_task(&pool, &id).await?.unwrap();
+        assert_eq!(task.ty, ty);
+        assert!(task.deleted_at.is_none());
+        assert_eq!(restore_customers(&pool, &[cid]).await?, 0);
+
+        // a retry after the rows are gone keeps the stored task until it is queued
+        let mut tx = pool.begin().await?;
+        purge_entities(&mut tx, &id, &ty, removal.deleted_at).await?;
+        tx.commit().await?;
+        assert_eq!(
+            purged_cleanup_task(&pool, &id).await?.map(|t| t.id),
+            Some(task.id)
+        );
+        remove_purged_cleanup_task(&pool, &id).await?;
+        assert!(purged_cleanup_task(&pool, &id).await?.is_none());
+        Ok(())
+    }
+}
@@ -3,17 +3,25 @@ use proc_macro::TokenStream;
 mod m2m;
 mod o2m;
 mod o2o;
+mod relation;
 
+/// Many to many relation between two entities, `m2m!(Appointment, Employee)`.
+///
+/// The relation macros implement `qm_entity::relation::Related` for both sides and generate a
+/// GraphQL object per side, `Employee::appointment_relation()` returns `EmployeeAppointmentRelation`
+/// with the `appointments` field, flatten it into the `ComplexObject` of `Employee`.
 #[proc_macro]
 pub fn m2m(item: TokenStream) -> TokenStream {
     m2m::expand(item)
 }
 
+/// One to many relation, the first entity owns the related entities, `o2m!(Employee, WorkTime)`.
 #[proc_macro]
 pub fn o2m(item: TokenStream) -> TokenStream {
     o2m::expand(item)
 }
 
+/// One to one relation, the first entity owns the related entity, `o2o!(Employee, Office)`.
 #[proc_macro]
 pub fn o2o(item: TokenStream) -> TokenStream {
     o2o::expand(item)
@@ -1,13 +1,8 @@
-use quote::quote;
-
-fn expand_impl(ast: syn::ExprTuple) -> syn::Result<proc_macro2::TokenStream> {
-    eprintln!("TODO: implement O2M, {ast:#?}");
-    Ok(quote!())
-}
+use crate::relation::{Kind, Relation};
 
 pub fn expand(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let ast = syn::parse_macro_input!(input as syn::ExprTuple);
-    expand_impl(ast)
+    let ast = syn::parse_macro_input!(input as Relation);
+    crate::relation::expand_impl(ast, Kind::OneToMany)
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }
@@ -0,0 +1,161 @@
+use inflector::Inflector;
+use proc_macro2::{Span, TokenStream};
+use proc_macro_crate::{crate_name, FoundCrate};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{Path, Token};
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    ManyToMany,
+    OneToMany,
+    OneToOne,
+}
+
+pub struct Relation {
+    left: Path,
+    right: Path,
+}
+
+impl Parse for Relation {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let left = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let right = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { left, right })
+    }
+}
+
+fn entity_crate() -> syn::Result<TokenStream> {
+    match crate_name("qm-entity") {
+        Ok(FoundCrate::Itself) => Ok(quote!(crate)),
+        Ok(FoundCrate::Name(name)) => {
+            let name = format_ident!("{name}");
+            Ok(quote!(::#name))
+        }
+        Err(_) => match crate_name("qm") {
+            Ok(FoundCrate::Itself) => Ok(quote!(crate::entity)),
+            Ok(FoundCrate::Name(name)) => {
+                let name = format_ident!("{name}");
+                Ok(quote!(::#name::entity))
+            }
+            Err(err) => Err(syn::Error::new(Span::call_site(), err)),
+        },
+    }
+}
+
+fn name(path: &Path) -> syn::Result<String> {
+    path.segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+        .ok_or_else(|| syn::Error::new_spanned(path, "expected entity type"))
+}
+
+struct Side<'a> {
+    ty: &'a Path,
+    name: String,
+    collection: String,
+    field: String,
+    single: bool,
+    owns: bool,
+}
+
+fn related_impl(krate: &TokenStream, links: &str, side: &Side, other: &Side) -> TokenStream {
+    let ty = side.ty;
+    let related_ty = other.ty;
+    let collection = &other.collection;
+    let field = &side.field;
+    let related_field = &other.field;
+    let single = side.single;
+    let owns = side.owns;
+    let (field_name, output, resolver) = if single {
+        (
+            format_ident!("{}", other.name.to_snake_case()),
+            quote!(::std::option::Option<#related_ty>),
+            quote!(resolve_related_one),
+        )
+    } else {
+        (
+            format_ident!("{}", collection),
+            quote!(::std::vec::Vec<#related_ty>),
+            quote!(resolve_related),
+        )
+    };
+    let relation_ty = format_ident!("{}{}Relation", side.name, other.name);
+    let relation_fn = format_ident!("{}_relation", other.name.to_snake_case());
+    let doc = format!(
+        "GraphQL field `{field_name}` of `{0}`, flatten `{0}::{relation_fn}()` into the object.",
+        side.name
+    );
+    quote! {
+        impl #krate::relation::Related<#related_ty> for #ty {
+            const LINKS: &'static str = #links;
+            const COLLECTION: &'static str = #collection;
+            const FIELD: &'static str = #field;
+            const RELATED_FIELD: &'static str = #related_field;
+            const SINGLE: bool = #single;
+            const OWNS: bool = #owns;
+        }
+
+        #[doc = #doc]
+        pub struct #relation_ty(#krate::ids::ID);
+
+        #[#krate::__private::async_graphql::Object]
+        impl #relation_ty {
+            async fn #field_name(
+                &self,
+                ctx: &#krate::__private::async_graphql::Context<'_>,
+            ) -> #krate::__private::async_graphql::FieldResult<#output> {
+                #krate::relation::#resolver::<#ty, #related_ty>(ctx, &self.0).await
+            }
+        }
+
+        impl #ty {
+            pub fn #relation_fn(&self) -> #relation_ty {
+                #relation_ty(*::std::convert::AsRef::<#krate::ids::ID>::as_ref(self))
+            }
+        }
+    }
+}
+
+pub fn expand_impl(relation: Relation, kind: Kind) -> syn::Result<TokenStream> {
+    let krate = entity_crate()?;
+    let left_name = name(&relation.left)?;
+    let right_name = name(&relation.right)?;
+    if left_name == right_name {
+        return Err(syn::Error::new_spanned(
+            &relation.right,
+            "relations of an entity to itself are not supported",
+        ));
+    }
+    let (left_single, right_single) = match kind {
+        Kind::ManyToMany => (false, false),
+        Kind::OneToMany => (false, true),
+        Kind::OneToOne => (true, true),
+    };
+    let owns = !matches!(kind, Kind::ManyToMany);
+    let left = Side {
+        ty: &relation.left,
+        name: left_name.clone(),
+        collection: left_name.to_table_case(),
+        field: format!("{}Id", left_name.to_camel_case()),
+        single: left_single,
+        owns,
+    };
+    let right = Side {
+        ty: &relation.right,
+        name: right_name.clone(),
+        collection: right_name.to_table_case(),
+        field: format!("{}Id", right_name.to_camel_case()),
+        single: right_single,
+        owns: false,
+    };
+    let links = format!("{}_{}", left.collection, right.collection);
+    let left_impl = related_impl(&krate, &links, &left, &right);
+    let right_impl = related_impl(&krate, &links, &right, &left);
+    Ok(quote! {
+        #left_impl
+        #right_impl
+    })
+}
@@ -0,0 +1,210 @@
+use async_graphql::connection::{Connection, CursorType, Edge, OpaqueCursor};
+use async_graphql::OutputType;
+use futures::stream::TryStreamExt;
+use qm_mongodb::bson::{doc, Document};
+use qm_mongodb::options::FindOptions;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::{EntityError, EntityResult};
+use crate::ids::ID;
+use crate::model::ConnectionFilter;
+use crate::Collection;
+
+/// Number of items of a page if neither `first` nor `last` is set.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// Connection with opaque cursors encoding the sort key `K` of the items.
+pub type KeyConnection<K, T> = Connection<OpaqueCursor<K>, T>;
+
+fn decode<K>(cursor: Option<&str>) -> EntityResult<Option<K>>
+where
+    K: Serialize + DeserializeOwned + Send + Sync,
+{
+    cursor
+        .map(|cursor| {
+            OpaqueCursor::<K>::decode_cursor(cursor)
+                .map(|cursor| cursor.0)
+                .map_err(|_| EntityError::bad_request("Cursor", "invalid cursor"))
+        })
+        .transpose()
+}
+
+impl ConnectionFilter {
+    pub fn after_key<K>(&self) -> EntityResult<Option<K>>
+    where
+        K: Serialize + DeserializeOwned + Send + Sync,
+    {
+        decode(self.after.as_deref())
+    }
+
+    pub fn before_key<K>(&self) -> EntityResult<Option<K>>
+    where
+        K: Serialize + DeserializeOwned + Send + Sync,
+    {
+        decode(self.before.as_deref())
+    }
+}
+
+/// Paginates in-memory items, the items are ordered by `key` to get stable pages between calls.
+pub fn paginate<T, K>(
+    mut items: Vec<T>,
+    filter: Option<&ConnectionFilter>,
+    key: impl Fn(&T) -> K,
+) -> EntityResult<KeyConnection<K, T>>
+where
+    T: OutputType,
+    K: Ord + Serialize + DeserializeOwned + Send + Sync,
+{
+    let default_filter = ConnectionFilter::default();
+    let filter = filter.unwrap_or(&default_filter);
+    items.sort_by_key(&key);
+    let after = filter.after_key::<K>()?;
+    let before = filter.before_key::<K>()?;
+    let mut start = after
+        .map(|after| items.partition_point(|item| key(item) <= after))
+        .unwrap_or(0);
+    let mut end = before
+        .map(|before| items.partition_point(|item| key(item) < before))
+        .unwrap_or(items.len())
+        .max(start);
+    match (filter.first, filter.last) {
+        (None, None) => end = end.min(start + DEFAULT_LIMIT),
+        (first, last) => {
+            if let Some(first) = first {
+                end = end.min(start + first);
+            }
+            if let Some(last) = last {
+                start = start.max(end.saturating_sub(last));
+            }
+        }
+    }
+    let mut connection = Connection::new(start > 0, end < items.len());
+    connection.edges.extend(
+        items
+            .drain(start..end)
+            .map(|item| Edge::new(OpaqueCursor(key(&item)), item)),
+    );
+    Ok(connection)
+}
+
+impl<T> Collection<T>
+where
+    T: DeserializeOwned + Send + Sync + Unpin,
+{
+    /// Keyset pagination ordered by `_id`.
+    pub async fn connection(
+        &self,
+        query: Option<Document>,
+        filter: Option<ConnectionFilter>,
+    ) -> EntityResult<KeyConnection<ID, T>>
+    where
+        T: AsRef<ID> + OutputType,
+    {
+        let filter = filter.unwrap_or_default();
+        let after = filter.after_key::<ID>()?;
+        let before = filter.before_key::<ID>()?;
+        let mut range = Document::new();
+        if let Some(after) = after {
+            range.insert("$gt", after);
+        }
+        if let Some(before) = before {
+            range.insert("$lt", before);
+        }
+        let query = match query.filter(|query| !query.is_empty()) {
+            Some(query) if !range.is_empty() => doc! { "$and": [ query, { "_id": range } ] },
+            Some(query) => query,
+            None if !range.is_empty() => doc! { "_id": range },
+            None => Document::new(),
+        };
+        let backward = filter.first.is_none() && filter.last.is_some();
+        let limit = if backward { filter.last } else { filter.first }.unwrap_or(DEFAULT_LIMIT);
+        let options = FindOptions::builder()
+            .sort(doc! { "_id": if backward { -1 } else { 1 } })
+            .limit(limit as i64 + 1)
+            .build();
+        let mut items: Vec<T> = self
+            .as_ref()
+            .find(query, options)
+            .await?
+            .try_collect()
+            .await?;
+        let has_more = items.len() > limit;
+        items.truncate(limit);
+        let (has_previous_page, has_next_page) = if backward {
+            items.reverse();
+            (has_more, before.is_some())
+        } else {
+            let mut has_previous_page = after.is_some();
+            if let Some(last) = filter.last {
+                let skip = items.len().saturating_sub(last);
+                has_previous_page |= skip > 0;
+                items.drain(..skip);
+            }
+            (has_previous_page, has_more)
+        };
+        let mut connection = Connection::new(has_previous_page, has_next_page);
+        connection.edges.extend(
+            items
+                .into_iter()
+                .map(|item| Edge::new(OpaqueCursor(*item.as_ref()), item)),
+        );
+        Ok(connection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::connection::CursorType;
+
+    use super::{paginate, DEFAULT_LIMIT};
+    use crate::model::ConnectionFilter;
+
+    fn page(filter: &ConnectionFilter) -> (Vec<i64>, bool, bool) {
+        let connection = paginate((0..10).rev().collect(), Some(filter), |v: &i64| *v).unwrap();
+        (
+            connection.edges.iter().map(|edge| edge.node).collect(),
+            connection.has_previous_page,
+            connection.has_next_page,
+        )
+    }
+
+    fn cursor(key: i64) -> Option<String> {
+        Some(async_graphql::connection::OpaqueCursor(key).encode_cursor())
+    }
+
+    #[test]
+    fn paginate_test() {
+        let filter = ConnectionFilter {
+            first: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(page(&filter), (vec![0, 1, 2], false, true));
+        let filter = ConnectionFilter {
+            first: Some(3),
+            after: cursor(2),
+            ..Default::default()
+        };
+        assert_eq!(page(&filter), (vec![3, 4, 5], true, true));
+        let filter = ConnectionFilter {
+            last: Some(2),
+            before: cursor(5),
+            ..Default::default()
+        };
+        assert_eq!(page(&filter), (vec![3, 4], true, true));
+        let filter = ConnectionFilter {
+            last: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(page(&filter), (vec![7, 8, 9], true, false));
+        assert_eq!(
+            page(&ConnectionFilter::default()).0.len(),
+            10.min(DEFAULT_LIMIT)
+        );
+        let filter = ConnectionFilter {
+            after: Some("invalid".to_string()),
+            ..Default::default()
+        };
+        assert!(paginate(vec![1_i64], Some(&filter), |v| *v).is_err());
+    }
+}
@@ -13,6 +13,9 @@ pub enum EntityError {
     /// A unhandled Database error occurred.
     #[error("{0}")]
     Database(#[from] qm_mongodb::error::Error),
+    /// A unhandled SQL database error occurred.
+    #[error("{0}")]
+    SqlDatabase(#[from] sqlx::Error),
     /// Keycloak request failure.
     #[error(transparent)]
     KeycloakRequest(#[from] reqwest::Error),
@@ -1,4 +1,5 @@
 use qm_mongodb::bson::oid::ObjectId;
+use qm_mongodb::bson::{doc, Document};
 
 use crate::ids::InfraContext;
 
@@ -33,6 +34,27 @@ pub struct OwnerId {
     pub uid: Option<i64>,
 }
 
+/// Field of an object containing the [Owner].
+pub const OWNER_FIELD: &str = "owner";
+
+impl OwnerId {
+    /// Filter on the [OWNER_FIELD] matching objects owned by this owner or one of its children.
+    pub fn filter(&self) -> Document {
+        let mut filter = Document::new();
+        for (key, value) in [
+            ("cid", self.cid),
+            ("oid", self.oid),
+            ("iid", self.iid),
+            ("uid", self.uid),
+        ] {
+            if let Some(value) = value {
+                filter.insert(format!("{OWNER_FIELD}.id.{key}"), value);
+            }
+        }
+        filter
+    }
+}
+
 impl From<CustomerId> for OwnerId {
     fn from(value: CustomerId) -> Self {
         Self {
@@ -116,6 +138,22 @@ pub enum OwnerType {
     CustomerUnit(OwnerId),
 }
 
+impl Owner {
+    pub fn new(o: OwnerType) -> Self {
+        Self { o }
+    }
+
+    pub fn ty(&self) -> &OwnerType {
+        &self.o
+    }
+}
+
+impl From<InfraContext> for Owner {
+    fn from(value: InfraContext) -> Self {
+        Self::new(value.into())
+    }
+}
+
 impl OwnerType {
     pub fn is_none(&self) -> bool {
         matches!(self, Self::None)
@@ -135,3 +173,59 @@ impl From<InfraContext> for OwnerType {
         }
     }
 }
+
+impl From<&InfraContext> for OwnerId {
+    fn from(value: &InfraContext) -> Self {
+        match *value {
+            InfraContext::Customer(v) => v.into(),
+            InfraContext::Organization(v) => v.into(),
+            InfraContext::Institution(v) => v.into(),
+            InfraContext::OrganizationUnit(v) => v.into(),
+        }
+    }
+}
+
+impl InfraContext {
+    /// Filter on the [OWNER_FIELD] matching objects within this context.
+    pub fn owner_filter(&self) -> Document {
+        let filter = OwnerId::from(self).filter();
+        match self {
+            InfraContext::OrganizationUnit(OrganizationUnitId::Customer(_)) => {
+                doc! { "$and": [ { format!("{OWNER_FIELD}.ty"): "CustomerUnit" }, filter ] }
+            }
+            InfraContext::OrganizationUnit(OrganizationUnitId::Organization(_)) => {
+                doc! { "$and": [ { format!("{OWNER_FIELD}.ty"): "InstitutionUnit" }, filter ] }
+            }
+            _ => filter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use qm_mongodb::bson::{doc, to_document};
+
+    use super::{OwnerType, OWNER_FIELD};
+    use crate::ids::{InfraContext, InstitutionId, OrganizationId};
+
+    #[test]
+    fn owner_filter_test() -> anyhow::Result<()> {
+        let context = InfraContext::Institution(InstitutionId::from((1, 2, 3)));
+        assert_eq!(
+            context.owner_filter(),
+            doc! { "owner.id.cid": 1_i64, "owner.id.oid": 2_i64, "owner.id.iid": 3_i64 }
+        );
+        let context = InfraContext::Organization(OrganizationId::from((1, 2)));
+        let owner = to_document(&OwnerType::from(context))?;
+        let id = owner.get_document("id")?;
+        assert_eq!(owner.get_str("ty")?, "Organization");
+        assert_eq!(
+            context.owner_filter(),
+            doc! {
+                format!("{OWNER_FIELD}.id.cid"): id.get_i64("cid")?,
+                format!("{OWNER_FIELD}.id.oid"): id.get_i64("oid")?,
+            }
+        );
+        Ok(())
+    }
+}
@@ -12,11 +12,16 @@ use qm_mongodb::options::FindOptions;
 use qm_mongodb::results::DeleteResult;
 use serde::{de::DeserializeOwned, Serialize};
 
+pub mod connection;
 pub mod ctx;
 pub mod error;
 pub mod ids;
 pub mod list;
 pub mod model;
+pub mod relation;
+pub mod repository;
+
+pub use qm_entity_derive::{m2m, o2m, o2o};
 
 pub trait MutatePermissions {
     fn create() -> Self;
@@ -202,6 +207,7 @@ pub trait Create<T, C: UserId> {
 #[doc(hidden)]
 pub mod __private {
     pub use crate::error::EntityError;
+    pub use async_graphql;
     #[doc(hidden)]
     pub use core::result::Result::Err;
 }
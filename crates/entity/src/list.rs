@@ -1,6 +1,9 @@
 use qm_mongodb::bson::Document;
 use serde::de::DeserializeOwned;
 
+use crate::connection::KeyConnection;
+use crate::ids::ID;
+use crate::model::ConnectionFilter;
 use crate::{error::EntityResult, model::ListResult};
 
 pub trait NewList<T>
@@ -43,4 +46,15 @@ where
         } = self.collection.list(self.query.take(), filter).await?;
         Ok(R::new(items, limit, total, page))
     }
+
+    /// Cursor based alternative to [Self::list], pages are ordered by `_id`.
+    pub async fn connection(
+        &mut self,
+        filter: Option<ConnectionFilter>,
+    ) -> EntityResult<KeyConnection<ID, T>>
+    where
+        T: AsRef<ID> + async_graphql::OutputType,
+    {
+        self.collection.connection(self.query.take(), filter).await
+    }
 }
@@ -28,6 +28,21 @@ pub struct ListFilter {
     pub limit: Option<usize>,
 }
 
+/// Arguments of a Relay-style connection, used alongside [ListFilter].
+///
+/// `first` and `after` select the items following the cursor, `last` and
+/// `before` the items preceding it. Cursors are opaque strings returned by
+/// the edges of a previous page.
+#[derive(
+    Default, Debug, Clone, InputObject, Serialize, Deserialize, Eq, PartialEq, Hash, PartialOrd, Ord,
+)]
+pub struct ConnectionFilter {
+    pub first: Option<usize>,
+    pub after: Option<String>,
+    pub last: Option<usize>,
+    pub before: Option<String>,
+}
+
 pub struct ListResult<T> {
     pub items: Vec<T>,
     pub limit: Option<i64>,
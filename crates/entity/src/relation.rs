@@ -0,0 +1,429 @@
+use async_graphql::{Context, FieldResult};
+use futures::stream::TryStreamExt;
+use qm_mongodb::bson::{doc, Document};
+use qm_mongodb::options::UpdateOptions;
+use serde::de::DeserializeOwned;
+
+use crate::ids::ID;
+use crate::Collection;
+
+/// Relation between two entities, generated by the `m2m!`, `o2m!` and `o2o!` macros.
+///
+/// Links are stored as join documents `{ <FIELD>: ID, <RELATED_FIELD>: ID }` in the `LINKS` collection.
+pub trait Related<R>: Sized {
+    /// Collection containing the join documents.
+    const LINKS: &'static str;
+    /// Collection containing the related entities.
+    const COLLECTION: &'static str;
+    /// Field of the join document referencing `Self`.
+    const FIELD: &'static str;
+    /// Field of the join document referencing the related entity.
+    const RELATED_FIELD: &'static str;
+    /// `Self` can be linked to one related entity at most.
+    const SINGLE: bool;
+    /// Related entities are removed together with `Self`.
+    const OWNS: bool;
+}
+
+impl<T> Collection<T>
+where
+    T: DeserializeOwned + Send + Sync + Unpin,
+{
+    fn links<R>(&self) -> qm_mongodb::Collection<Document>
+    where
+        T: Related<R>,
+    {
+        let ns = self.as_ref().namespace();
+        self.as_ref()
+            .client()
+            .database(&ns.db)
+            .collection(<T as Related<R>>::LINKS)
+    }
+
+    fn related_collection<R, D>(&self) -> qm_mongodb::Collection<D>
+    where
+        T: Related<R>,
+    {
+        let ns = self.as_ref().namespace();
+        self.as_ref()
+            .client()
+            .database(&ns.db)
+            .collection(<T as Related<R>>::COLLECTION)
+    }
+
+    /// Links the entity with `id` to the related entity with `related_id`.
+    ///
+    /// Existing links conflicting with a single side of the relation are replaced in the same
+    /// transaction, so concurrent links never leave more than one link on a single side.
+    ///
+    /// Transactions require a replica set or a sharded cluster, on a standalone server `link`
+    /// fails. A single node replica set is enough for development.
+    pub async fn link<R>(&self, id: &ID, related_id: &ID) -> qm_mongodb::error::Result<()>
+    where
+        T: Related<R>,
+        R: Related<T>,
+    {
+        let links = self.links::<R>();
+        let mut session = self.as_ref().client().start_session(None).await?;
+        session.start_transaction(None).await?;
+        for query in conflicting_links::<T, R>(id, related_id) {
+            links
+                .delete_many_with_session(query, None, &mut session)
+                .await?;
+        }
+        let link = link_document::<T, R>(id, related_id);
+        links
+            .update_one_with_session(
+                link.clone(),
+                doc! { "$setOnInsert": link },
+                UpdateOptions::builder().upsert(true).build(),
+                &mut session,
+            )
+            .await?;
+        session.commit_transaction().await
+    }
+
+    pub async fn unlink<R>(&self, id: &ID, related_id: &ID) -> qm_mongodb::error::Result<u64>
+    where
+        T: Related<R>,
+    {
+        let result = self
+            .links::<R>()
+            .delete_many(link_document::<T, R>(id, related_id), None)
+            .await?;
+        Ok(result.deleted_count)
+    }
+
+    pub async fn related_ids<R>(&self, id: &ID) -> qm_mongodb::error::Result<Vec<ID>>
+    where
+        T: Related<R>,
+    {
+        let related_field = <T as Related<R>>::RELATED_FIELD;
+        let links: Vec<Document> = self
+            .links::<R>()
+            .find(doc! { <T as Related<R>>::FIELD: id }, None)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(links
+            .iter()
+            .filter_map(|link| link.get_object_id(related_field).ok())
+            .collect())
+    }
+
+    /// Lookup of the related entities of the entity with `id`.
+    pub async fn related<R>(&self, id: &ID) -> qm_mongodb::error::Result<Vec<R>>
+    where
+        T: Related<R>,
+        R: DeserializeOwned + Send + Sync + Unpin,
+    {
+        let ids = self.related_ids::<R>(id).await?;
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        self.related_collection::<R, R>()
+            .find(doc! { "_id": { "$in": ids } }, None)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    pub async fn related_one<R>(&self, id: &ID) -> qm_mongodb::error::Result<Option<R>>
+    where
+        T: Related<R>,
+        R: DeserializeOwned + Send + Sync + Unpin,
+    {
+        let Some(related_id) = self.related_ids::<R>(id).await?.into_iter().next() else {
+            return Ok(None);
+        };
+        self.related_collection::<R, R>()
+            .find_one(doc! { "_id": related_id }, None)
+            .await
+    }
+
+    /// Lookup of the entities linked to the related entity with `related_id`.
+    pub async fn by_related<R>(&self, related_id: &ID) -> qm_mongodb::error::Result<Vec<T>>
+    where
+        T: Related<R>,
+    {
+        let field = <T as Related<R>>::FIELD;
+        let links: Vec<Document> = self
+            .links::<R>()
+            .find(doc! { <T as Related<R>>::RELATED_FIELD: related_id }, None)
+            .await?
+            .try_collect()
+            .await?;
+        let ids: Vec<ID> = links
+            .iter()
+            .filter_map(|link| link.get_object_id(field).ok())
+            .collect();
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        self.as_ref()
+            .find(doc! { "_id": { "$in": ids } }, None)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// Cascade delete hook, call it when the entity with `id` is removed.
+    ///
+    /// [Repository::delete](crate::repository::Repository::delete) calls it for the relations
+    /// registered with [Repository::with_relation](crate::repository::Repository::with_relation).
+    ///
+    /// Removes all links of the entity and, if the entity owns the relation, the related entities.
+    pub async fn remove_relations<R>(&self, id: &ID) -> qm_mongodb::error::Result<u64>
+    where
+        T: Related<R>,
+    {
+        if <T as Related<R>>::OWNS {
+            let ids = self.related_ids::<R>(id).await?;
+            if !ids.is_empty() {
+                self.related_collection::<R, Document>()
+                    .delete_many(doc! { "_id": { "$in": ids } }, None)
+                    .await?;
+            }
+        }
+        let result = self
+            .links::<R>()
+            .delete_many(doc! { <T as Related<R>>::FIELD: id }, None)
+            .await?;
+        Ok(result.deleted_count)
+    }
+}
+
+/// Join document of a link between `id` and `related_id`.
+fn link_document<T, R>(id: &ID, related_id: &ID) -> Document
+where
+    T: Related<R>,
+{
+    doc! {
+        <T as Related<R>>::FIELD: id,
+        <T as Related<R>>::RELATED_FIELD: related_id,
+    }
+}
+
+/// Queries of the links which have to be removed before `id` is linked to `related_id`.
+fn conflicting_links<T, R>(id: &ID, related_id: &ID) -> Vec<Document>
+where
+    T: Related<R>,
+    R: Related<T>,
+{
+    let field = <T as Related<R>>::FIELD;
+    let related_field = <T as Related<R>>::RELATED_FIELD;
+    let mut queries = vec![];
+    if <T as Related<R>>::SINGLE {
+        queries.push(doc! { field: id, related_field: { "$ne": related_id } });
+    }
+    if <R as Related<T>>::SINGLE {
+        queries.push(doc! { related_field: related_id, field: { "$ne": id } });
+    }
+    queries
+}
+
+fn collection<T, R>(ctx: &Context<'_>) -> FieldResult<Collection<T>>
+where
+    R: Related<T>,
+{
+    let db = ctx.data::<qm_mongodb::DB>().map_err(|err| {
+        log::warn!("qm::mongodb::DB is not installed in schema context");
+        err
+    })?;
+    Ok(Collection(
+        db.get().collection(<R as Related<T>>::COLLECTION),
+    ))
+}
+
+/// Resolves the related entities of a GraphQL object.
+pub async fn resolve_related<T, R>(ctx: &Context<'_>, id: &ID) -> FieldResult<Vec<R>>
+where
+    T: Related<R> + DeserializeOwned + Send + Sync + Unpin,
+    R: Related<T> + DeserializeOwned + Send + Sync + Unpin,
+{
+    Ok(collection::<T, R>(ctx)?.related::<R>(id).await?)
+}
+
+/// Resolves the related entity of a GraphQL object.
+pub async fn resolve_related_one<T, R>(ctx: &Context<'_>, id: &ID) -> FieldResult<Option<R>>
+where
+    T: Related<R> + DeserializeOwned + Send + Sync + Unpin,
+    R: Related<T> + DeserializeOwned + Send + Sync + Unpin,
+{
+    Ok(collection::<T, R>(ctx)?.related_one::<R>(id).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{conflicting_links, link_document, Related};
+    use crate::ids::ID;
+    use async_graphql::{
+        ComplexObject, EmptyMutation, EmptySubscription, Object, OutputType, SimpleObject,
+    };
+    use qm_mongodb::bson::doc;
+
+    macro_rules! entity {
+        ($name:ident) => {
+            #[derive(serde::Deserialize, SimpleObject)]
+            struct $name {
+                #[serde(rename = "_id")]
+                id: ID,
+            }
+
+            impl AsRef<ID> for $name {
+                fn as_ref(&self) -> &ID {
+                    &self.id
+                }
+            }
+        };
+    }
+
+    entity!(Appointment);
+    entity!(WorkTime);
+    entity!(Office);
+
+    #[derive(serde::Deserialize, SimpleObject)]
+    #[graphql(complex)]
+    struct Employee {
+        #[serde(rename = "_id")]
+        id: ID,
+    }
+
+    impl AsRef<ID> for Employee {
+        fn as_ref(&self) -> &ID {
+            &self.id
+        }
+    }
+
+    #[ComplexObject]
+    impl Employee {
+        #[graphql(flatten)]
+        async fn appointments(&self) -> EmployeeAppointmentRelation {
+            self.appointment_relation()
+        }
+
+        #[graphql(flatten)]
+        async fn work_times(&self) -> EmployeeWorkTimeRelation {
+            self.work_time_relation()
+        }
+
+        #[graphql(flatten)]
+        async fn office(&self) -> EmployeeOfficeRelation {
+            self.office_relation()
+        }
+    }
+
+    crate::m2m!(Appointment, Employee);
+    crate::o2m!(Employee, WorkTime);
+    crate::o2o!(Employee, Office);
+
+    /// Returns `(SINGLE, OWNS)` of the relation.
+    fn flags<T: Related<R>, R>() -> (bool, bool) {
+        (T::SINGLE, T::OWNS)
+    }
+
+    #[test]
+    fn m2m_relation_test() {
+        assert_eq!(
+            <Appointment as Related<Employee>>::LINKS,
+            "appointments_employees"
+        );
+        assert_eq!(<Appointment as Related<Employee>>::COLLECTION, "employees");
+        assert_eq!(<Appointment as Related<Employee>>::FIELD, "appointmentId");
+        assert_eq!(
+            <Appointment as Related<Employee>>::RELATED_FIELD,
+            "employeeId"
+        );
+        assert_eq!(
+            <Employee as Related<Appointment>>::COLLECTION,
+            "appointments"
+        );
+        assert_eq!(<Employee as Related<Appointment>>::FIELD, "employeeId");
+        assert_eq!(flags::<Appointment, Employee>(), (false, false));
+        assert_eq!(flags::<Employee, Appointment>(), (false, false));
+    }
+
+    #[test]
+    fn o2m_relation_test() {
+        assert_eq!(
+            <Employee as Related<WorkTime>>::LINKS,
+            "employees_work_times"
+        );
+        assert_eq!(<Employee as Related<WorkTime>>::COLLECTION, "work_times");
+        assert_eq!(<WorkTime as Related<Employee>>::RELATED_FIELD, "employeeId");
+        assert_eq!(flags::<Employee, WorkTime>(), (false, true));
+        assert_eq!(flags::<WorkTime, Employee>(), (true, false));
+    }
+
+    #[test]
+    fn o2o_relation_test() {
+        assert_eq!(<Employee as Related<Office>>::LINKS, "employees_offices");
+        assert_eq!(flags::<Employee, Office>(), (true, true));
+        assert_eq!(flags::<Office, Employee>(), (true, false));
+    }
+
+    #[test]
+    fn link_document_test() {
+        let id = ID::new();
+        let related_id = ID::new();
+        assert_eq!(
+            link_document::<Employee, WorkTime>(&id, &related_id),
+            doc! { "employeeId": id, "workTimeId": related_id }
+        );
+        assert_eq!(
+            link_document::<WorkTime, Employee>(&related_id, &id),
+            doc! { "workTimeId": related_id, "employeeId": id }
+        );
+    }
+
+    #[test]
+    fn conflicting_links_test() {
+        let id = ID::new();
+        let related_id = ID::new();
+        assert!(conflicting_links::<Appointment, Employee>(&id, &related_id).is_empty());
+        // A work time belongs to one employee, other employees lose the link.
+        assert_eq!(
+            conflicting_links::<Employee, WorkTime>(&id, &related_id),
+            vec![doc! { "workTimeId": related_id, "employeeId": { "$ne": id } }]
+        );
+        assert_eq!(
+            conflicting_links::<WorkTime, Employee>(&related_id, &id),
+            vec![doc! { "workTimeId": related_id, "employeeId": { "$ne": id } }]
+        );
+        assert_eq!(
+            conflicting_links::<Employee, Office>(&id, &related_id),
+            vec![
+                doc! { "employeeId": id, "officeId": { "$ne": related_id } },
+                doc! { "officeId": related_id, "employeeId": { "$ne": id } },
+            ]
+        );
+    }
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn employee(&self) -> Employee {
+            Employee { id: ID::new() }
+        }
+    }
+
+    fn schema() -> async_graphql::Schema<Query, EmptyMutation, EmptySubscription> {
+        async_graphql::Schema::build(Query, EmptyMutation, EmptySubscription).finish()
+    }
+
+    #[test]
+    fn relation_fields_test() {
+        let sdl = schema().sdl();
+        assert!(sdl.contains("appointments: [Appointment!]!"));
+        assert!(sdl.contains("workTimes: [WorkTime!]!"));
+        assert!(sdl.contains("office: Office"));
+    }
+
+    #[tokio::test]
+    async fn resolve_without_db_test() {
+        let response = schema().execute("{ employee { id office { id } } }").await;
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(response.errors[0].path.len(), 2);
+    }
+}
@@ -0,0 +1,573 @@
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use async_graphql::{ErrorExtensions, FieldResult};
+use chrono::Utc;
+use futures::future::BoxFuture;
+use qm_mongodb::bson::{doc, to_document, Bson, Document, Uuid};
+use qm_mongodb::error::{ErrorKind, WriteFailure};
+use qm_mongodb::options::{FindOneAndUpdateOptions, FindOneOptions, IndexOptions, ReturnDocument};
+use qm_mongodb::results::DeleteResult;
+use qm_mongodb::IndexModel;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::EntityError;
+use crate::ids::{InfraContext, Owner, ID, OWNER_FIELD};
+use crate::model::{ListFilter, ListResult, Modification};
+use crate::relation::Related;
+use crate::{conflicting_name, Collection};
+
+/// Entity stored in a [Repository].
+pub trait ScopedEntity: AsMut<Option<ID>> {
+    /// Field containing the creation [Modification], it is kept on updates.
+    const CREATED_FIELD: &'static str = "created";
+
+    fn id(&self) -> Option<&ID>;
+
+    /// Unique name per owner, used for conflict detection.
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    fn set_owner(&mut self, owner: Owner);
+
+    fn set_created(&mut self, created: Modification);
+
+    fn set_modified(&mut self, modified: Modification);
+}
+
+const DUPLICATE_KEY: i32 = 11000;
+
+fn is_duplicate_key(err: &qm_mongodb::error::Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(err)) => err.code == DUPLICATE_KEY,
+        ErrorKind::Command(err) => err.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+/// Unique index on the name per owner, create it on collections used by a [Repository]
+/// so that concurrent writes can not bypass the name check.
+pub fn name_index() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! {
+            format!("{OWNER_FIELD}.ty"): 1,
+            format!("{OWNER_FIELD}.id.cid"): 1,
+            format!("{OWNER_FIELD}.id.oid"): 1,
+            format!("{OWNER_FIELD}.id.iid"): 1,
+            format!("{OWNER_FIELD}.id.uid"): 1,
+            "name": 1,
+        })
+        .options(
+            IndexOptions::builder()
+                .name("owner_name_unique".to_string())
+                .unique(true)
+                .partial_filter_expression(doc! { "name": { "$exists": true } })
+                .build(),
+        )
+        .build()
+}
+
+/// Namespaces of the collections this process created the [name_index] on.
+static NAME_INDEXES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Entities with `name` and the exact owner tuple of the [name_index], other than `id`.
+///
+/// Fields missing in `owner` match missing fields, like the index does.
+fn name_query(name: &str, owner: Option<&Document>, id: Option<&ID>) -> Document {
+    let owner_id = owner.and_then(|owner| owner.get_document("id").ok());
+    let mut query = doc! {
+        format!("{OWNER_FIELD}.ty"): owner.and_then(|owner| owner.get("ty")).cloned().unwrap_or(Bson::Null),
+    };
+    for key in ["cid", "oid", "iid", "uid"] {
+        query.insert(
+            format!("{OWNER_FIELD}.id.{key}"),
+            owner_id
+                .and_then(|id| id.get(key))
+                .cloned()
+                .unwrap_or(Bson::Null),
+        );
+    }
+    query.insert("name", name);
+    if let Some(id) = id {
+        query.insert("_id", doc! { "$ne": id });
+    }
+    query
+}
+
+/// Maps a duplicate key error of the [name_index] to a name conflict.
+fn name_error<T, R>(err: qm_mongodb::error::Error, name: Option<&str>) -> FieldResult<R> {
+    match name {
+        Some(name) if is_duplicate_key(&err) => conflicting_name(&tynm::type_name::<T>(), name),
+        _ => Err(err.into()),
+    }
+}
+
+/// Fields of an update, the id, owner and creation of the stored entity are kept.
+fn update_document<T>(value: &T) -> qm_mongodb::bson::ser::Result<Document>
+where
+    T: ScopedEntity + Serialize,
+{
+    let mut replacement = to_document(value)?;
+    for field in ["_id", OWNER_FIELD, T::CREATED_FIELD] {
+        replacement.remove(field);
+    }
+    Ok(doc! { "$set": replacement })
+}
+
+/// Removes the relations of a deleted entity, registered with [Repository::with_relation].
+type RemoveRelations<T> =
+    for<'a> fn(&'a Collection<T>, &'a ID) -> BoxFuture<'a, qm_mongodb::error::Result<u64>>;
+
+fn remove_relations<'a, T, R>(
+    collection: &'a Collection<T>,
+    id: &'a ID,
+) -> BoxFuture<'a, qm_mongodb::error::Result<u64>>
+where
+    T: Related<R> + DeserializeOwned + Send + Sync + Unpin,
+    R: 'static,
+{
+    Box::pin(collection.remove_relations::<R>(id))
+}
+
+/// CRUD operations on a [Collection] scoped to the owner of the entities.
+///
+/// Every read is restricted by the owner filter, created and updated entities
+/// are stamped with the owner and the modification of the user.
+pub struct Repository<T> {
+    collection: Collection<T>,
+    context: Option<InfraContext>,
+    filter: Document,
+    user_id: Option<Uuid>,
+    relations: Vec<RemoveRelations<T>>,
+}
+
+impl<T> Repository<T> {
+    /// Repository scoped to `context`, `None` is unscoped and should only be used for admins.
+    ///
+    /// Call [Self::ensure_name_index] before writing, `AuthCtx::repository` does it.
+    pub fn new(collection: Collection<T>, context: Option<InfraContext>) -> Self {
+        let filter = context
+            .as_ref()
+            .map(InfraContext::owner_filter)
+            .unwrap_or_default();
+        Self {
+            collection,
+            context,
+            filter,
+            user_id: None,
+            relations: vec![],
+        }
+    }
+
+    /// Replaces the owner filter, e.g. with the result of `AuthCtx::build_context_query`.
+    pub fn with_filter(mut self, filter: Document) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// User stamped into the modifications of created and updated entities.
+    pub fn with_user_id(mut self, user_id: Uuid) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    /// Removes the relations to `R` of deleted entities, see [Collection::remove_relations].
+    pub fn with_relation<R>(mut self) -> Self
+    where
+        T: Related<R> + DeserializeOwned + Send + Sync + Unpin,
+        R: 'static,
+    {
+        self.relations.push(remove_relations::<T, R>);
+        self
+    }
+
+    pub fn context(&self) -> Option<&InfraContext> {
+        self.context.as_ref()
+    }
+
+    pub fn filter(&self) -> &Document {
+        &self.filter
+    }
+
+    pub fn collection(&self) -> &Collection<T> {
+        &self.collection
+    }
+
+    /// Creates the [name_index] on the collection, once per collection and process.
+    pub async fn ensure_name_index(&self) -> qm_mongodb::error::Result<()> {
+        let namespace = self.collection.as_ref().namespace().to_string();
+        if NAME_INDEXES.lock().unwrap().contains(&namespace) {
+            return Ok(());
+        }
+        self.collection
+            .as_ref()
+            .create_index(name_index(), None)
+            .await?;
+        NAME_INDEXES.lock().unwrap().insert(namespace);
+        Ok(())
+    }
+
+    fn scoped(&self, query: Document) -> Document {
+        if self.filter.is_empty() {
+            query
+        } else if query.is_empty() {
+            self.filter.clone()
+        } else {
+            doc! { "$and": [ self.filter.clone(), query ] }
+        }
+    }
+
+    fn modification(&self) -> Modification {
+        Modification {
+            user_id: self.user_id,
+            at: Utc::now(),
+        }
+    }
+}
+
+impl<T> Repository<T>
+where
+    T: DeserializeOwned + Send + Sync + Unpin,
+{
+    pub async fn find_one(&self, query: Document) -> qm_mongodb::error::Result<Option<T>> {
+        self.collection
+            .as_ref()
+            .find_one(self.scoped(query), None)
+            .await
+    }
+
+    pub async fn by_id(&self, id: &ID) -> qm_mongodb::error::Result<Option<T>> {
+        self.find_one(doc! { "_id": id }).await
+    }
+
+    pub async fn by_name(&self, name: &str) -> qm_mongodb::error::Result<Option<T>> {
+        self.find_one(doc! { "name": name }).await
+    }
+
+    pub async fn by_field(&self, field: &str, value: &str) -> qm_mongodb::error::Result<Option<T>> {
+        self.find_one(doc! { field: value }).await
+    }
+
+    pub async fn list(
+        &self,
+        query: Option<Document>,
+        filter: Option<ListFilter>,
+    ) -> qm_mongodb::error::Result<ListResult<T>> {
+        self.collection
+            .list(Some(self.scoped(query.unwrap_or_default())), filter)
+            .await
+    }
+
+    /// Removes the entity with `id`, returns `false` if it does not exist in the scope.
+    ///
+    /// The relations registered with [Self::with_relation] are removed with the entity.
+    pub async fn delete(&self, id: &ID) -> qm_mongodb::error::Result<bool> {
+        let result = self
+            .collection
+            .as_ref()
+            .delete_one(self.scoped(doc! { "_id": id }), None)
+            .await?;
+        if result.deleted_count == 0 {
+            return Ok(false);
+        }
+        self.remove_relations(&[*id]).await?;
+        Ok(true)
+    }
+
+    /// Removes the entities with `ids` in the scope together with their relations.
+    pub async fn delete_many(&self, ids: &[ID]) -> qm_mongodb::error::Result<DeleteResult> {
+        let query = self.scoped(doc! { "_id": { "$in": ids } });
+        let ids: Vec<ID> = if self.relations.is_empty() {
+            vec![]
+        } else {
+            self.collection
+                .as_ref()
+                .distinct("_id", query.clone(), None)
+                .await?
+                .iter()
+                .filter_map(Bson::as_object_id)
+                .collect()
+        };
+        let result = self.collection.as_ref().delete_many(query, None).await?;
+        self.remove_relations(&ids).await?;
+        Ok(result)
+    }
+
+    async fn remove_relations(&self, ids: &[ID]) -> qm_mongodb::error::Result<()> {
+        for id in ids {
+            for remove in &self.relations {
+                remove(&self.collection, id).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> Repository<T>
+where
+    T: ScopedEntity + Serialize + DeserializeOwned + Send + Sync + Unpin,
+{
+    fn stamp_owner(&self, value: &mut T) {
+        if let Some(context) = self.context {
+            value.set_owner(context.into());
+        }
+    }
+
+    /// Fails if another entity of `owner` has the name of `value`.
+    ///
+    /// The check uses the exact owner of the [name_index] and not the scope, which also
+    /// contains the entities of child owners.
+    async fn check_name(
+        &self,
+        value: &T,
+        owner: Option<&Document>,
+        id: Option<&ID>,
+    ) -> FieldResult<()> {
+        let Some(name) = value.name() else {
+            return Ok(());
+        };
+        let count = self
+            .collection
+            .as_ref()
+            .count_documents(name_query(name, owner, id), None)
+            .await?;
+        if count > 0 {
+            return conflicting_name(&tynm::type_name::<T>(), name);
+        }
+        Ok(())
+    }
+
+    /// Inserts a new entity, fails if an entity of the same owner has the same name.
+    pub async fn create(&self, mut value: T) -> FieldResult<T> {
+        self.stamp_owner(&mut value);
+        let owner = to_document(&value)?.get_document(OWNER_FIELD).ok().cloned();
+        self.check_name(&value, owner.as_ref(), None).await?;
+        let modification = self.modification();
+        value.set_created(modification.clone());
+        value.set_modified(modification);
+        let name = value.name().map(str::to_string);
+        match self.collection.save(value).await {
+            Ok(value) => Ok(value),
+            Err(err) => name_error::<T, T>(err, name.as_deref()),
+        }
+    }
+
+    /// Updates an existing entity, fails if it does not exist in the scope or
+    /// another entity of the same owner has the same name.
+    ///
+    /// The owner and the creation of the stored entity are kept, the stored entity is returned.
+    pub async fn update(&self, mut value: T) -> FieldResult<T> {
+        let id = *value
+            .id()
+            .ok_or_else(|| EntityError::bad_request("Update", "missing id").extend())?;
+        let stored = self
+            .collection
+            .as_ref()
+            .clone_with_type::<Document>()
+            .find_one(
+                self.scoped(doc! { "_id": id }),
+                FindOneOptions::builder()
+                    .projection(doc! { OWNER_FIELD: 1 })
+                    .build(),
+            )
+            .await?
+            .ok_or_else(|| EntityError::not_found_by_id::<T>(id.to_hex()).extend())?;
+        let owner = stored.get_document(OWNER_FIELD).ok();
+        self.check_name(&value, owner, Some(&id)).await?;
+        value.set_modified(self.modification());
+        let name = value.name().map(str::to_string);
+        let result = self
+            .collection
+            .as_ref()
+            .find_one_and_update(
+                self.scoped(doc! { "_id": id }),
+                update_document(&value)?,
+                FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await;
+        match result {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => Err(EntityError::not_found_by_id::<T>(id.to_hex()).extend()),
+            Err(err) => name_error::<T, T>(err, name.as_deref()),
+        }
+    }
+
+    /// Updates the entity if it has an id, otherwise creates it.
+    pub async fn upsert(&self, value: T) -> FieldResult<T> {
+        if value.id().is_some() {
+            self.update(value).await
+        } else {
+            self.create(value).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use qm_mongodb::bson::{doc, to_document, Bson, Document};
+    use qm_mongodb::options::{ClientOptions, ServerAddress};
+
+    use super::{name_index, name_query, update_document, Repository, ScopedEntity};
+    use crate::ids::{CustomerId, InfraContext, Owner, ID};
+    use crate::model::Modification;
+    use crate::relation::Related;
+    use crate::Collection;
+
+    #[derive(Default, serde::Deserialize, serde::Serialize)]
+    struct Item {
+        #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+        id: Option<ID>,
+        name: String,
+        owner: Option<Owner>,
+        created: Modification,
+        modified: Modification,
+    }
+
+    impl AsMut<Option<ID>> for Item {
+        fn as_mut(&mut self) -> &mut Option<ID> {
+            &mut self.id
+        }
+    }
+
+    impl ScopedEntity for Item {
+        fn id(&self) -> Option<&ID> {
+            self.id.as_ref()
+        }
+
+        fn name(&self) -> Option<&str> {
+            Some(&self.name)
+        }
+
+        fn set_owner(&mut self, owner: Owner) {
+            self.owner = Some(owner);
+        }
+
+        fn set_created(&mut self, created: Modification) {
+            self.created = created;
+        }
+
+        fn set_modified(&mut self, modified: Modification) {
+            self.modified = modified;
+        }
+    }
+
+    fn collection() -> Collection<Document> {
+        let options = ClientOptions::builder()
+            .hosts(vec![ServerAddress::Tcp {
+                host: "localhost".to_string(),
+                port: None,
+            }])
+            .build();
+        let client = qm_mongodb::Client::with_options(options).unwrap();
+        Collection(client.database("test").collection("items"))
+    }
+
+    /// Items linked to other items, like a parent item owning its children.
+    impl Related<Item> for Item {
+        const LINKS: &'static str = "items_items";
+        const COLLECTION: &'static str = "items";
+        const FIELD: &'static str = "parentId";
+        const RELATED_FIELD: &'static str = "childId";
+        const SINGLE: bool = false;
+        const OWNS: bool = true;
+    }
+
+    #[tokio::test]
+    async fn with_relation_test() {
+        let repository =
+            Repository::new(Collection(collection().0.clone_with_type::<Item>()), None);
+        assert!(repository.relations.is_empty());
+        let repository = repository.with_relation::<Item>();
+        assert_eq!(repository.relations.len(), 1);
+        // Deleting without ids does not remove any relations.
+        repository.remove_relations(&[]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn scoped_query_test() {
+        let repository = Repository::new(collection(), None);
+        assert_eq!(
+            repository.scoped(doc! { "name": "a" }),
+            doc! { "name": "a" }
+        );
+        let context = InfraContext::Customer(CustomerId::from(1));
+        let repository = Repository::new(collection(), Some(context));
+        assert_eq!(repository.scoped(doc! {}), doc! { "owner.id.cid": 1_i64 });
+        assert_eq!(
+            repository.scoped(doc! { "name": "a" }),
+            doc! { "$and": [ { "owner.id.cid": 1_i64 }, { "name": "a" } ] }
+        );
+    }
+
+    #[test]
+    fn update_document_test() -> anyhow::Result<()> {
+        let mut item = Item {
+            id: Some(ID::new()),
+            name: "a".to_string(),
+            ..Default::default()
+        };
+        item.set_owner(InfraContext::Customer(CustomerId::from(1)).into());
+        let update = update_document(&item)?;
+        let set = update.get_document("$set")?;
+        assert_eq!(
+            set.keys().collect::<Vec<_>>(),
+            vec!["name", "modified"],
+            "id, owner and creation of the stored entity must be kept"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn name_index_test() {
+        let index = name_index();
+        assert_eq!(
+            index.keys,
+            doc! {
+                "owner.ty": 1,
+                "owner.id.cid": 1,
+                "owner.id.oid": 1,
+                "owner.id.iid": 1,
+                "owner.id.uid": 1,
+                "name": 1,
+            }
+        );
+        let options = index.options.unwrap();
+        assert_eq!(options.unique, Some(true));
+        assert_eq!(
+            options.partial_filter_expression,
+            Some(doc! { "name": { "$exists": true } })
+        );
+    }
+
+    #[test]
+    fn name_query_test() -> anyhow::Result<()> {
+        let owner = to_document(&Owner::from(InfraContext::Customer(CustomerId::from(1))))?;
+        let id = ID::new();
+        let query = name_query("a", Some(&owner), Some(&id));
+        assert_eq!(
+            query,
+            doc! {
+                "owner.ty": "Customer",
+                "owner.id.cid": 1_i64,
+                "owner.id.oid": Bson::Null,
+                "owner.id.iid": Bson::Null,
+                "owner.id.uid": Bson::Null,
+                "name": "a",
+                "_id": { "$ne": id },
+            }
+        );
+        // The query has the fields of the unique index, entities of child owners do not match.
+        let index_keys: Vec<_> = name_index().keys.keys().cloned().collect();
+        let query_keys: Vec<_> = query.keys().take(index_keys.len()).cloned().collect();
+        assert_eq!(query_keys, index_keys);
+        assert_eq!(
+            name_query("a", None, None).get("owner.ty"),
+            Some(&Bson::Null)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,517 @@
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer as _, StreamConsumer};
+use rdkafka::message::{BorrowedMessage, Message};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::producer::{Event, EventNs, EventType};
+
+#[async_trait::async_trait]
+pub trait EventHandler<Ctx>: Send + Sync
+where
+    Ctx: Clone + Send + Sync + 'static,
+{
+    async fn handle(&self, ctx: &Ctx, event_ns: EventNs, event: Event) -> anyhow::Result<()>;
+
+    /// Called with the last error of an event which failed `max_attempts` times, see
+    /// [`ConsumerBuilder::with_max_attempts`]. The event is committed afterwards, e.g. store
+    /// it to process it later.
+    async fn dead_letter(&self, _ctx: &Ctx, event_ns: EventNs, event: Event, err: anyhow::Error) {
+        log::error!(
+            "skip {:?} event {:?} for type {} in {event_ns:?}: {err:#?}",
+            event.event,
+            event.id,
+            event.ty
+        );
+    }
+}
+
+/// Exponential backoff between retries of a failed event or after a Kafka error.
+#[derive(Debug, Clone)]
+struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            current: initial,
+        }
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+/// Waits for `delay`, returns `false` if the consumer is terminated meanwhile.
+async fn wait(rx: &mut watch::Receiver<bool>, delay: Duration) -> bool {
+    tokio::select! {
+        _ = rx.changed() => false,
+        _ = tokio::time::sleep(delay) => true,
+    }
+}
+
+#[derive(Debug)]
+enum Retry {
+    Done,
+    /// Last error after `max_attempts` failed attempts.
+    Exhausted(anyhow::Error),
+    Terminated,
+}
+
+/// Runs `f` until it succeeds or failed `max_attempts` times and waits with
+/// `backoff` between the attempts. Without `max_attempts` a failed event is
+/// never skipped.
+async fn retry<F, Fut>(
+    backoff: &mut Backoff,
+    rx: &mut watch::Receiver<bool>,
+    context: &str,
+    max_attempts: Option<u32>,
+    mut f: F,
+) -> Retry
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut attempts = 0;
+    loop {
+        match f().await {
+            Ok(()) => {
+                backoff.reset();
+                return Retry::Done;
+            }
+            Err(err) => {
+                attempts += 1;
+                if max_attempts.is_some_and(|max| attempts >= max) {
+                    backoff.reset();
+                    log::error!("{context}, failed {attempts} times");
+                    return Retry::Exhausted(err);
+                }
+                let delay = backoff.next_delay();
+                log::error!("{context}, retry in {delay:?}: {err:#?}");
+                if !wait(rx, delay).await {
+                    return Retry::Terminated;
+                }
+            }
+        }
+    }
+}
+
+/// Handles the event with retries, an event which failed `max_attempts` times is passed
+/// to [`EventHandler::dead_letter`]. Returns `false` if the consumer is terminated before
+/// the event is handled.
+async fn handle_event<Ctx>(
+    handler: &dyn EventHandler<Ctx>,
+    ctx: &Ctx,
+    (event_ns, event): (EventNs, Event),
+    backoff: &mut Backoff,
+    rx: &mut watch::Receiver<bool>,
+    context: &str,
+    max_attempts: Option<u32>,
+) -> bool
+where
+    Ctx: Clone + Send + Sync + 'static,
+{
+    let result = retry(backoff, rx, context, max_attempts, || {
+        handler.handle(ctx, event_ns, event.clone())
+    })
+    .await;
+    match result {
+        Retry::Done => true,
+        Retry::Exhausted(err) => {
+            handler.dead_letter(ctx, event_ns, event, err).await;
+            true
+        }
+        Retry::Terminated => false,
+    }
+}
+
+pub struct ConsumerBuilder {
+    env_prefix: Option<&'static str>,
+    group: String,
+    event_ns: Vec<EventNs>,
+    event_types: Vec<EventType>,
+    backoff: Backoff,
+    max_attempts: Option<u32>,
+}
+
+impl ConsumerBuilder {
+    pub fn new<S>(group: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            env_prefix: None,
+            group: group.into(),
+            event_ns: vec![],
+            event_types: vec![],
+            backoff: Backoff::new(Duration::from_millis(100), Duration::from_secs(30)),
+            max_attempts: None,
+        }
+    }
+
+    pub fn with_env_prefix(mut self, prefix: &'static str) -> Self {
+        self.env_prefix = Some(prefix);
+        self
+    }
+
+    pub fn with_event_ns(mut self, event_ns: EventNs) -> Self {
+        self.event_ns.push(event_ns);
+        self
+    }
+
+    pub fn with_event_type(mut self, event_type: EventType) -> Self {
+        self.event_types.push(event_type);
+        self
+    }
+
+    /// Delays between the retries of a failed event, doubled after each
+    /// attempt up to `max`. Defaults to 100ms and 30s.
+    pub fn with_retry_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = Backoff::new(initial, max);
+        self
+    }
+
+    /// Number of failed attempts after which an event is passed to
+    /// [`EventHandler::dead_letter`] and skipped. By default a failed event is
+    /// retried until it succeeds and blocks its partition meanwhile.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts.max(1));
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<Consumer> {
+        let mut config_builder = Config::builder();
+        if let Some(prefix) = self.env_prefix {
+            config_builder = config_builder.with_prefix(prefix);
+        }
+        let config = config_builder.build()?;
+        let group_id = format!(
+            "{}_{}",
+            config.consumer_group_mutation_events_prefix(),
+            self.group
+        );
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", config.address())
+            .set("group.id", &group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .set("session.timeout.ms", "6000")
+            .create()?;
+        consumer.subscribe(&[config.topic_mutation_events()])?;
+        log::info!(
+            "consumer group '{group_id}' subscribed to '{}'",
+            config.topic_mutation_events()
+        );
+        Ok(Consumer {
+            inner: Arc::new(Inner {
+                config,
+                consumer,
+                group_id,
+                event_ns: self.event_ns,
+                event_types: self.event_types,
+                backoff: self.backoff,
+                max_attempts: self.max_attempts,
+            }),
+        })
+    }
+}
+
+struct Inner {
+    config: Config,
+    consumer: StreamConsumer,
+    group_id: String,
+    event_ns: Vec<EventNs>,
+    event_types: Vec<EventType>,
+    backoff: Backoff,
+    max_attempts: Option<u32>,
+}
+
+#[derive(Clone)]
+pub struct Consumer {
+    inner: Arc<Inner>,
+}
+
+impl Consumer {
+    pub fn builder<S>(group: S) -> ConsumerBuilder
+    where
+        S: Into<String>,
+    {
+        ConsumerBuilder::new(group)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.inner.config
+    }
+
+    pub fn group_id(&self) -> &str {
+        &self.inner.group_id
+    }
+
+    fn accepts(&self, event_ns: &EventNs, event_type: &EventType) -> bool {
+        (self.inner.event_ns.is_empty() || self.inner.event_ns.contains(event_ns))
+            && (self.inner.event_types.is_empty() || self.inner.event_types.contains(event_type))
+    }
+
+    /// Event of the message, `None` for invalid messages and events which are not consumed.
+    fn parse(&self, message: &BorrowedMessage<'_>) -> Option<(EventNs, Event)> {
+        let key = message.key_view::<str>().and_then(Result::ok);
+        let Some(event_ns) = key.and_then(|key| EventNs::from_str(key).ok()) else {
+            log::warn!(
+                "skip message without valid event namespace at offset {}",
+                message.offset()
+            );
+            return None;
+        };
+        let Some(payload) = message.payload() else {
+            log::warn!("skip empty message at offset {}", message.offset());
+            return None;
+        };
+        let event: Event = match serde_json::from_slice(payload) {
+            Ok(event) => event,
+            Err(err) => {
+                log::error!(
+                    "invalid event at offset {}: {err:#?} Payload: {}",
+                    message.offset(),
+                    String::from_utf8_lossy(payload)
+                );
+                return None;
+            }
+        };
+        if !self.accepts(&event_ns, &event.event) {
+            return None;
+        }
+        log::debug!(
+            "consume {:?} event for type {} in '{}'",
+            event.event,
+            event.ty,
+            self.inner.group_id
+        );
+        Some((event_ns, event))
+    }
+
+    pub fn start<Ctx>(&self, ctx: Ctx, handler: impl EventHandler<Ctx> + 'static) -> RunningConsumer
+    where
+        Ctx: Clone + Send + Sync + 'static,
+    {
+        let (tx, mut rx) = watch::channel(false);
+        let consumer = self.clone();
+        let handle = tokio::spawn(async move {
+            let group_id = consumer.inner.group_id.as_str();
+            let mut backoff = consumer.inner.backoff.clone();
+            log::info!("start consumer '{group_id}'");
+            loop {
+                let message = tokio::select! {
+                    _ = rx.changed() => break,
+                    message = consumer.inner.consumer.recv() => message,
+                };
+                let message = match message {
+                    Ok(message) => message,
+                    Err(err) => {
+                        let delay = backoff.next_delay();
+                        log::error!("kafka error in '{group_id}', retry in {delay:?}: {err:#?}");
+                        if !wait(&mut rx, delay).await {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                backoff.reset();
+                let context = format!(
+                    "unable to handle event at offset {} in '{group_id}'",
+                    message.offset()
+                );
+                if let Some(event) = consumer.parse(&message) {
+                    let handled = handle_event(
+                        &handler,
+                        &ctx,
+                        event,
+                        &mut backoff,
+                        &mut rx,
+                        &context,
+                        consumer.inner.max_attempts,
+                    )
+                    .await;
+                    if !handled {
+                        break;
+                    }
+                }
+                // A failed commit is repeated by the commit of the next message.
+                if let Err(err) = consumer
+                    .inner
+                    .consumer
+                    .commit_message(&message, CommitMode::Async)
+                {
+                    log::error!(
+                        "unable to commit offset {} in '{group_id}': {err:#?}",
+                        message.offset()
+                    );
+                }
+            }
+            log::info!("stopped consumer '{group_id}'");
+            Ok(())
+        });
+        RunningConsumer { tx, handle }
+    }
+}
+
+pub struct RunningConsumer {
+    tx: watch::Sender<bool>,
+    handle: JoinHandle<anyhow::Result<()>>,
+}
+
+impl RunningConsumer {
+    pub async fn terminate(self) -> anyhow::Result<()> {
+        self.tx.send(true).ok();
+        self.handle.await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handle_event, retry, Backoff, EventHandler, Retry};
+    use crate::producer::{Event, EventNs, EventType};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio::sync::watch;
+
+    #[test]
+    fn backoff_test() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(350));
+        assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+        assert_eq!(backoff.next_delay(), Duration::from_millis(200));
+        assert_eq!(backoff.next_delay(), Duration::from_millis(350));
+        assert_eq!(backoff.next_delay(), Duration::from_millis(350));
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn retry_until_success_test() {
+        let (_tx, mut rx) = watch::channel(false);
+        let mut backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(4));
+        let attempts = AtomicUsize::new(0);
+        let result = retry(&mut backoff, &mut rx, "test", None, || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 3 {
+                anyhow::bail!("failed");
+            }
+            Ok(())
+        })
+        .await;
+        assert!(matches!(result, Retry::Done));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(backoff.next_delay(), Duration::from_millis(1));
+    }
+
+    #[tokio::test]
+    async fn retry_stops_on_terminate_test() {
+        let (tx, mut rx) = watch::channel(false);
+        let mut backoff = Backoff::new(Duration::from_secs(60), Duration::from_secs(60));
+        let attempts = AtomicUsize::new(0);
+        tx.send(true).unwrap();
+        let result = retry(&mut backoff, &mut rx, "test", Some(3), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            anyhow::bail!("failed")
+        })
+        .await;
+        assert!(matches!(result, Retry::Terminated));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_exhausted_test() {
+        let (_tx, mut rx) = watch::channel(false);
+        let mut backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(4));
+        let attempts = AtomicUsize::new(0);
+        let result = retry(&mut backoff, &mut rx, "test", Some(3), || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            anyhow::bail!("failed attempt {attempt}")
+        })
+        .await;
+        match result {
+            Retry::Exhausted(err) => assert_eq!(err.to_string(), "failed attempt 3"),
+            result => panic!("unexpected result {result:?}"),
+        }
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(backoff.next_delay(), Duration::from_millis(1));
+    }
+
+    /// Handler which always fails and records the dead letters.
+    #[derive(Default)]
+    struct FailingHandler {
+        attempts: AtomicUsize,
+        dead_letters: Mutex<Vec<(EventNs, Option<String>, String)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EventHandler<()> for FailingHandler {
+        async fn handle(&self, _ctx: &(), _event_ns: EventNs, _event: Event) -> anyhow::Result<()> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            anyhow::bail!("poison")
+        }
+
+        async fn dead_letter(
+            &self,
+            _ctx: &(),
+            event_ns: EventNs,
+            event: Event,
+            err: anyhow::Error,
+        ) {
+            self.dead_letters
+                .lock()
+                .unwrap()
+                .push((event_ns, event.id, err.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn handle_event_dead_letter_test() {
+        let (_tx, mut rx) = watch::channel(false);
+        let mut backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(4));
+        let handler = FailingHandler::default();
+        let event = Event {
+            id: Some("1".to_string()),
+            event: EventType::Create,
+            ty: "customer".to_string(),
+            object: serde_json::json!([1]),
+        };
+        let handled = handle_event(
+            &handler,
+            &(),
+            (EventNs::Customer, event),
+            &mut backoff,
+            &mut rx,
+            "test",
+            Some(2),
+        )
+        .await;
+        // The event is skipped, so the consumer commits it and continues.
+        assert!(handled);
+        assert_eq!(handler.attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            *handler.dead_letters.lock().unwrap(),
+            vec![(
+                EventNs::Customer,
+                Some("1".to_string()),
+                "poison".to_string()
+            )]
+        );
+    }
+}
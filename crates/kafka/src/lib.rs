@@ -1,3 +1,4 @@
 pub mod config;
+pub mod consumer;
 pub mod producer;
 pub mod topics;
@@ -6,6 +6,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::config::Config;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EventNs {
     Customer,
     Organization,
@@ -52,7 +53,7 @@ impl FromStr for EventNs {
     }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum EventType {
     Create,
     Update,
@@ -60,8 +61,35 @@ pub enum EventType {
     Link,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+impl AsRef<str> for EventType {
+    fn as_ref(&self) -> &str {
+        match self {
+            EventType::Create => "create",
+            EventType::Update => "update",
+            EventType::Delete => "delete",
+            EventType::Link => "link",
+        }
+    }
+}
+
+impl FromStr for EventType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(EventType::Create),
+            "update" => Ok(EventType::Update),
+            "delete" => Ok(EventType::Delete),
+            "link" => Ok(EventType::Link),
+            _ => Err(anyhow::anyhow!("variant not found '{s}' for Event type")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Event {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub event: EventType,
     pub ty: String,
     pub object: serde_json::Value,
@@ -175,24 +203,33 @@ impl Producer {
         log::debug!("{event_name} event for type: {ty}");
         let object = serde_json::to_value(object)?;
         let event = Event {
+            id: None,
             event,
             ty: ty.to_string(),
             object,
         };
-        let event = serde_json::to_string(&event)?;
+        self.send_event(event_ns.as_ref(), &event).await
+    }
+
+    pub async fn send_event(&self, event_ns: &str, event: &Event) -> anyhow::Result<()> {
+        let payload = serde_json::to_string(event)?;
         let (a, b) = self
             .inner
             .producer
             .send_result(
                 FutureRecord::to(self.inner.config.topic_mutation_events())
-                    .key(event_ns.as_ref())
-                    .payload(&event)
+                    .key(event_ns)
+                    .payload(&payload)
                     .timestamp(now()),
             )
             .map_err(|e| anyhow::anyhow!("{e:#?}"))?
             .await?
             .map_err(|e| anyhow::anyhow!("{e:#?}"))?;
-        log::debug!("produced {event_name} event for type {ty} with partition {a} and offset {b}");
+        log::debug!(
+            "produced {} event for type {} with partition {a} and offset {b}",
+            event.event.as_ref(),
+            event.ty
+        );
         Ok(())
     }
 }
@@ -240,6 +240,22 @@ impl Keycloak {
     }
 
     pub async fn all_roles(&self, realm: &str) -> Result<Vec<RoleRepresentation>, KeycloakError> {
+        self.all_roles_paged(realm, true).await
+    }
+
+    /// All realm roles including their attributes.
+    pub async fn all_roles_with_attributes(
+        &self,
+        realm: &str,
+    ) -> Result<Vec<RoleRepresentation>, KeycloakError> {
+        self.all_roles_paged(realm, false).await
+    }
+
+    async fn all_roles_paged(
+        &self,
+        realm: &str,
+        brief_representation: bool,
+    ) -> Result<Vec<RoleRepresentation>, KeycloakError> {
         let page_offset = 1000;
         let mut offset = 0;
         let mut roles = vec![];
@@ -247,7 +263,13 @@ impl Keycloak {
             let result = self
                 .inner
                 .admin
-                .realm_roles_get(realm, Some(true), Some(offset), Some(page_offset), None)
+                .realm_roles_get(
+                    realm,
+                    Some(brief_representation),
+                    Some(offset),
+                    Some(page_offset),
+                    None,
+                )
                 .await?;
             if result.is_empty() {
                 break;
@@ -277,6 +299,18 @@ impl Keycloak {
         self.inner.admin.realm_roles_post(realm, rep).await
     }
 
+    pub async fn update_role(
+        &self,
+        realm: &str,
+        role_name: &str,
+        rep: RoleRepresentation,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_roles_with_role_name_put(realm, role_name, rep)
+            .await
+    }
+
     pub async fn create_group(
         &self,
         realm: &str,
@@ -332,6 +366,52 @@ impl Keycloak {
             .await
     }
 
+    pub async fn sub_groups(
+        &self,
+        realm: &str,
+        parent_id: &str,
+    ) -> Result<Vec<GroupRepresentation>, KeycloakError> {
+        self.inner
+            .admin
+            .realm_groups_with_group_id_children_get(realm, parent_id, Some(false), None, None)
+            .await
+    }
+
+    pub async fn update_group(
+        &self,
+        realm: &str,
+        id: &str,
+        rep: GroupRepresentation,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_groups_with_group_id_put(realm, id, rep)
+            .await
+    }
+
+    pub async fn realm_role_mappings_by_group_id(
+        &self,
+        realm: &str,
+        id: &str,
+    ) -> Result<Vec<RoleRepresentation>, KeycloakError> {
+        self.inner
+            .admin
+            .realm_groups_with_group_id_role_mappings_realm_get(realm, id)
+            .await
+    }
+
+    pub async fn remove_realm_role_mappings_by_group_id(
+        &self,
+        realm: &str,
+        id: &str,
+        roles: Vec<RoleRepresentation>,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_groups_with_group_id_role_mappings_realm_delete(realm, id, roles)
+            .await
+    }
+
     pub async fn user_by_id(
         &self,
         realm: &str,
@@ -486,6 +566,23 @@ impl Keycloak {
         Ok(())
     }
 
+    /// Sends an email with links to execute the required `actions`, e.g.
+    /// `UPDATE_PASSWORD`. The links are valid for `lifespan` seconds.
+    pub async fn execute_actions_email(
+        &self,
+        realm: &str,
+        user_id: &str,
+        actions: Vec<String>,
+        lifespan: Option<i32>,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_users_with_user_id_execute_actions_email_put(
+                realm, user_id, None, lifespan, None, actions,
+            )
+            .await
+    }
+
     pub async fn add_user_to_group(
         &self,
         realm: &str,
@@ -511,6 +608,18 @@ impl Keycloak {
             .await
     }
 
+    pub async fn remove_user_role(
+        &self,
+        realm: &str,
+        user_id: &str,
+        role: RoleRepresentation,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_users_with_user_id_role_mappings_realm_delete(realm, user_id, vec![role])
+            .await
+    }
+
     pub async fn remove_user_from_group(
         &self,
         realm: &str,
@@ -15,6 +15,7 @@ pub mod session;
 pub use client::*;
 pub mod config;
 pub mod realm;
+pub mod reconcile;
 pub mod schema;
 pub mod token;
 pub mod validation;
@@ -1,5 +1,8 @@
+use crate::reconcile::{RealmPlan, RealmReconciler};
 use crate::schema::UserInput;
 use crate::validation::context::{Config, ValidationContext};
+use crate::validation::policy::RealmPolicy;
+use crate::validation::report::{validate_and_fix, Fix, RealmValidationReport};
 use crate::validation::updater::{get_smtp_server_defaults, update_for_errors};
 use crate::validation::validator::validate_realm;
 use crate::Keycloak;
@@ -8,6 +11,7 @@ use crate::{
     CredentialRepresentation, GroupRepresentation, RoleRepresentation, UserRepresentation,
 };
 use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::OnceLock;
 
 use qm_role::Group;
 
@@ -16,6 +20,19 @@ lazy_static::lazy_static! {
     static ref APP_URL: String = std::env::var("SERVER_APP_URL").unwrap_or_else(|_| "http://localhost:5173".to_string());
 }
 
+static REALM_POLICY: OnceLock<RealmPolicy> = OnceLock::new();
+
+/// Realm policy loaded from the environment, call it at startup to fail early on an
+/// invalid policy. The policy is kept once it was loaded successfully.
+pub fn realm_policy() -> anyhow::Result<&'static RealmPolicy> {
+    if let Some(policy) = REALM_POLICY.get() {
+        return Ok(policy);
+    }
+    let policy =
+        RealmPolicy::load().map_err(|err| anyhow::anyhow!("invalid realm policy: {err:#}"))?;
+    Ok(REALM_POLICY.get_or_init(|| policy))
+}
+
 pub async fn create(keycloak: &Keycloak) -> anyhow::Result<()> {
     let realm = keycloak.config().realm();
     let url = APP_URL.as_str();
@@ -43,6 +60,7 @@ pub async fn create(keycloak: &Keycloak) -> anyhow::Result<()> {
             realm,
             keycloak: keycloak.config(),
             public_url: url,
+            policy: realm_policy()?,
         },
         keycloak,
     };
@@ -68,6 +86,7 @@ where
             realm,
             keycloak: keycloak_config,
             public_url: url,
+            policy: realm_policy()?,
         },
         keycloak,
     };
@@ -96,6 +115,54 @@ where
     Ok(())
 }
 
+/// Validates the realm of `keycloak` and fixes the errors selected by `fix`.
+pub async fn validate_realm_report(
+    keycloak: &Keycloak,
+    fix: Fix,
+) -> anyhow::Result<RealmValidationReport> {
+    let ctx = ValidationContext {
+        config: &Config {
+            realm: keycloak.config().realm(),
+            keycloak: keycloak.config(),
+            public_url: APP_URL.as_str(),
+            policy: realm_policy()?,
+        },
+        keycloak,
+    };
+    validate_and_fix(&ctx, fix).await
+}
+
+/// Reconciles roles, groups, role mappings, the client and realm settings with
+/// `groups`, stale roles and built-in groups are removed. With `dry_run` the
+/// plan is only logged.
+pub async fn reconcile_realm<R, P>(
+    keycloak: &Keycloak,
+    groups: &[Group<R, P>],
+    dry_run: bool,
+) -> anyhow::Result<RealmPlan>
+where
+    R: AsRef<str> + std::fmt::Debug,
+    P: AsRef<str> + std::fmt::Debug,
+{
+    let realm = keycloak.config().realm();
+    let ctx = ValidationContext {
+        config: &Config {
+            realm,
+            keycloak: keycloak.config(),
+            public_url: APP_URL.as_str(),
+            policy: realm_policy()?,
+        },
+        keycloak,
+    };
+    let reconciler = RealmReconciler::new(&ctx, groups);
+    let plan = reconciler.plan().await?;
+    log::info!("{plan}");
+    if !dry_run && !plan.is_empty() {
+        reconciler.apply(&plan).await?;
+    }
+    Ok(plan)
+}
+
 fn set_attributes(attributes: HashMap<&str, Option<String>>, u: &mut UserRepresentation) {
     if u.attributes.is_none() {
         u.attributes = Some(HashMap::new());
@@ -0,0 +1,670 @@
+//! Declarative reconciliation of a realm.
+//!
+//! The desired roles, groups and role mappings are computed from the groups
+//! generated by `qm-role-build`, realm settings and the `spa` client from the
+//! realm validation. [RealmReconciler::plan] compares them with the current
+//! state of the realm and [RealmReconciler::apply] executes the resulting
+//! [RealmPlan]. Applying a plan is idempotent, stale roles and built-in groups
+//! which are no longer defined are removed. Only roles managed by the reconciler
+//! are removed, they are marked with the `managed` attribute. Defined roles which
+//! exist without the attribute, e.g. created by `ensure_roles`, are adopted.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use qm_role::{Access, Group};
+
+use crate::validation::context::ValidationContext;
+use crate::validation::model::RealmConfigErrorInput;
+use crate::validation::realm_errors;
+use crate::validation::updater::update_for_errors;
+use crate::validation::validator::validate_realm;
+use crate::{GroupRepresentation, KeycloakError, RoleRepresentation};
+
+const BUILT_IN_ATTRIBUTE: &str = "built_in";
+const MANAGED_ATTRIBUTE: &str = "managed";
+const DISPLAY_NAME_ATTRIBUTE: &str = "display_name";
+const ALLOWED_ACCESS_LEVELS_ATTRIBUTE: &str = "allowed_access_levels";
+
+/// Roles created by Keycloak itself, these are never removed.
+const DEFAULT_ROLES: [&str; 2] = ["offline_access", "uma_authorization"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupSpec {
+    pub display_name: String,
+    pub allowed_access_levels: String,
+    pub roles: BTreeSet<String>,
+}
+
+impl<R, P> From<&Group<R, P>> for GroupSpec
+where
+    R: AsRef<str> + std::fmt::Debug,
+    P: AsRef<str> + std::fmt::Debug,
+{
+    fn from(group: &Group<R, P>) -> Self {
+        Self {
+            display_name: group.name.clone(),
+            allowed_access_levels: group
+                .allowed_access_levels()
+                .iter()
+                .map(|v| v.as_ref())
+                .collect::<Vec<&str>>()
+                .join(","),
+            roles: group.resources().into_iter().collect(),
+        }
+    }
+}
+
+/// Group as it currently exists in the realm.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupState {
+    pub display_name: Option<String>,
+    pub allowed_access_levels: Option<String>,
+    pub built_in: bool,
+    pub roles: BTreeSet<String>,
+}
+
+impl GroupState {
+    fn from_representation(rep: &GroupRepresentation, roles: Vec<RoleRepresentation>) -> Self {
+        let attribute = |key: &str| {
+            rep.attributes
+                .as_ref()
+                .and_then(|a| a.get(key))
+                .and_then(|v| v.first())
+                .cloned()
+        };
+        Self {
+            display_name: attribute(DISPLAY_NAME_ATTRIBUTE),
+            allowed_access_levels: attribute(ALLOWED_ACCESS_LEVELS_ATTRIBUTE),
+            built_in: attribute(BUILT_IN_ATTRIBUTE).as_deref() == Some("1"),
+            roles: roles.into_iter().filter_map(|r| r.name).collect(),
+        }
+    }
+}
+
+/// Role as it currently exists in the realm.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoleState {
+    /// Role was created or adopted by the reconciler and may be removed by it.
+    pub managed: bool,
+}
+
+impl RoleState {
+    fn from_representation(rep: &RoleRepresentation) -> Self {
+        Self {
+            managed: rep
+                .attributes
+                .as_ref()
+                .and_then(|a| a.get(MANAGED_ATTRIBUTE))
+                .and_then(|v| v.first())
+                .is_some_and(|v| v == "1"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    CreateRole(String),
+    /// Defined role which exists without the `managed` attribute.
+    AdoptRole(String),
+    DeleteRole(String),
+    CreateGroup {
+        path: String,
+        spec: GroupSpec,
+    },
+    UpdateGroup {
+        path: String,
+        spec: GroupSpec,
+    },
+    DeleteGroup(String),
+    AddRoleMapping {
+        path: String,
+        role: String,
+    },
+    RemoveRoleMapping {
+        path: String,
+        role: String,
+    },
+    /// Client setting fixed by the realm updater, identified by the validation error id.
+    UpdateClient(String),
+    /// Realm setting fixed by the realm updater, identified by the validation error id.
+    UpdateRealmSetting(String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::CreateRole(role) => write!(f, "+ role '{role}'"),
+            Change::AdoptRole(role) => write!(f, "~ role '{role}' (managed)"),
+            Change::DeleteRole(role) => write!(f, "- role '{role}'"),
+            Change::CreateGroup { path, spec } => write!(
+                f,
+                "+ group '{path}' ({}, access levels: {})",
+                spec.display_name, spec.allowed_access_levels
+            ),
+            Change::UpdateGroup { path, spec } => write!(
+                f,
+                "~ group '{path}' ({}, access levels: {})",
+                spec.display_name, spec.allowed_access_levels
+            ),
+            Change::DeleteGroup(path) => write!(f, "- group '{path}'"),
+            Change::AddRoleMapping { path, role } => {
+                write!(f, "+ role mapping '{path}' -> '{role}'")
+            }
+            Change::RemoveRoleMapping { path, role } => {
+                write!(f, "- role mapping '{path}' -> '{role}'")
+            }
+            Change::UpdateClient(id) => write!(f, "~ client '{id}'"),
+            Change::UpdateRealmSetting(id) => write!(f, "~ realm setting '{id}'"),
+        }
+    }
+}
+
+/// Changes required to bring a realm to the desired state.
+#[derive(Debug, Default)]
+pub struct RealmPlan {
+    pub realm: String,
+    pub changes: Vec<Change>,
+}
+
+impl RealmPlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for RealmPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return write!(f, "realm '{}' is up to date", self.realm);
+        }
+        write!(
+            f,
+            "realm '{}': {} change(s)",
+            self.realm,
+            self.changes.len()
+        )?;
+        for change in self.changes.iter() {
+            write!(f, "\n  {change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the changes of roles, groups and role mappings, settings are not part of the diff.
+///
+/// Roles are only removed if they are managed by the reconciler, defined roles are adopted
+/// so that they are removed once they are no longer defined. Access roles of tenants
+/// (e.g. `customer:access@1`) are never removed.
+pub fn diff(
+    desired: &BTreeMap<String, GroupSpec>,
+    current_roles: &BTreeMap<String, RoleState>,
+    current_groups: &BTreeMap<String, GroupState>,
+    is_protected_role: impl Fn(&str) -> bool,
+) -> Vec<Change> {
+    let mut changes = vec![];
+    let desired_roles: BTreeSet<&String> = desired.values().flat_map(|g| g.roles.iter()).collect();
+    for role in desired_roles.iter() {
+        match current_roles.get(*role) {
+            None => changes.push(Change::CreateRole(role.to_string())),
+            Some(state) if !state.managed => changes.push(Change::AdoptRole(role.to_string())),
+            Some(_) => {}
+        }
+    }
+    for (path, spec) in desired.iter() {
+        match current_groups.get(path) {
+            None => {
+                changes.push(Change::CreateGroup {
+                    path: path.clone(),
+                    spec: spec.clone(),
+                });
+                for role in spec.roles.iter() {
+                    changes.push(Change::AddRoleMapping {
+                        path: path.clone(),
+                        role: role.clone(),
+                    });
+                }
+            }
+            Some(state) => {
+                if state.display_name.as_ref() != Some(&spec.display_name)
+                    || state.allowed_access_levels.as_ref() != Some(&spec.allowed_access_levels)
+                    || !state.built_in
+                {
+                    changes.push(Change::UpdateGroup {
+                        path: path.clone(),
+                        spec: spec.clone(),
+                    });
+                }
+                for role in spec.roles.difference(&state.roles) {
+                    changes.push(Change::AddRoleMapping {
+                        path: path.clone(),
+                        role: role.clone(),
+                    });
+                }
+                for role in state.roles.difference(&spec.roles) {
+                    if desired_roles.contains(role) || !is_protected_role(role) {
+                        changes.push(Change::RemoveRoleMapping {
+                            path: path.clone(),
+                            role: role.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    // Deeper groups are removed first, removing a parent removes its children as well.
+    let mut stale_groups: Vec<&String> = current_groups
+        .iter()
+        .filter(|(path, state)| state.built_in && !desired.contains_key(*path))
+        .map(|(path, _)| path)
+        .collect();
+    stale_groups.sort_by_key(|path| std::cmp::Reverse(path.matches('/').count()));
+    for path in stale_groups {
+        changes.push(Change::DeleteGroup(path.clone()));
+    }
+    for (role, state) in current_roles.iter() {
+        if state.managed
+            && !desired_roles.contains(role)
+            && !is_protected_role(role)
+            && Access::from_str(role).is_err()
+        {
+            changes.push(Change::DeleteRole(role.clone()));
+        }
+    }
+    changes
+}
+
+fn ignore_not_found(result: Result<(), KeycloakError>) -> Result<(), KeycloakError> {
+    match result {
+        Err(KeycloakError::HttpFailure { status: 404, .. }) => Ok(()),
+        result => result,
+    }
+}
+
+pub struct RealmReconciler<'a> {
+    ctx: &'a ValidationContext<'a>,
+    groups: BTreeMap<String, GroupSpec>,
+    protected_roles: BTreeSet<String>,
+}
+
+impl<'a> RealmReconciler<'a> {
+    pub fn new<R, P>(ctx: &'a ValidationContext<'a>, groups: &[Group<R, P>]) -> Self
+    where
+        R: AsRef<str> + std::fmt::Debug,
+        P: AsRef<str> + std::fmt::Debug,
+    {
+        let realm = ctx.cfg().realm();
+        Self {
+            ctx,
+            groups: groups
+                .iter()
+                .map(|g| (g.path.clone(), GroupSpec::from(g)))
+                .collect(),
+            protected_roles: DEFAULT_ROLES
+                .iter()
+                .map(|r| r.to_string())
+                .chain([format!("default-roles-{realm}")])
+                .collect(),
+        }
+    }
+
+    /// Role which is kept even if it is not part of the role definition.
+    pub fn with_protected_role(mut self, role: impl Into<String>) -> Self {
+        self.protected_roles.insert(role.into());
+        self
+    }
+
+    fn realm(&self) -> &'a str {
+        self.ctx.cfg().realm()
+    }
+
+    async fn current_roles(&self) -> anyhow::Result<BTreeMap<String, RoleState>> {
+        Ok(self
+            .ctx
+            .keycloak()
+            .all_roles_with_attributes(self.realm())
+            .await?
+            .into_iter()
+            .filter(|r| !r.composite.unwrap_or(false))
+            .filter_map(|r| {
+                let state = RoleState::from_representation(&r);
+                r.name.map(|name| (name, state))
+            })
+            .collect())
+    }
+
+    /// Groups below the root groups of the desired group paths.
+    async fn current_groups(&self) -> anyhow::Result<BTreeMap<String, GroupState>> {
+        let keycloak = self.ctx.keycloak();
+        let realm = self.realm();
+        let roots: BTreeSet<&str> = self
+            .groups
+            .keys()
+            .filter_map(|path| path.split('/').find(|s| !s.trim().is_empty()))
+            .collect();
+        let mut result = BTreeMap::new();
+        let mut pending = vec![];
+        for root in roots {
+            match keycloak.group_by_path(realm, &format!("/{root}")).await {
+                Ok(group) => pending.push(group),
+                Err(KeycloakError::HttpFailure { status: 404, .. }) => {}
+                Err(err) => Err(err)?,
+            }
+        }
+        while let Some(group) = pending.pop() {
+            let Some(id) = group.id.as_deref() else {
+                continue;
+            };
+            let roles = keycloak.realm_role_mappings_by_group_id(realm, id).await?;
+            if let Some(path) = group.path.clone() {
+                result.insert(path, GroupState::from_representation(&group, roles));
+            }
+            pending.extend(keycloak.sub_groups(realm, id).await?);
+        }
+        Ok(result)
+    }
+
+    pub async fn plan(&self) -> anyhow::Result<RealmPlan> {
+        let realm = self.realm();
+        let mut changes = vec![];
+        if let Some(errors) = validate_realm(self.ctx).await? {
+            changes.extend(errors.into_iter().map(|e| {
+                if e.id.starts_with(realm_errors::CLIENTS_CLIENT_PREFIX) {
+                    Change::UpdateClient(e.id)
+                } else {
+                    Change::UpdateRealmSetting(e.id)
+                }
+            }));
+        }
+        let current_roles = self.current_roles().await?;
+        let current_groups = self.current_groups().await?;
+        changes.extend(diff(
+            &self.groups,
+            &current_roles,
+            &current_groups,
+            |role| self.protected_roles.contains(role),
+        ));
+        Ok(RealmPlan {
+            realm: realm.to_string(),
+            changes,
+        })
+    }
+
+    /// Creates the group and its missing parents, parents are created without attributes.
+    async fn create_group(&self, path: &str, spec: &GroupSpec) -> anyhow::Result<()> {
+        let keycloak = self.ctx.keycloak();
+        let realm = self.realm();
+        let parts: Vec<&str> = path.split('/').filter(|s| !s.trim().is_empty()).collect();
+        let mut parent: Option<GroupRepresentation> = None;
+        let mut current_path = String::new();
+        for (idx, part) in parts.iter().enumerate() {
+            current_path += &format!("/{part}");
+            let group = match keycloak.group_by_path(realm, &current_path).await {
+                Ok(group) => group,
+                Err(KeycloakError::HttpFailure { status: 404, .. }) => {
+                    let mut rep = GroupRepresentation {
+                        name: Some(part.to_string()),
+                        ..Default::default()
+                    };
+                    if idx == parts.len() - 1 {
+                        rep.attributes = Some(attributes(spec, None));
+                    }
+                    match parent.as_ref().and_then(|p| p.id.as_deref()) {
+                        Some(parent_id) => {
+                            keycloak
+                                .create_sub_group_with_id(realm, parent_id, rep)
+                                .await?
+                        }
+                        None => keycloak.create_group(realm, rep).await?,
+                    }
+                    keycloak.group_by_path(realm, &current_path).await?
+                }
+                Err(err) => Err(err)?,
+            };
+            parent = Some(group);
+        }
+        Ok(())
+    }
+
+    async fn update_group(&self, path: &str, spec: &GroupSpec) -> anyhow::Result<()> {
+        let keycloak = self.ctx.keycloak();
+        let mut group = keycloak.group_by_path(self.realm(), path).await?;
+        group.attributes = Some(attributes(spec, group.attributes.take()));
+        let id = group.id.clone().unwrap_or_default();
+        keycloak.update_group(self.realm(), &id, group).await?;
+        Ok(())
+    }
+
+    /// Executes the plan, the changes are applied in an order which keeps the
+    /// realm consistent: settings, roles, groups, mappings and finally removals.
+    pub async fn apply(&self, plan: &RealmPlan) -> anyhow::Result<()> {
+        let keycloak = self.ctx.keycloak();
+        let realm = self.realm();
+        let settings: Vec<RealmConfigErrorInput> = plan
+            .changes
+            .iter()
+            .filter_map(|change| match change {
+                Change::UpdateClient(id) | Change::UpdateRealmSetting(id) => {
+                    Some(RealmConfigErrorInput { id: id.clone() })
+                }
+                _ => None,
+            })
+            .collect();
+        if !settings.is_empty() {
+            update_for_errors(self.ctx, settings).await?;
+        }
+        for change in plan.changes.iter() {
+            match change {
+                Change::CreateRole(role) => {
+                    let result = keycloak
+                        .create_role(
+                            realm,
+                            RoleRepresentation {
+                                name: Some(role.clone()),
+                                attributes: Some(HashMap::from([(
+                                    MANAGED_ATTRIBUTE.to_string(),
+                                    vec!["1".to_string()],
+                                )])),
+                                ..Default::default()
+                            },
+                        )
+                        .await;
+                    match result {
+                        Ok(_) | Err(KeycloakError::HttpFailure { status: 409, .. }) => {}
+                        Err(err) => Err(err)?,
+                    }
+                }
+                Change::AdoptRole(role) => {
+                    let mut rep = keycloak.realm_role_by_name(realm, role).await?;
+                    rep.attributes
+                        .get_or_insert_with(HashMap::new)
+                        .insert(MANAGED_ATTRIBUTE.to_string(), vec!["1".to_string()]);
+                    keycloak.update_role(realm, role, rep).await?;
+                }
+                Change::CreateGroup { path, spec } => self.create_group(path, spec).await?,
+                Change::UpdateGroup { path, spec } => self.update_group(path, spec).await?,
+                _ => {}
+            }
+        }
+        let roles: HashMap<String, RoleRepresentation> = keycloak
+            .all_roles(realm)
+            .await?
+            .into_iter()
+            .filter_map(|r| r.name.clone().map(|name| (name, r)))
+            .collect();
+        let mut added: BTreeMap<&str, Vec<RoleRepresentation>> = BTreeMap::new();
+        let mut removed: BTreeMap<&str, Vec<RoleRepresentation>> = BTreeMap::new();
+        for change in plan.changes.iter() {
+            match change {
+                Change::AddRoleMapping { path, role } => {
+                    if let Some(rep) = roles.get(role) {
+                        added.entry(path).or_default().push(rep.clone());
+                    }
+                }
+                Change::RemoveRoleMapping { path, role } => {
+                    if let Some(rep) = roles.get(role) {
+                        removed.entry(path).or_default().push(rep.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (path, roles) in added {
+            let group = keycloak.group_by_path(realm, path).await?;
+            keycloak
+                .create_realm_role_mappings_by_group_id(
+                    realm,
+                    group.id.as_deref().unwrap_or_default(),
+                    roles,
+                )
+                .await?;
+        }
+        for (path, roles) in removed {
+            let group = keycloak.group_by_path(realm, path).await?;
+            ignore_not_found(
+                keycloak
+                    .remove_realm_role_mappings_by_group_id(
+                        realm,
+                        group.id.as_deref().unwrap_or_default(),
+                        roles,
+                    )
+                    .await,
+            )?;
+        }
+        for change in plan.changes.iter() {
+            match change {
+                Change::DeleteGroup(path) => {
+                    ignore_not_found(keycloak.remove_group_by_path(realm, path).await)?
+                }
+                Change::DeleteRole(role) => {
+                    ignore_not_found(keycloak.remove_role(realm, role).await)?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn attributes(
+    spec: &GroupSpec,
+    existing: Option<HashMap<String, Vec<String>>>,
+) -> HashMap<String, Vec<String>> {
+    let mut attributes = existing.unwrap_or_default();
+    attributes.insert(BUILT_IN_ATTRIBUTE.to_string(), vec!["1".to_string()]);
+    attributes.insert(
+        DISPLAY_NAME_ATTRIBUTE.to_string(),
+        vec![spec.display_name.clone()],
+    );
+    attributes.insert(
+        ALLOWED_ACCESS_LEVELS_ATTRIBUTE.to_string(),
+        vec![spec.allowed_access_levels.clone()],
+    );
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{diff, Change, GroupSpec, GroupState, RoleState};
+
+    fn spec(roles: &[&str]) -> GroupSpec {
+        GroupSpec {
+            display_name: "Admin".to_string(),
+            allowed_access_levels: "admin".to_string(),
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+
+    fn roles(roles: &[(&str, bool)]) -> BTreeMap<String, RoleState> {
+        roles
+            .iter()
+            .map(|(r, managed)| (r.to_string(), RoleState { managed: *managed }))
+            .collect()
+    }
+
+    #[test]
+    fn diff_test() {
+        let desired = BTreeMap::from([("/app/admin".to_string(), spec(&["a", "b"]))]);
+        let current_roles = roles(&[
+            ("a", true),
+            ("old", true),
+            ("offline_access", false),
+            // Access roles of tenants and roles of other clients are kept.
+            ("customer:access@1", true),
+            ("other", false),
+        ]);
+        let current_groups = BTreeMap::from([
+            (
+                "/app/admin".to_string(),
+                GroupState {
+                    display_name: Some("Admin".to_string()),
+                    allowed_access_levels: Some("admin".to_string()),
+                    built_in: true,
+                    roles: ["a", "old"].iter().map(|r| r.to_string()).collect(),
+                },
+            ),
+            (
+                "/app/stale".to_string(),
+                GroupState {
+                    built_in: true,
+                    ..Default::default()
+                },
+            ),
+            ("/app/custom".to_string(), GroupState::default()),
+        ]);
+        let changes = diff(&desired, &current_roles, &current_groups, |r| {
+            r == "offline_access"
+        });
+        assert_eq!(
+            changes,
+            vec![
+                Change::CreateRole("b".to_string()),
+                Change::AddRoleMapping {
+                    path: "/app/admin".to_string(),
+                    role: "b".to_string()
+                },
+                Change::RemoveRoleMapping {
+                    path: "/app/admin".to_string(),
+                    role: "old".to_string()
+                },
+                Change::DeleteGroup("/app/stale".to_string()),
+                Change::DeleteRole("old".to_string()),
+            ]
+        );
+        let current_roles = roles(&[("a", true), ("b", true)]);
+        let current_groups = BTreeMap::from([(
+            "/app/admin".to_string(),
+            GroupState {
+                display_name: Some("Admin".to_string()),
+                allowed_access_levels: Some("admin".to_string()),
+                built_in: true,
+                roles: ["a", "b"].iter().map(|r| r.to_string()).collect(),
+            },
+        )]);
+        assert!(diff(&desired, &current_roles, &current_groups, |_| false).is_empty());
+
+        // Roles of an existing realm, e.g. created by `ensure_roles`, are adopted.
+        let unmarked_roles = roles(&[("a", false), ("b", false)]);
+        assert_eq!(
+            diff(&desired, &unmarked_roles, &current_groups, |_| false),
+            vec![
+                Change::AdoptRole("a".to_string()),
+                Change::AdoptRole("b".to_string()),
+            ]
+        );
+        // and removed once they disappear from the definition.
+        let desired = BTreeMap::from([("/app/admin".to_string(), spec(&["a"]))]);
+        assert_eq!(
+            diff(&desired, &current_roles, &current_groups, |_| false),
+            vec![
+                Change::RemoveRoleMapping {
+                    path: "/app/admin".to_string(),
+                    role: "b".to_string()
+                },
+                Change::DeleteRole("b".to_string()),
+            ]
+        );
+    }
+}
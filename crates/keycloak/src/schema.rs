@@ -4,6 +4,12 @@ use async_graphql::{Enum, InputObject};
 pub enum RequiredUserAction {
     #[graphql(name = "UPDATE_PASSWORD")]
     UpdatePassword,
+    #[graphql(name = "VERIFY_EMAIL")]
+    VerifyEmail,
+    #[graphql(name = "UPDATE_PROFILE")]
+    UpdateProfile,
+    #[graphql(name = "CONFIGURE_TOTP")]
+    ConfigureTotp,
 }
 
 impl std::fmt::Display for RequiredUserAction {
@@ -13,6 +19,9 @@ impl std::fmt::Display for RequiredUserAction {
             "{}",
             match self {
                 RequiredUserAction::UpdatePassword => "UPDATE_PASSWORD",
+                RequiredUserAction::VerifyEmail => "VERIFY_EMAIL",
+                RequiredUserAction::UpdateProfile => "UPDATE_PROFILE",
+                RequiredUserAction::ConfigureTotp => "CONFIGURE_TOTP",
             }
         )
     }
@@ -171,7 +171,7 @@ impl KeycloakSessionClient {
         let result = error(
             self.inner
                 .client
-                .post(&format!(
+                .post(format!(
                     "{url}/realms/{realm}/protocol/openid-connect/token",
                 ))
                 .form(&serde_json::json!({
@@ -203,7 +203,7 @@ impl KeycloakSessionClient {
         let result = error(
             self.inner
                 .client
-                .post(&format!(
+                .post(format!(
                     "{url}/realms/{realm}/protocol/openid-connect/token",
                 ))
                 .form(&serde_json::json!({
@@ -1,9 +1,11 @@
+use crate::validation::policy::RealmPolicy;
 use crate::{Keycloak, KeycloakConfig};
 
 pub struct Config<'a> {
     pub keycloak: &'a KeycloakConfig,
     pub public_url: &'a str,
     pub realm: &'a str,
+    pub policy: &'a RealmPolicy,
 }
 
 impl<'a> Config<'a> {
@@ -16,6 +18,9 @@ impl<'a> Config<'a> {
     pub fn realm(&self) -> &'a str {
         self.realm
     }
+    pub fn policy(&self) -> &'a RealmPolicy {
+        self.policy
+    }
 }
 
 pub struct ValidationContext<'a> {
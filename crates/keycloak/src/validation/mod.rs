@@ -1,5 +1,7 @@
 pub mod context;
 pub mod model;
+pub mod policy;
 pub mod realm_errors;
+pub mod report;
 pub mod updater;
 pub mod validator;
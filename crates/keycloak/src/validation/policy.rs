@@ -0,0 +1,178 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+pub const ENV_PREFIX: &str = "REALM_POLICY_";
+/// Environment variable with the path of a TOML file containing the policy.
+pub const FILE_ENV: &str = "REALM_POLICY_FILE";
+
+/// Expected settings of a realm, used by the validator and the updater.
+///
+/// The defaults match the settings of the realm template. The policy can be
+/// loaded from environment variables prefixed with `REALM_POLICY_`, e.g.
+/// `REALM_POLICY_DEFAULT_LOCALE=en`, or from a TOML file with the same keys.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RealmPolicy {
+    pub default_locale: String,
+    /// Locales which must be supported, the first one is used if the realm has none.
+    pub supported_locales: Vec<String>,
+    pub internationalization_enabled: bool,
+    /// Login theme, defaults to the theme of the Keycloak config.
+    pub login_theme: Option<String>,
+    /// Required password policy components, e.g. `length(8)`.
+    pub password_policy: Vec<String>,
+    pub remember_me: bool,
+    pub registration_allowed: bool,
+    pub reset_password_allowed: bool,
+    /// Report a missing SMTP server configuration.
+    pub smtp_required: bool,
+    /// Expected SMTP settings if they are not part of the Keycloak config.
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_from: String,
+    pub smtp_from_display_name: String,
+    pub smtp_starttls: bool,
+    pub smtp_ssl: bool,
+    /// Allowed redirect URIs of the `spa` client, defaults to the public url.
+    pub client_redirect_uris: Option<Vec<String>>,
+}
+
+impl Default for RealmPolicy {
+    fn default() -> Self {
+        Self {
+            default_locale: "de".to_string(),
+            supported_locales: vec!["de".to_string()],
+            internationalization_enabled: true,
+            login_theme: None,
+            password_policy: [
+                "length(8)",
+                "specialChars(1)",
+                "upperCase(1)",
+                "lowerCase(1)",
+                "digits(1)",
+            ]
+            .map(String::from)
+            .to_vec(),
+            remember_me: true,
+            registration_allowed: false,
+            reset_password_allowed: true,
+            smtp_required: true,
+            smtp_host: "smtp".to_string(),
+            smtp_port: 1025,
+            smtp_from: "noreply@qm.local".to_string(),
+            smtp_from_display_name: "qm".to_string(),
+            smtp_starttls: false,
+            smtp_ssl: false,
+            client_redirect_uris: None,
+        }
+    }
+}
+
+/// Name of a password policy component, e.g. `length` for `length(8)`.
+fn component_name(component: &str) -> &str {
+    component
+        .split_once('(')
+        .map(|(name, _)| name)
+        .unwrap_or(component)
+        .trim()
+}
+
+impl RealmPolicy {
+    pub fn from_env() -> envy::Result<Self> {
+        envy::prefixed(ENV_PREFIX).from_env()
+    }
+
+    pub fn from_toml(s: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Loads the policy from the file in `REALM_POLICY_FILE` or from the environment.
+    pub fn load() -> anyhow::Result<Self> {
+        match std::env::var(FILE_ENV) {
+            Ok(path) => Self::from_file(path),
+            Err(_) => Ok(Self::from_env()?),
+        }
+    }
+
+    pub fn login_theme<'a>(&'a self, fallback: &'a str) -> &'a str {
+        self.login_theme.as_deref().unwrap_or(fallback)
+    }
+
+    /// Required password policy components missing in `policy`.
+    pub fn missing_password_policy<'a>(&'a self, policy: &str) -> Vec<&'a str> {
+        let components: Vec<&str> = policy.split(" and ").map(str::trim).collect();
+        self.password_policy
+            .iter()
+            .map(String::as_str)
+            .filter(|required| !components.contains(required))
+            .collect()
+    }
+
+    /// Replaces the components of `policy` with the required ones, other
+    /// components are kept.
+    pub fn merge_password_policy(&self, policy: Option<&str>) -> String {
+        let required: Vec<&str> = self
+            .password_policy
+            .iter()
+            .map(|c| component_name(c))
+            .collect();
+        policy
+            .unwrap_or_default()
+            .split(" and ")
+            .map(str::trim)
+            .filter(|c| !c.is_empty() && !required.contains(&component_name(c)))
+            .chain(self.password_policy.iter().map(String::as_str))
+            .collect::<Vec<&str>>()
+            .join(" and ")
+    }
+
+    /// Redirect URIs of the `spa` client, `public_url` is used if none are configured.
+    pub fn redirect_uris(&self, public_url: &str) -> Vec<String> {
+        self.client_redirect_uris
+            .clone()
+            .unwrap_or_else(|| vec![public_url.to_string(), format!("{public_url}*")])
+    }
+
+    pub fn is_redirect_uri_allowed(&self, uri: &str, public_url: &str) -> bool {
+        match &self.client_redirect_uris {
+            Some(uris) => uris.iter().any(|u| u == uri),
+            None => uri == public_url || uri.replace('*', "") == public_url,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RealmPolicy;
+
+    #[test]
+    fn password_policy_test() {
+        let policy = RealmPolicy::default();
+        assert_eq!(
+            policy.missing_password_policy("length(8) and digits(1)"),
+            vec!["specialChars(1)", "upperCase(1)", "lowerCase(1)"]
+        );
+        let policy = RealmPolicy::from_toml(
+            r#"
+            default_locale = "en"
+            password_policy = ["length(12)", "digits(1)"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(policy.default_locale, "en");
+        assert_eq!(policy.supported_locales, vec!["de".to_string()]);
+        assert_eq!(
+            policy.merge_password_policy(Some("length(8) and notUsername(undefined)")),
+            "notUsername(undefined) and length(12) and digits(1)"
+        );
+        assert_eq!(
+            policy.merge_password_policy(None),
+            "length(12) and digits(1)"
+        );
+    }
+}
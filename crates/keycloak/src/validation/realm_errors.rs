@@ -11,6 +11,7 @@ pub const REALM_PASSWORD_POLICY_UPPERCASE_ID: &str = "realm-password_policy-uppe
 pub const REALM_PASSWORD_POLICY_LOWERCASE_ID: &str = "realm-password_policy-lowercase";
 pub const REALM_PASSWORD_POLICY_DIGIT_ID: &str = "realm-password_policy-digit";
 pub const REALM_PASSWORD_POLICY_MISSING_ID: &str = "realm-password_policy-missing";
+pub const REALM_PASSWORD_POLICY_INVALID_ID: &str = "realm-password_policy-invalid";
 pub const REALM_REMEMBER_ME_ID: &str = "realm-remember_me";
 pub const REALM_REGISTRATION_ALLOWED_ID: &str = "realm-registration_allowed";
 pub const REALM_RESET_PASSWORD_ALLOWED_ID: &str = "realm-reset_password_allowed";
@@ -94,6 +95,7 @@ pub const REALM_PASSWORD_POLICY_UPPERCASE_KEY: &str = "realm.password_policy.upp
 pub const REALM_PASSWORD_POLICY_LOWERCASE_KEY: &str = "realm.password_policy.lowercase";
 pub const REALM_PASSWORD_POLICY_DIGIT_KEY: &str = "realm.password_policy.digit";
 pub const REALM_PASSWORD_POLICY_MISSING_KEY: &str = "realm.password_policy.missing";
+pub const REALM_PASSWORD_POLICY_INVALID_KEY: &str = "realm.password_policy.invalid";
 pub const REALM_REMEMBER_ME_KEY: &str = "realm.remember_me";
 pub const REALM_REGISTRATION_ALLOWED_KEY: &str = "realm.registration_allowed";
 pub const REALM_RESET_PASSWORD_ALLOWED_KEY: &str = "realm.reset_password_allowed";
@@ -0,0 +1,208 @@
+use async_graphql::{Enum, SimpleObject};
+use serde::{Deserialize, Serialize};
+
+use crate::validation::context::ValidationContext as Ctx;
+use crate::validation::model::{RealmConfigError, RealmConfigErrorInput};
+use crate::validation::realm_errors;
+use crate::validation::updater::update_for_errors;
+use crate::validation::validator::validate_realm;
+
+/// Errors which prevent users from logging in to the application.
+const CRITICAL_IDS: [&str; 6] = [
+    realm_errors::CLIENTS_CLIENT_MISSING_ID,
+    realm_errors::CLIENTS_CLIENT_ENABLED_ID,
+    realm_errors::CLIENTS_CLIENT_PUBLIC_CLIENT_ID,
+    realm_errors::CLIENTS_CLIENT_REDIRECT_URIS_INVALID_ID,
+    realm_errors::CLIENTS_CLIENT_REDIRECT_URIS_MISSING_ID,
+    realm_errors::CLIENTS_CLIENT_STANDARD_FLOW_ENABLED_ID,
+];
+
+#[derive(
+    Debug, Clone, Copy, Enum, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub enum Severity {
+    Critical,
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn of(id: &str) -> Self {
+        if CRITICAL_IDS.contains(&id) {
+            Severity::Critical
+        } else if id.starts_with(realm_errors::CLIENTS_CLIENT_PREFIX)
+            || id.starts_with("realm-password_policy")
+            || id.starts_with("realm-smtp_server")
+        {
+            Severity::Error
+        } else {
+            Severity::Warning
+        }
+    }
+}
+
+/// Returns `true` if the updater is able to fix the error with `id`.
+pub fn is_fixable(id: &str) -> bool {
+    id.starts_with(realm_errors::REALM_PREFIX)
+        || id.starts_with(realm_errors::CLIENTS_CLIENT_PREFIX)
+}
+
+#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+pub struct RealmValidationIssue {
+    pub id: String,
+    pub key: String,
+    pub severity: Severity,
+    pub fixable: bool,
+}
+
+impl From<RealmConfigError> for RealmValidationIssue {
+    fn from(value: RealmConfigError) -> Self {
+        Self {
+            severity: Severity::of(&value.id),
+            fixable: is_fixable(&value.id),
+            id: value.id,
+            key: value.key,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+pub struct RealmValidationGroup {
+    pub severity: Severity,
+    pub issues: Vec<RealmValidationIssue>,
+}
+
+#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+pub struct RealmValidationReport {
+    pub realm: String,
+    /// Remaining issues grouped by severity, the most severe group comes first.
+    pub groups: Vec<RealmValidationGroup>,
+    /// Ids of the errors which were fixed by the updater.
+    pub fixed: Vec<String>,
+}
+
+impl RealmValidationReport {
+    pub fn new(realm: &str, errors: Vec<RealmConfigError>, fixed: Vec<String>) -> Self {
+        let mut groups: Vec<RealmValidationGroup> = vec![];
+        for issue in errors.into_iter().map(RealmValidationIssue::from) {
+            match groups.iter_mut().find(|g| g.severity == issue.severity) {
+                Some(group) => group.issues.push(issue),
+                None => groups.push(RealmValidationGroup {
+                    severity: issue.severity,
+                    issues: vec![issue],
+                }),
+            }
+        }
+        groups.sort_by_key(|g| g.severity);
+        Self {
+            realm: realm.to_string(),
+            groups,
+            fixed,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn issues(&self) -> impl Iterator<Item = &RealmValidationIssue> {
+        self.groups.iter().flat_map(|g| g.issues.iter())
+    }
+}
+
+impl std::fmt::Display for RealmValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for id in self.fixed.iter() {
+            writeln!(f, "fixed {id}")?;
+        }
+        if self.is_valid() {
+            return write!(f, "realm '{}' is valid", self.realm);
+        }
+        write!(f, "realm '{}' has issues", self.realm)?;
+        for group in self.groups.iter() {
+            write!(f, "\n{:?}:", group.severity)?;
+            for issue in group.issues.iter() {
+                write!(
+                    f,
+                    "\n  {}{}",
+                    issue.id,
+                    if issue.fixable { "" } else { " (not fixable)" }
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which validation errors should be fixed by [validate_and_fix].
+#[derive(Debug, Clone, Default)]
+pub enum Fix {
+    #[default]
+    None,
+    All,
+    Only(Vec<String>),
+}
+
+/// Validates the realm, optionally fixes the errors selected by `fix` with the
+/// updater and validates again to report the remaining errors.
+pub async fn validate_and_fix(ctx: &Ctx<'_>, fix: Fix) -> anyhow::Result<RealmValidationReport> {
+    let realm = ctx.cfg().realm();
+    let errors = validate_realm(ctx).await?.unwrap_or_default();
+    let selected: Vec<RealmConfigErrorInput> = errors
+        .iter()
+        .filter(|e| is_fixable(&e.id))
+        .filter(|e| match &fix {
+            Fix::None => false,
+            Fix::All => true,
+            Fix::Only(ids) => ids.contains(&e.id),
+        })
+        .map(|e| RealmConfigErrorInput { id: e.id.clone() })
+        .collect();
+    if selected.is_empty() {
+        return Ok(RealmValidationReport::new(realm, errors, vec![]));
+    }
+    log::info!("fixing {} error(s) in realm '{realm}'", selected.len());
+    update_for_errors(ctx, selected.clone()).await?;
+    let errors = validate_realm(ctx).await?.unwrap_or_default();
+    let fixed = selected
+        .into_iter()
+        .map(|e| e.id)
+        .filter(|id| !errors.iter().any(|e| &e.id == id))
+        .collect();
+    Ok(RealmValidationReport::new(realm, errors, fixed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RealmValidationReport, Severity};
+    use crate::validation::model::RealmConfigError;
+    use crate::validation::realm_errors;
+
+    #[test]
+    fn report_test() {
+        let error = |id: &str| RealmConfigError::new(id.to_string(), String::new());
+        let report = RealmValidationReport::new(
+            "test",
+            vec![
+                error(realm_errors::REALM_REMEMBER_ME_ID),
+                error(realm_errors::CLIENTS_CLIENT_MISSING_ID),
+                error(realm_errors::REALM_SMTP_SERVER_MISSING_ID),
+                error(realm_errors::GROUPS_OWNER_ID),
+            ],
+            vec![],
+        );
+        let severities: Vec<Severity> = report.groups.iter().map(|g| g.severity).collect();
+        assert_eq!(
+            severities,
+            vec![Severity::Critical, Severity::Error, Severity::Warning]
+        );
+        assert_eq!(report.groups[2].issues.len(), 2);
+        assert!(
+            !report
+                .issues()
+                .find(|i| i.id == realm_errors::GROUPS_OWNER_ID)
+                .unwrap()
+                .fixable
+        );
+    }
+}
@@ -60,102 +60,57 @@ async fn update_realm_settings(
     }
 
     let mut rep: RealmRepresentation = ctx.keycloak().realm_by_name(realm).await?;
+    let policy = ctx.cfg().policy();
 
     errors.iter().for_each(|e| match e.id.as_str() {
         realm_errors::REALM_DEFAULT_LOCALE_INVALID_ID
         | realm_errors::REALM_DEFAULT_LOCALE_MISSING_ID => {
             log::trace!("Setting 'default_locale' for realm '{}'", realm);
-            rep.default_locale = Some("de".to_string());
+            rep.default_locale = Some(policy.default_locale.clone());
         }
         realm_errors::REALM_INTERNATIONALIZATION_ENABLED_ID => {
             log::trace!(
                 "Setting 'internationalization_enabled' for realm '{}'",
                 realm
             );
-            rep.internationalization_enabled = Some(true);
+            rep.internationalization_enabled = Some(policy.internationalization_enabled);
         }
         realm_errors::REALM_LOGIN_THEME_INVALID_ID | realm_errors::REALM_LOGIN_THEME_MISSING_ID => {
             log::trace!("Setting 'login_theme' for realm '{}'", realm);
-            rep.login_theme = Some(ctx.cfg().keycloak().theme().to_string());
+            rep.login_theme = Some(policy.login_theme(ctx.cfg().keycloak().theme()).to_string());
         }
-        realm_errors::REALM_PASSWORD_POLICY_LENGTH_ID => {
-            log::trace!(
-                "Adding 'password_policy' value 'length(8)' for realm '{}'",
-                realm
-            );
-            let new_policy = match &rep.password_policy {
-                Some(s) => format!("{} and length(8)", s),
-                None => "length(8)".to_string(),
-            };
-            rep.password_policy = Some(new_policy)
-        }
-        realm_errors::REALM_PASSWORD_POLICY_SYMBOL_ID => {
-            log::trace!(
-                "Adding 'password_policy' value 'specialChars(1)' for realm '{}'",
-                realm
-            );
-            let new_policy = match &rep.password_policy {
-                Some(s) => format!("{} and specialChars(1)", s),
-                None => "specialChars(1)".to_string(),
-            };
-            rep.password_policy = Some(new_policy)
-        }
-        realm_errors::REALM_PASSWORD_POLICY_UPPERCASE_ID => {
-            log::trace!(
-                "Adding 'password_policy' value 'upperCase(1)' for realm '{}'",
-                realm
-            );
-            let new_policy = match &rep.password_policy {
-                Some(s) => format!("{} and upperCase(1)", s),
-                None => "upperCase(1)".to_string(),
-            };
-            rep.password_policy = Some(new_policy)
-        }
-        realm_errors::REALM_PASSWORD_POLICY_LOWERCASE_ID => {
-            log::trace!(
-                "Adding 'password_policy' value 'lowerCase(1)' for realm '{}'",
-                realm
-            );
-            let new_policy = match &rep.password_policy {
-                Some(s) => format!("{} and lowerCase(1)", s),
-                None => "lowerCase(1)".to_string(),
-            };
-            rep.password_policy = Some(new_policy)
-        }
-        realm_errors::REALM_PASSWORD_POLICY_DIGIT_ID => {
-            log::trace!(
-                "Adding 'password_policy' value 'digits(1)' for realm '{}'",
-                realm
-            );
-            let new_policy = match &rep.password_policy {
-                Some(s) => format!("{} and digits(1)", s),
-                None => "digits(1)".to_string(),
-            };
-            rep.password_policy = Some(new_policy)
-        }
-        realm_errors::REALM_PASSWORD_POLICY_MISSING_ID => {
+        realm_errors::REALM_PASSWORD_POLICY_LENGTH_ID
+        | realm_errors::REALM_PASSWORD_POLICY_SYMBOL_ID
+        | realm_errors::REALM_PASSWORD_POLICY_UPPERCASE_ID
+        | realm_errors::REALM_PASSWORD_POLICY_LOWERCASE_ID
+        | realm_errors::REALM_PASSWORD_POLICY_DIGIT_ID
+        | realm_errors::REALM_PASSWORD_POLICY_INVALID_ID
+        | realm_errors::REALM_PASSWORD_POLICY_MISSING_ID => {
             log::trace!("Setting 'password_policy' for realm '{}'", realm);
-            rep.password_policy = Some(
-                "length(8) and specialChars(1) and upperCase(1) and lowerCase(1) and digits(1)"
-                    .to_string(),
-            )
+            rep.password_policy =
+                Some(policy.merge_password_policy(rep.password_policy.as_deref()));
         }
         realm_errors::REALM_REMEMBER_ME_ID => {
             log::trace!("Setting 'remember_me' for realm '{}'", realm);
-            rep.remember_me = Some(true);
+            rep.remember_me = Some(policy.remember_me);
         }
         realm_errors::REALM_REGISTRATION_ALLOWED_ID => {
             log::trace!("Setting 'registration_allowed' for realm '{}'", realm);
-            rep.registration_allowed = Some(false);
+            rep.registration_allowed = Some(policy.registration_allowed);
         }
         realm_errors::REALM_RESET_PASSWORD_ALLOWED_ID => {
             log::trace!("Setting 'reset_password_allowed' for realm '{}'", realm);
-            rep.reset_password_allowed = Some(true);
+            rep.reset_password_allowed = Some(policy.reset_password_allowed);
         }
         realm_errors::REALM_SUPPORTED_LOCALES_INVALID_ID
         | realm_errors::REALM_SUPPORTED_LOCALES_MISSING_ID => {
             log::trace!("Setting 'supported_locales' for realm '{}'", realm);
-            rep.supported_locales = Some(vec!["de".to_string()]);
+            let locales = rep.supported_locales.get_or_insert_with(Vec::new);
+            for locale in policy.supported_locales.iter() {
+                if !locales.contains(locale) {
+                    locales.push(locale.clone());
+                }
+            }
         }
         realm_errors::REALM_SMTP_SERVER_MISSING_ID => {
             log::trace!("Setting 'smtp_server' for realm '{}'", realm);
@@ -327,13 +282,7 @@ async fn update_client_settings(
                 realm_errors::CLIENTS_CLIENT_REDIRECT_URIS_INVALID_ID
                 | realm_errors::CLIENTS_CLIENT_REDIRECT_URIS_MISSING_ID => {
                     log::trace!("Adding 'redirect_uris' for configured value for client 'spa' in realm '{}'", realm);
-                    if let Some(uris) = rep.redirect_uris.as_mut() {
-                        uris.clear();
-                        uris.push(ctx.cfg().public_url().to_string());
-                        uris.push(format!("{}*", ctx.cfg().public_url()));
-                    } else {
-                        rep.redirect_uris = Some(vec![format!("{}*", ctx.cfg().public_url())]);
-                    }
+                    rep.redirect_uris = Some(ctx.cfg().policy().redirect_uris(ctx.cfg().public_url()));
                 }
                 realm_errors::CLIENTS_CLIENT_ROOT_URL_INVALID_ID
                 | realm_errors::CLIENTS_CLIENT_ROOT_URL_MISSING_ID => {
@@ -383,7 +332,7 @@ async fn update_client_settings(
             enabled: Some(true),
             implicit_flow_enabled: Some(false),
             public_client: Some(true),
-            redirect_uris: Some(vec![format!("{}*", ctx.cfg().public_url())]),
+            redirect_uris: Some(ctx.cfg().policy().redirect_uris(ctx.cfg().public_url())),
             root_url: Some(ctx.cfg().public_url().trim_end_matches('/').to_string()),
             service_accounts_enabled: Some(false),
             standard_flow_enabled: Some(true),
@@ -402,37 +351,41 @@ async fn update_client_settings(
 }
 
 pub fn get_smtp_server_defaults(ctx: &Ctx<'_>) -> Option<HashMap<String, String>> {
+    let policy = ctx.cfg().policy();
     let mut defaults: HashMap<String, String> = HashMap::new();
 
     if let Some(configured_starttls) = ctx.cfg().keycloak().smtp_starttls() {
         defaults.insert(String::from("starttls"), configured_starttls.to_string());
     } else {
-        defaults.insert(String::from("starttls"), "false".to_string());
+        defaults.insert(String::from("starttls"), policy.smtp_starttls.to_string());
     }
     if let Some(configured_port) = ctx.cfg().keycloak().smtp_port() {
         defaults.insert(String::from("port"), configured_port.to_string());
     } else {
-        defaults.insert(String::from("port"), "1025".to_string());
+        defaults.insert(String::from("port"), policy.smtp_port.to_string());
     }
     if let Some(configured_host) = ctx.cfg().keycloak().smtp_host() {
         defaults.insert(String::from("host"), configured_host.to_string());
     } else {
-        defaults.insert(String::from("host"), "smtp".to_string());
+        defaults.insert(String::from("host"), policy.smtp_host.clone());
     }
     if let Some(configured_from) = ctx.cfg().keycloak().smtp_from() {
         defaults.insert(String::from("from"), configured_from.to_string());
     } else {
-        defaults.insert(String::from("from"), "noreply@qm.local".to_string());
+        defaults.insert(String::from("from"), policy.smtp_from.clone());
     }
     if let Some(configured_from) = ctx.cfg().keycloak().smtp_from_display_name() {
         defaults.insert(String::from("fromDisplayName"), configured_from.to_string());
     } else {
-        defaults.insert(String::from("fromDisplayName"), "qm".to_string());
+        defaults.insert(
+            String::from("fromDisplayName"),
+            policy.smtp_from_display_name.clone(),
+        );
     }
     if let Some(configured_ssl) = ctx.cfg().keycloak().smtp_ssl() {
         defaults.insert(String::from("ssl"), configured_ssl.to_owned().to_string());
     } else {
-        defaults.insert(String::from("ssl"), "false".to_string());
+        defaults.insert(String::from("ssl"), policy.smtp_ssl.to_string());
     }
 
     Some(defaults)
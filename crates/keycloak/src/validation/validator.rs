@@ -20,10 +20,11 @@ async fn check_realm_settings(
     errors: &mut Vec<RealmConfigError>,
 ) -> anyhow::Result<()> {
     let rep: RealmRepresentation = ctx.keycloak().realm_by_name(realm).await?;
+    let policy = ctx.cfg().policy();
 
-    // default_locale must be `de`
+    // default_locale must match the policy
     if let Some(locale) = &rep.default_locale {
-        if locale != "de" {
+        if locale != &policy.default_locale {
             add_error(
                 realm_errors::REALM_DEFAULT_LOCALE_INVALID_ID,
                 realm_errors::REALM_DEFAULT_LOCALE_INVALID_KEY,
@@ -37,17 +38,17 @@ async fn check_realm_settings(
             errors,
         );
     }
-    // internationalization_enabled must be `true`
-    if !rep.internationalization_enabled.unwrap_or(false) {
+    // internationalization_enabled must match the policy
+    if rep.internationalization_enabled.unwrap_or(false) != policy.internationalization_enabled {
         add_error(
             realm_errors::REALM_INTERNATIONALIZATION_ENABLED_ID,
             realm_errors::REALM_INTERNATIONALIZATION_ENABLED_KEY,
             errors,
         );
     }
-    // login_theme must be `qm`
+    // login_theme must match the policy or the configured theme
     if let Some(theme) = &rep.login_theme {
-        if theme != ctx.keycloak().config().theme() {
+        if theme != policy.login_theme(ctx.keycloak().config().theme()) {
             add_error(
                 realm_errors::REALM_LOGIN_THEME_INVALID_ID,
                 realm_errors::REALM_LOGIN_THEME_INVALID_KEY,
@@ -61,77 +62,52 @@ async fn check_realm_settings(
             errors,
         );
     }
-    // password_policy must contain `length(8)`, `specialChars(1)`, `upperCase(1)`, `lowerCase(1)`, `digits(1)`
-    if let Some(policy) = &rep.password_policy {
-        if !policy.contains("length(8)") {
-            add_error(
-                realm_errors::REALM_PASSWORD_POLICY_LENGTH_ID,
-                realm_errors::REALM_PASSWORD_POLICY_LENGTH_KEY,
-                errors,
-            );
-        }
-        if !policy.contains("specialChars(1)") {
-            add_error(
-                realm_errors::REALM_PASSWORD_POLICY_SYMBOL_ID,
-                realm_errors::REALM_PASSWORD_POLICY_SYMBOL_KEY,
-                errors,
-            );
-        }
-        if !policy.contains("upperCase(1)") {
-            add_error(
-                realm_errors::REALM_PASSWORD_POLICY_UPPERCASE_ID,
-                realm_errors::REALM_PASSWORD_POLICY_UPPERCASE_KEY,
-                errors,
-            );
-        }
-        if !policy.contains("lowerCase(1)") {
-            add_error(
-                realm_errors::REALM_PASSWORD_POLICY_LOWERCASE_ID,
-                realm_errors::REALM_PASSWORD_POLICY_LOWERCASE_KEY,
-                errors,
-            );
-        }
-        if !policy.contains("digits(1)") {
-            add_error(
-                realm_errors::REALM_PASSWORD_POLICY_DIGIT_ID,
-                realm_errors::REALM_PASSWORD_POLICY_DIGIT_KEY,
-                errors,
-            );
+    // password_policy must contain all components of the policy
+    if let Some(password_policy) = &rep.password_policy {
+        for component in policy.missing_password_policy(password_policy) {
+            let (id, key) = password_policy_error(component);
+            if !errors.iter().any(|e| e.id == id) {
+                add_error(id, key, errors);
+            }
         }
-    } else {
+    } else if !policy.password_policy.is_empty() {
         add_error(
             realm_errors::REALM_PASSWORD_POLICY_MISSING_ID,
             realm_errors::REALM_PASSWORD_POLICY_MISSING_KEY,
             errors,
         );
     }
-    // remember_me must be `true`
-    if !rep.remember_me.unwrap_or(false) {
+    // remember_me must match the policy
+    if rep.remember_me.unwrap_or(false) != policy.remember_me {
         add_error(
             realm_errors::REALM_REMEMBER_ME_ID,
             realm_errors::REALM_REMEMBER_ME_KEY,
             errors,
         );
     }
-    // registration_allowed must be `false`
-    if rep.registration_allowed.unwrap_or(false) {
+    // registration_allowed must match the policy
+    if rep.registration_allowed.unwrap_or(false) != policy.registration_allowed {
         add_error(
             realm_errors::REALM_REGISTRATION_ALLOWED_ID,
             realm_errors::REALM_REGISTRATION_ALLOWED_KEY,
             errors,
         );
     }
-    // reset_password_allowed must be `true`
-    if !rep.reset_password_allowed.unwrap_or(false) {
+    // reset_password_allowed must match the policy
+    if rep.reset_password_allowed.unwrap_or(false) != policy.reset_password_allowed {
         add_error(
             realm_errors::REALM_RESET_PASSWORD_ALLOWED_ID,
             realm_errors::REALM_RESET_PASSWORD_ALLOWED_KEY,
             errors,
         );
     }
-    // supported_locales must contain `de`
+    // supported_locales must contain the locales of the policy
     if let Some(locales) = &rep.supported_locales {
-        if !locales.contains(&"de".to_string()) {
+        if !policy
+            .supported_locales
+            .iter()
+            .all(|locale| locales.contains(locale))
+        {
             add_error(
                 realm_errors::REALM_SUPPORTED_LOCALES_INVALID_ID,
                 realm_errors::REALM_SUPPORTED_LOCALES_INVALID_KEY,
@@ -148,7 +124,7 @@ async fn check_realm_settings(
     // smtp_server must be configured
     if let Some(smtp_server) = &rep.smtp_server {
         check_realm_smtp_settings(ctx, smtp_server, errors);
-    } else {
+    } else if policy.smtp_required {
         add_error(
             realm_errors::REALM_SMTP_SERVER_MISSING_ID,
             realm_errors::REALM_SMTP_SERVER_MISSING_KEY,
@@ -285,10 +261,12 @@ async fn check_client(
                 errors,
             );
         }
-        // redirect_uris must contain a pattern matching the configured value
+        // redirect_uris must be allowed by the policy or match the configured value
         if let Some(urls) = &client.redirect_uris {
             if !urls.iter().all(|url| {
-                url == ctx.cfg().public_url() || url.replace('*', "") == ctx.cfg().public_url()
+                ctx.cfg()
+                    .policy()
+                    .is_redirect_uri_allowed(url, ctx.cfg().public_url())
             }) {
                 log::info!(
                     "[{}]: Expected the 'redirect_uris' values '{:?}' to contain a pattern that matches '{}'",
@@ -365,6 +343,36 @@ async fn check_client(
     Ok(())
 }
 
+/// Error of a missing password policy component, unknown components share one error.
+fn password_policy_error(component: &str) -> (&'static str, &'static str) {
+    match component.split('(').next().unwrap_or_default() {
+        "length" => (
+            realm_errors::REALM_PASSWORD_POLICY_LENGTH_ID,
+            realm_errors::REALM_PASSWORD_POLICY_LENGTH_KEY,
+        ),
+        "specialChars" => (
+            realm_errors::REALM_PASSWORD_POLICY_SYMBOL_ID,
+            realm_errors::REALM_PASSWORD_POLICY_SYMBOL_KEY,
+        ),
+        "upperCase" => (
+            realm_errors::REALM_PASSWORD_POLICY_UPPERCASE_ID,
+            realm_errors::REALM_PASSWORD_POLICY_UPPERCASE_KEY,
+        ),
+        "lowerCase" => (
+            realm_errors::REALM_PASSWORD_POLICY_LOWERCASE_ID,
+            realm_errors::REALM_PASSWORD_POLICY_LOWERCASE_KEY,
+        ),
+        "digits" => (
+            realm_errors::REALM_PASSWORD_POLICY_DIGIT_ID,
+            realm_errors::REALM_PASSWORD_POLICY_DIGIT_KEY,
+        ),
+        _ => (
+            realm_errors::REALM_PASSWORD_POLICY_INVALID_ID,
+            realm_errors::REALM_PASSWORD_POLICY_INVALID_KEY,
+        ),
+    }
+}
+
 fn add_error<S>(error_id: S, error_key: S, errors: &mut Vec<RealmConfigError>)
 where
     S: Into<String>,
@@ -404,7 +412,7 @@ fn check_realm_smtp_settings(
     }
 
     if let Some(starttls_value) = smtp_server.get("starttls") {
-        // starttls must be the configured value or `false` if not configured
+        // starttls must be the configured value or the one of the policy
         let starttls = get_bool_from_string_value(starttls_value);
         if let Some(configured_starttls) = ctx.cfg().keycloak().smtp_starttls() {
             if configured_starttls != &starttls {
@@ -419,7 +427,7 @@ fn check_realm_smtp_settings(
                     errors,
                 );
             }
-        } else if starttls {
+        } else if starttls != ctx.cfg().policy().smtp_starttls {
             add_error(
                 realm_errors::REALM_SMTP_SERVER_STARTTLS_INVALID_ID,
                 realm_errors::REALM_SMTP_SERVER_STARTTLS_INVALID_KEY,
@@ -435,7 +443,7 @@ fn check_realm_smtp_settings(
     }
 
     if let Some(port_value) = smtp_server.get("port") {
-        // port must be the configured value or `1025` if not configured
+        // port must be the configured value or the one of the policy
         let port = get_u16_from_value(port_value);
         if let Some(configured_port) = ctx.cfg().keycloak().smtp_port() {
             if configured_port != &port {
@@ -450,7 +458,7 @@ fn check_realm_smtp_settings(
                     errors,
                 );
             }
-        } else if port != 1025 {
+        } else if port != ctx.cfg().policy().smtp_port {
             add_error(
                 realm_errors::REALM_SMTP_SERVER_PORT_INVALID_ID,
                 realm_errors::REALM_SMTP_SERVER_PORT_INVALID_KEY,
@@ -466,7 +474,7 @@ fn check_realm_smtp_settings(
     }
 
     if let Some(host) = smtp_server.get("host") {
-        // port must be the configured value or `smtp` if not configured
+        // host must be the configured value or the one of the policy
         if let Some(configured_host) = ctx.cfg().keycloak().smtp_host() {
             if configured_host != host {
                 log::info!(
@@ -480,7 +488,7 @@ fn check_realm_smtp_settings(
                     errors,
                 );
             }
-        } else if host != "smtp" {
+        } else if host != &ctx.cfg().policy().smtp_host {
             add_error(
                 realm_errors::REALM_SMTP_SERVER_HOST_INVALID_ID,
                 realm_errors::REALM_SMTP_SERVER_HOST_INVALID_KEY,
@@ -520,7 +528,7 @@ fn check_realm_smtp_settings(
     }
 
     if let Some(from) = smtp_server.get("from") {
-        // from must be the configured value or `noreply@qm.local` if not configured
+        // from must be the configured value or the one of the policy
         if let Some(configured_from) = ctx.cfg().keycloak().smtp_from() {
             if configured_from != from {
                 log::info!(
@@ -534,7 +542,7 @@ fn check_realm_smtp_settings(
                     errors,
                 );
             }
-        } else if from != "noreply@qm.local" {
+        } else if from != &ctx.cfg().policy().smtp_from {
             add_error(
                 realm_errors::REALM_SMTP_SERVER_FROM_INVALID_ID,
                 realm_errors::REALM_SMTP_SERVER_FROM_INVALID_KEY,
@@ -574,7 +582,7 @@ fn check_realm_smtp_settings(
     }
 
     if let Some(ssl_value) = smtp_server.get("ssl") {
-        // ssl must be the configured value or `false` if not configured
+        // ssl must be the configured value or the one of the policy
         let ssl = get_bool_from_string_value(ssl_value);
         if let Some(configured_ssl) = ctx.cfg().keycloak().smtp_ssl() {
             if configured_ssl != &ssl {
@@ -589,7 +597,7 @@ fn check_realm_smtp_settings(
                     errors,
                 );
             }
-        } else if ssl {
+        } else if ssl != ctx.cfg().policy().smtp_ssl {
             add_error(
                 realm_errors::REALM_SMTP_SERVER_SSL_INVALID_ID,
                 realm_errors::REALM_SMTP_SERVER_SSL_INVALID_KEY,
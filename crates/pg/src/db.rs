@@ -70,3 +70,11 @@ impl DB {
         &self.inner.pool
     }
 }
+
+impl From<PgPool> for DB {
+    fn from(pool: PgPool) -> Self {
+        Self {
+            inner: Arc::new(Inner { pool }),
+        }
+    }
+}
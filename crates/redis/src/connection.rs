@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use redis::aio::{ConnectionLike, MultiplexedConnection};
+use redis::{Cmd, Pipeline, RedisFuture, Value};
+
+/// Source of the connections of the worker loops.
+///
+/// A multiplexed connection of a client is kept until it is reset after an error, a
+/// connection of a pool is returned to the pool with [`Connections::release`].
+#[derive(Clone)]
+pub(crate) enum Connections {
+    Client(Arc<redis::Client>),
+    Pool(Arc<deadpool_redis::Pool>),
+}
+
+pub(crate) enum Connection {
+    Multiplexed(MultiplexedConnection),
+    Pooled(deadpool_redis::Connection),
+}
+
+impl Connections {
+    pub(crate) async fn get(&self) -> anyhow::Result<Connection> {
+        Ok(match self {
+            Self::Client(client) => {
+                Connection::Multiplexed(client.get_multiplexed_async_connection().await?)
+            }
+            Self::Pool(pool) => Connection::Pooled(pool.get().await?),
+        })
+    }
+
+    /// Connection of `con`, a new one is taken if there is none.
+    pub(crate) async fn connect<'a>(
+        &self,
+        con: &'a mut Option<Connection>,
+    ) -> anyhow::Result<&'a mut Connection> {
+        if con.is_none() {
+            *con = Some(self.get().await?);
+        }
+        Ok(con.as_mut().expect("connection is set"))
+    }
+
+    /// Returns a pooled connection after a step of a worker loop.
+    pub(crate) fn release(&self, con: &mut Option<Connection>) {
+        if let Self::Pool(_) = self {
+            con.take();
+        }
+    }
+}
+
+impl ConnectionLike for Connection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            Self::Multiplexed(con) => con.req_packed_command(cmd),
+            Self::Pooled(con) => con.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            Self::Multiplexed(con) => con.req_packed_commands(cmd, offset, count),
+            Self::Pooled(con) => con.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            Self::Multiplexed(con) => con.get_db(),
+            Self::Pooled(con) => con.get_db(),
+        }
+    }
+}
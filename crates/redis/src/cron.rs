@@ -0,0 +1,216 @@
+use chrono::{DateTime, TimeZone, Utc};
+use redis::AsyncCommands;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::connection::Connections;
+use crate::lock;
+use crate::work_queue::{Item, KeyPrefix, WorkQueue};
+use crate::{AsyncWorker, Workers};
+
+const KEY_PREFIX: &str = "qm_cron:";
+const LOCK_TTL: usize = 30_000;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CronStatus {
+    pub last_run: Option<DateTime<Utc>>,
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+struct CronJob {
+    name: String,
+    schedule: cron::Schedule,
+    queue: WorkQueue,
+    data: Box<[u8]>,
+    lock_key: String,
+    last_run_key: String,
+    next_run_key: String,
+}
+
+fn to_date_time(millis: Option<i64>) -> Option<DateTime<Utc>> {
+    millis.and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+}
+
+impl CronJob {
+    fn next_after(&self, time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedule.after(time).next()
+    }
+
+    async fn next_run<C: AsyncCommands>(&self, db: &mut C) -> anyhow::Result<Option<i64>> {
+        Ok(db.get(&self.next_run_key).await?)
+    }
+
+    /// Enqueues the job if it is due, the lock makes sure only one replica runs it.
+    async fn tick<C: AsyncCommands>(&self, db: &mut C) -> anyhow::Result<bool> {
+        let now = Utc::now();
+        let Some(next_run) = self.next_run(db).await? else {
+            if let Some(next) = self.next_after(&now) {
+                db.set_nx::<_, _, ()>(&self.next_run_key, next.timestamp_millis())
+                    .await?;
+            }
+            return Ok(false);
+        };
+        if next_run > now.timestamp_millis() {
+            return Ok(false);
+        }
+        let Ok(l) = lock::try_lock(db, &self.lock_key, LOCK_TTL).await else {
+            return Ok(false);
+        };
+        // another replica could have run the job before the lock was acquired
+        let result = async {
+            if self.next_run(db).await? != Some(next_run) {
+                return anyhow::Ok(false);
+            }
+            let item = Item::new(self.data.clone());
+            let mut pipeline = redis::pipe();
+            self.queue.add_item_to_pipeline(&mut pipeline, &item);
+            pipeline.set(&self.last_run_key, now.timestamp_millis());
+            if let Some(next) = self.next_after(&now) {
+                pipeline.set(&self.next_run_key, next.timestamp_millis());
+            } else {
+                pipeline.del(&self.next_run_key);
+            }
+            pipeline.query_async::<_, ()>(db).await?;
+            Ok(true)
+        }
+        .await;
+        lock::unlock(db, &self.lock_key, &l.id).await?;
+        result
+    }
+}
+
+pub async fn status<C: AsyncCommands>(db: &mut C, name: &str) -> anyhow::Result<CronStatus> {
+    let prefix = KeyPrefix::new(format!("{KEY_PREFIX}{name}"));
+    let last_run: Option<i64> = db.get(prefix.of(":last_run")).await?;
+    let next_run: Option<i64> = db.get(prefix.of(":next_run")).await?;
+    Ok(CronStatus {
+        last_run: to_date_time(last_run),
+        next_run: to_date_time(next_run),
+    })
+}
+
+/// Registers recurring jobs which are enqueued into `AsyncWorker` queues.
+///
+/// Expressions use the `cron` crate format including seconds, e.g. `0 */5 * * * *`.
+pub struct CronBuilder {
+    workers: Workers,
+    jobs: Vec<CronJob>,
+    interval: Duration,
+}
+
+impl CronBuilder {
+    pub(crate) fn new(workers: Workers) -> Self {
+        Self {
+            workers,
+            jobs: vec![],
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Interval in which the schedules are checked.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_job<Ctx, T>(
+        mut self,
+        name: &str,
+        expression: &str,
+        worker: &AsyncWorker<Ctx, T>,
+        data: &T,
+    ) -> anyhow::Result<Self>
+    where
+        Ctx: Clone + Send + Sync + 'static,
+        T: DeserializeOwned + Serialize + Send + Sync,
+    {
+        let schedule = cron::Schedule::from_str(expression)
+            .map_err(|err| anyhow::anyhow!("invalid cron expression '{expression}': {err}"))?;
+        let prefix = KeyPrefix::new(format!("{KEY_PREFIX}{name}"));
+        self.jobs.push(CronJob {
+            name: name.to_string(),
+            schedule,
+            queue: WorkQueue::new(KeyPrefix::new(worker.prefix.clone())),
+            data: serde_json::to_vec(data)?.into_boxed_slice(),
+            lock_key: prefix.of(":lock"),
+            last_run_key: prefix.of(":last_run"),
+            next_run_key: prefix.of(":next_run"),
+        });
+        Ok(self)
+    }
+
+    pub fn start(self) {
+        let client = self.workers.inner.client.clone();
+        let jobs = Arc::new(self.jobs);
+        let interval = self.interval;
+        self.workers.spawn(
+            "cron scheduler".to_string(),
+            move |is_running: Arc<AtomicBool>| async move {
+                log::info!("start cron scheduler with {} jobs", jobs.len());
+                let connections = Connections::Client(client);
+                let mut con = None;
+                loop {
+                    if !is_running.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let mut failed = false;
+                    match connections.connect(&mut con).await {
+                        Ok(db) => {
+                            for job in jobs.iter() {
+                                match job.tick(db).await {
+                                    Ok(true) => log::info!("enqueued cron job '{}'", job.name),
+                                    Ok(false) => {}
+                                    Err(err) => {
+                                        failed = true;
+                                        log::error!("cron job '{}' failed: {err:#?}", job.name)
+                                    }
+                                }
+                            }
+                        }
+                        Err(err) => log::error!("unable to connect cron scheduler: {err:#?}"),
+                    }
+                    if failed {
+                        // Reconnect with the next tick, the connection might be broken.
+                        con = None;
+                    }
+                    tokio::time::sleep(interval).await;
+                }
+                Ok(())
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    use crate::{AsyncWorker, Workers};
+
+    #[test]
+    fn cron_job_test() -> anyhow::Result<()> {
+        let client = crate::redis::Client::open("redis://127.0.0.1:6379")?;
+        let workers = Workers::new_with_client(Arc::new(client));
+        let worker = AsyncWorker::<(), ()>::new("test");
+        assert!(workers
+            .cron()
+            .with_job("invalid", "every minute", &worker, &())
+            .is_err());
+        let builder = workers
+            .cron()
+            .with_job("every_minute", "0 * * * * *", &worker, &())?;
+        let job = &builder.jobs[0];
+        assert_eq!(job.next_run_key, "qm_cron:every_minute:next_run");
+        let time = Utc.with_ymd_and_hms(2024, 4, 10, 9, 0, 30).unwrap();
+        assert_eq!(
+            job.next_after(&time),
+            Some(Utc.with_ymd_and_hms(2024, 4, 10, 9, 1, 0).unwrap())
+        );
+        Ok(())
+    }
+}
@@ -2,7 +2,10 @@ pub use deadpool_redis::redis;
 use deadpool_redis::Runtime;
 use std::sync::Arc;
 mod config;
+mod connection;
+pub mod cron;
 pub mod lock;
+pub mod tasks;
 pub mod work_queue;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
@@ -15,15 +18,19 @@ use std::pin::Pin;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::time::Duration;
+use std::time::SystemTime;
 use tokio::runtime::Builder;
 use tokio::sync::RwLock;
 use tokio::task::LocalSet;
+use work_queue::DeadItem;
 use work_queue::Item;
 use work_queue::KeyPrefix;
 use work_queue::WorkQueue;
 
 pub use crate::config::Config as RedisConfig;
+use crate::connection::{Connection, Connections};
 use crate::lock::Lock;
+use crate::lock::LockGuard;
 
 pub struct Inner {
     config: RedisConfig,
@@ -90,6 +97,21 @@ impl Redis {
         lock::lock(&mut con, key, ttl, retry_count, retry_delay).await
     }
 
+    /// Acquires a lock which is extended while it is held and released when it is dropped.
+    pub async fn lock_guard(
+        &self,
+        key: &str,
+        ttl: usize,
+        retry_count: u32,
+        retry_delay: u32,
+    ) -> Result<LockGuard, lock::Error> {
+        LockGuard::acquire(&self.inner.pool, key, ttl, retry_count, retry_delay).await
+    }
+
+    pub async fn try_lock_guard(&self, key: &str, ttl: usize) -> Result<LockGuard, lock::Error> {
+        LockGuard::try_acquire(&self.inner.pool, key, ttl).await
+    }
+
     pub async fn unlock(&self, key: &str, lock_id: &str) -> Result<i64, lock::Error> {
         let mut con = self.connect().await?;
         lock::unlock(&mut con, key, lock_id).await
@@ -121,6 +143,7 @@ where
     pub queue: Arc<WorkQueue>,
     pub client: Arc<redis::Client>,
     pub item: Item,
+    connections: Connections,
 }
 
 impl<Ctx> WorkerContext<Ctx>
@@ -131,7 +154,7 @@ where
         &self.ctx
     }
     pub async fn complete(&self) -> anyhow::Result<()> {
-        let mut con = self.client.get_multiplexed_async_connection().await?;
+        let mut con = self.connections.get().await?;
         self.queue.complete(&mut con, &self.item).await?;
         Ok(())
     }
@@ -158,7 +181,7 @@ where
 }
 
 async fn run_recovery_worker<Ctx, T>(
-    client: Arc<redis::Client>,
+    connections: Connections,
     is_running: Arc<AtomicBool>,
     worker: Arc<AsyncWorker<Ctx, T>>,
 ) -> anyhow::Result<()>
@@ -167,20 +190,174 @@ where
     T: DeserializeOwned + Send + Sync,
 {
     log::info!("start {} worker recovery", worker.prefix);
-    let mut con = client.get_multiplexed_async_connection().await?;
+    let mut con = None;
     loop {
         if !is_running.load(Ordering::SeqCst) {
             break;
         }
         tokio::time::sleep(Duration::from_secs(10)).await;
-        worker.recover(&mut con).await?;
+        let result = match connections.connect(&mut con).await {
+            Ok(con) => worker.recover(con).await,
+            Err(err) => Err(err),
+        };
+        connections.release(&mut con);
+        if let Err(err) = result {
+            // Reconnect with the next attempt, the connection might be broken.
+            con = None;
+            log::error!("unable to recover items of {}: {err:#?}", worker.prefix);
+        }
+    }
+    Ok(())
+}
+
+async fn promote_due(
+    connections: &Connections,
+    con: &mut Option<Connection>,
+    queue: &WorkQueue,
+) -> anyhow::Result<usize> {
+    let count = queue.promote_due(connections.connect(con).await?).await?;
+    connections.release(con);
+    Ok(count)
+}
+
+async fn run_promoter_worker<Ctx, T>(
+    connections: Connections,
+    is_running: Arc<AtomicBool>,
+    worker: Arc<AsyncWorker<Ctx, T>>,
+) -> anyhow::Result<()>
+where
+    Ctx: Clone + Send + Sync + 'static,
+    T: DeserializeOwned + Send + Sync,
+{
+    log::info!("start {} worker promoter", worker.prefix);
+    let queue = WorkQueue::new(KeyPrefix::new(worker.prefix.clone()));
+    let mut con = None;
+    let mut failures = 0;
+    loop {
+        if !is_running.load(Ordering::SeqCst) {
+            break;
+        }
+        let delay = match promote_due(&connections, &mut con, &queue).await {
+            Ok(count) => {
+                failures = 0;
+                if count > 0 {
+                    log::debug!("promoted {count} due items of {}", worker.prefix);
+                }
+                worker.promote_interval
+            }
+            Err(err) => {
+                // Reconnect with the next attempt, the connection might be broken.
+                con = None;
+                failures += 1;
+                let delay = worker.backoff(failures);
+                log::error!(
+                    "unable to promote due items of {}, retry in {delay:?}: {err:#?}",
+                    worker.prefix
+                );
+                delay
+            }
+        };
+        tokio::time::sleep(delay).await;
     }
     Ok(())
 }
 
+/// Leases the next item of the queue and runs the work on it.
+async fn process_next<Ctx, T>(
+    ctx: &Ctx,
+    client: &Arc<redis::Client>,
+    connections: &Connections,
+    slot: &mut Option<Connection>,
+    request_queue: &Arc<WorkQueue>,
+    worker: &AsyncWorker<Ctx, T>,
+    worker_id: usize,
+) -> anyhow::Result<()>
+where
+    Ctx: Clone + Send + Sync + 'static,
+    T: DeserializeOwned + Send + Sync,
+{
+    let con = connections.connect(slot).await?;
+    let Some(item) = request_queue
+        .lease(
+            con,
+            Some(Duration::from_secs(worker.timeout)),
+            Duration::from_secs(worker.lease_duration),
+        )
+        .await?
+    else {
+        return Ok(());
+    };
+    if item.data.is_empty() {
+        log::info!("item is empty");
+        request_queue.complete(con, &item).await?;
+        return Ok(());
+    }
+    let request = match serde_json::from_slice::<T>(&item.data) {
+        Ok(request) => request,
+        Err(err) => {
+            log::error!(
+                "invalid request item on worker {} #{worker_id}, move to dead letter queue: {err} Item: {}",
+                worker.prefix,
+                String::from_utf8_lossy(&item.data)
+            );
+            request_queue.bury(con, &item).await?;
+            return Ok(());
+        }
+    };
+    let Some(work) = worker.work.as_ref() else {
+        return Ok(());
+    };
+    let result = work
+        .run(
+            WorkerContext {
+                ctx: ctx.clone(),
+                worker_id,
+                queue: request_queue.clone(),
+                client: client.clone(),
+                item: Item {
+                    id: item.id.clone(),
+                    data: Box::new([]),
+                },
+                connections: connections.clone(),
+            },
+            request,
+        )
+        .await;
+    let Err(err) = result else {
+        return Ok(());
+    };
+    let Some(attempts) = request_queue.record_attempt(con, &item).await? else {
+        log::warn!(
+            "item '{}' on worker {} #{worker_id} failed after it was completed: {err:#?}",
+            item.id,
+            worker.prefix
+        );
+        return Ok(());
+    };
+    if attempts >= worker.max_attempts {
+        log::error!(
+            "item '{}' on worker {} #{worker_id} failed {attempts} times, move to dead letter queue: {err:#?}",
+            item.id,
+            worker.prefix
+        );
+        request_queue.bury(con, &item).await?;
+    } else {
+        let delay = worker.backoff(attempts);
+        log::warn!(
+            "item '{}' on worker {} #{worker_id} failed {attempts} times, retry in {delay:?}: {err:#?}",
+            item.id,
+            worker.prefix
+        );
+        request_queue.retry_later(con, &item, delay).await?;
+    }
+    Ok(())
+}
+
+/// Processes items until the workers are terminated, failed steps are retried with backoff.
 async fn run_worker_queue<Ctx, T>(
     ctx: Ctx,
     client: Arc<redis::Client>,
+    connections: Connections,
     is_running: Arc<AtomicBool>,
     worker: Arc<AsyncWorker<Ctx, T>>,
     worker_id: usize,
@@ -191,50 +368,35 @@ where
 {
     log::info!("start {} worker #{worker_id} queue", worker.prefix);
     let request_queue = Arc::new(WorkQueue::new(KeyPrefix::new(worker.prefix.clone())));
-    let mut con = client.get_multiplexed_async_connection().await?;
+    let mut slot = None;
+    let mut failures = 0;
     loop {
+        connections.release(&mut slot);
         if !is_running.load(Ordering::SeqCst) {
             break;
         }
-        if let Some(item) = request_queue
-            .lease(
-                &mut con,
-                Some(Duration::from_secs(worker.timeout)),
-                Duration::from_secs(worker.lease_duration),
-            )
-            .await?
-        {
-            if item.data.is_empty() {
-                log::info!("item is empty");
-                request_queue.complete(&mut con, &item).await?;
-                continue;
-            }
-            if let Ok(request) = serde_json::from_slice::<T>(&item.data).map_err(|err| {
+        let result = process_next(
+            &ctx,
+            &client,
+            &connections,
+            &mut slot,
+            &request_queue,
+            &worker,
+            worker_id,
+        )
+        .await;
+        match result {
+            Ok(()) => failures = 0,
+            Err(err) => {
+                // Reconnect with the next attempt, the connection might be broken.
+                slot = None;
+                failures += 1;
+                let delay = worker.backoff(failures);
                 log::error!(
-                    "invalid request item on worker {} #{worker_id} Item: {}",
-                    worker.prefix,
-                    String::from_utf8_lossy(&item.data)
+                    "worker {} #{worker_id} failed, retry in {delay:?}: {err:#?}",
+                    worker.prefix
                 );
-                err
-            }) {
-                if let Some(work) = worker.work.as_ref() {
-                    work.run(
-                        WorkerContext {
-                            ctx: ctx.clone(),
-                            worker_id,
-                            queue: request_queue.clone(),
-                            client: client.clone(),
-                            item: Item {
-                                id: item.id.clone(),
-                                data: Box::new([]),
-                            },
-                        },
-                        request,
-                    )
-                    .await?;
-                }
-            } else {
-                request_queue.complete(&mut con, &item).await?;
+                tokio::time::sleep(delay).await;
             }
         }
     }
@@ -300,15 +462,23 @@ impl Workers {
                         }),
                     )
                     .await;
-                    if let Err(err) = run_recovery_worker(client, is_running, worker).await {
+                    let result =
+                        run_recovery_worker(Connections::Client(client), is_running, worker).await;
+                    if let Err(err) = result {
                         log::error!("{err:#?}");
-                        std::process::exit(1);
                     }
                     tx.send(()).ok();
                 });
                 rt.block_on(local);
             });
         }
+        {
+            let client = self.inner.client.clone();
+            let worker = worker.clone();
+            self.spawn(format!("{} promoter", worker.prefix), move |is_running| {
+                run_promoter_worker(Connections::Client(client), is_running, worker)
+            });
+        }
         for worker_id in 0..worker.num_workers {
             let worker = worker.clone();
             let client = self.inner.client.clone();
@@ -334,11 +504,12 @@ impl Workers {
                         }),
                     )
                     .await;
-                    if let Err(err) =
-                        run_worker_queue(ctx.clone(), client, is_running, worker, worker_id).await
-                    {
+                    let connections = Connections::Client(client.clone());
+                    let result =
+                        run_worker_queue(ctx, client, connections, is_running, worker, worker_id)
+                            .await;
+                    if let Err(err) = result {
                         log::error!("{err:#?}");
-                        std::process::exit(1);
                     }
                     tx.send(()).ok();
                 });
@@ -348,6 +519,48 @@ impl Workers {
         Ok(())
     }
 
+    /// Runs `f` on a dedicated thread until the workers are terminated.
+    ///
+    /// An error of `f` is logged and ends the task, `f` should retry transient errors itself.
+    pub(crate) fn spawn<F, Fut>(&self, name: String, f: F)
+    where
+        F: FnOnce(Arc<AtomicBool>) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + 'static,
+    {
+        let instances = self.inner.instances.clone();
+        let _th = std::thread::spawn(move || {
+            let rt = Builder::new_current_thread().enable_all().build().unwrap();
+            let local = LocalSet::new();
+            local.spawn_local(async move {
+                let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+                let is_running = Arc::new(AtomicBool::new(true));
+                let is_fut_running = is_running.clone();
+                let task = name.clone();
+                add(
+                    is_running.clone(),
+                    instances,
+                    Box::pin(async move {
+                        log::info!("stopping {name}");
+                        is_fut_running.store(false, Ordering::SeqCst);
+                        rx.await.ok();
+                        name
+                    }),
+                )
+                .await;
+                if let Err(err) = f(is_running).await {
+                    log::error!("{task} stopped: {err:#?}");
+                }
+                tx.send(()).ok();
+            });
+            rt.block_on(local);
+        });
+    }
+
+    /// Builder to register recurring jobs, see [`cron::CronBuilder`].
+    pub fn cron(&self) -> cron::CronBuilder {
+        cron::CronBuilder::new(self.clone())
+    }
+
     pub async fn terminate(&self) -> anyhow::Result<()> {
         if !self.inner.is_running.load(Ordering::SeqCst) {
             anyhow::bail!("Workers already terminated");
@@ -403,6 +616,58 @@ impl Producer {
         self.queue.add_item(&mut con, &item).await?;
         Ok(())
     }
+
+    /// Adds an item which is processed at the given time.
+    pub async fn add_item_at<T>(&self, data: &T, at: SystemTime) -> anyhow::Result<()>
+    where
+        T: Serialize,
+    {
+        let item = Item::from_json_data(data)?;
+        let mut con = self.client.get().await?;
+        self.queue.add_item_at(&mut con, &item, at).await?;
+        Ok(())
+    }
+
+    /// Adds an item which is processed after the given delay.
+    pub async fn add_item_delayed<T>(&self, data: &T, delay: Duration) -> anyhow::Result<()>
+    where
+        T: Serialize,
+    {
+        let item = Item::from_json_data(data)?;
+        let mut con = self.client.get().await?;
+        self.queue.add_item_delayed(&mut con, &item, delay).await?;
+        Ok(())
+    }
+
+    pub async fn dead_len(&self) -> anyhow::Result<usize> {
+        let mut con = self.client.get().await?;
+        Ok(self.queue.dead_len(&mut con).await?)
+    }
+
+    pub async fn dead_items(&self, offset: isize, count: isize) -> anyhow::Result<Vec<DeadItem>> {
+        let mut con = self.client.get().await?;
+        Ok(self.queue.dead_items(&mut con, offset, count).await?)
+    }
+
+    pub async fn requeue_dead(&self, item_id: &str) -> anyhow::Result<bool> {
+        let mut con = self.client.get().await?;
+        Ok(self.queue.requeue_dead(&mut con, item_id).await?)
+    }
+
+    pub async fn requeue_all_dead(&self) -> anyhow::Result<usize> {
+        let mut con = self.client.get().await?;
+        Ok(self.queue.requeue_all_dead(&mut con).await?)
+    }
+
+    pub async fn purge_dead(&self, item_id: &str) -> anyhow::Result<bool> {
+        let mut con = self.client.get().await?;
+        Ok(self.queue.purge_dead(&mut con, item_id).await?)
+    }
+
+    pub async fn purge_all_dead(&self) -> anyhow::Result<usize> {
+        let mut con = self.client.get().await?;
+        Ok(self.queue.purge_all_dead(&mut con).await?)
+    }
 }
 
 pub struct AsyncWorker<Ctx, T>
@@ -414,6 +679,10 @@ where
     num_workers: usize,
     timeout: u64,
     lease_duration: u64,
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    promote_interval: Duration,
     recovery_key: String,
     recovery_queue: WorkQueue,
     work: Option<Box<dyn Work<Ctx, T>>>,
@@ -435,6 +704,10 @@ where
             recovery_queue: WorkQueue::new(name),
             timeout: 5,
             lease_duration: 60,
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+            promote_interval: Duration::from_secs(1),
             num_workers: 1,
             prefix,
             work: None,
@@ -456,6 +729,32 @@ where
         self
     }
 
+    /// Number of failed runs after which an item is moved to the dead letter queue.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Delay before the first retry, it doubles with every failed attempt up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Interval in which due delayed and scheduled items are moved to the main queue.
+    pub fn with_promote_interval(mut self, promote_interval: Duration) -> Self {
+        self.promote_interval = promote_interval;
+        self
+    }
+
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
     pub fn producer(&self, client: Arc<deadpool_redis::Pool>) -> Producer {
         Producer {
             client,
@@ -475,3 +774,19 @@ where
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_test() {
+        let worker = super::AsyncWorker::<(), ()>::new("test")
+            .with_backoff(Duration::from_secs(2), Duration::from_secs(30));
+        assert_eq!(worker.backoff(1), Duration::from_secs(2));
+        assert_eq!(worker.backoff(2), Duration::from_secs(4));
+        assert_eq!(worker.backoff(4), Duration::from_secs(16));
+        assert_eq!(worker.backoff(5), Duration::from_secs(30));
+        assert_eq!(worker.backoff(64), Duration::from_secs(30));
+    }
+}
@@ -1,6 +1,9 @@
 use redis::AsyncCommands;
 use redis::RedisError;
 use redis::Value as RedisValue;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
 use tokio::time::{sleep, Duration};
 use uuid::Uuid;
 
@@ -45,11 +48,30 @@ const UNLOCK_SCRIPT: &str = r#"
   end
 "#;
 
+const FENCED_LOCK_SCRIPT: &str = r#"
+  if redis.call("set", KEYS[1], ARGV[1], "px", ARGV[2], "nx") then
+    return redis.call("incr", KEYS[2])
+  else
+    return nil
+  end
+"#;
+const EXTEND_SCRIPT: &str = r#"
+  if redis.call("get", KEYS[1]) == ARGV[1] then
+    return redis.call("pexpire", KEYS[1], ARGV[2])
+  else
+    return 0
+  end
+"#;
+
 #[derive(Debug)]
 pub struct Lock {
     pub id: String,
 }
 
+fn fencing_key(key: &str) -> String {
+    format!("{key}:fencing")
+}
+
 pub async fn try_lock<C: AsyncCommands, T: AsRef<str>>(
     db: &mut C,
     key: T,
@@ -118,3 +140,385 @@ where
         _ => Ok(0),
     }
 }
+
+pub async fn extend<C: AsyncCommands, K, V>(
+    db: &mut C,
+    key: K,
+    lock_id: V,
+    ttl: usize,
+) -> Result<bool, Error>
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let result: i64 = redis::Script::new(EXTEND_SCRIPT)
+        .key(key.as_ref())
+        .arg(lock_id.as_ref())
+        .arg(ttl)
+        .invoke_async(db)
+        .await?;
+    Ok(result == 1)
+}
+
+/// Returns the fencing token of the most recent holder of the lock `key`.
+pub async fn fencing_token<C: AsyncCommands, K>(db: &mut C, key: K) -> Result<u64, Error>
+where
+    K: AsRef<str>,
+{
+    let token: Option<u64> = db.get(fencing_key(key.as_ref())).await?;
+    Ok(token.unwrap_or_default())
+}
+
+/// Connections of a [`LockGuard`], the watchdog and the release on drop take their own.
+#[async_trait::async_trait]
+pub trait LockPool: Clone + Send + Sync + 'static {
+    type Connection: AsyncCommands;
+
+    async fn connection(&self) -> Result<Self::Connection, Error>;
+}
+
+#[async_trait::async_trait]
+impl LockPool for deadpool_redis::Pool {
+    type Connection = deadpool_redis::Connection;
+
+    async fn connection(&self) -> Result<Self::Connection, Error> {
+        Ok(self.get().await?)
+    }
+}
+
+async fn try_lock_fenced<P: LockPool>(
+    pool: &P,
+    key: &str,
+    ttl: usize,
+) -> Result<Option<(String, u64)>, Error> {
+    let mut con = pool.connection().await?;
+    let id = Uuid::new_v4().to_string();
+    let token: Option<u64> = redis::Script::new(FENCED_LOCK_SCRIPT)
+        .key(key)
+        .key(fencing_key(key))
+        .arg(&id)
+        .arg(ttl)
+        .invoke_async(&mut con)
+        .await?;
+    Ok(token.map(|token| (id, token)))
+}
+
+async fn watchdog<P: LockPool>(
+    pool: P,
+    key: String,
+    id: String,
+    ttl: usize,
+    lost: Arc<AtomicBool>,
+) {
+    let interval = Duration::from_millis((ttl as u64 / 3).max(1));
+    loop {
+        sleep(interval).await;
+        let result = match pool.connection().await {
+            Ok(mut con) => extend(&mut con, &key, &id, ttl).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(true) => {}
+            Ok(false) => {
+                log::warn!("lock '{key}' was lost before it was released");
+                lost.store(true, Ordering::SeqCst);
+                return;
+            }
+            Err(err) => log::error!("unable to extend lock '{key}': {err}"),
+        }
+    }
+}
+
+/// Lock which is extended in the background while it is held and released on drop.
+///
+/// Every acquisition increments a fencing token, writes guarded by the lock can store the token
+/// and reject writes with a lower token from a stale lock holder.
+pub struct LockGuard<P: LockPool = deadpool_redis::Pool> {
+    pool: P,
+    key: String,
+    id: String,
+    fencing_token: u64,
+    lost: Arc<AtomicBool>,
+    watchdog: Option<JoinHandle<()>>,
+}
+
+impl<P: LockPool> LockGuard<P> {
+    pub async fn try_acquire(pool: &P, key: &str, ttl: usize) -> Result<Self, Error> {
+        let Some((id, fencing_token)) = try_lock_fenced(pool, key, ttl).await? else {
+            return Err(Error::CanNotGetLock(
+                error::CanNotGetLockReason::LockIsBussy,
+            ));
+        };
+        let lost = Arc::new(AtomicBool::new(false));
+        let watchdog = tokio::spawn(watchdog(
+            pool.clone(),
+            key.to_string(),
+            id.clone(),
+            ttl,
+            lost.clone(),
+        ));
+        Ok(Self {
+            pool: pool.clone(),
+            key: key.to_string(),
+            id,
+            fencing_token,
+            lost,
+            watchdog: Some(watchdog),
+        })
+    }
+
+    pub async fn acquire(
+        pool: &P,
+        key: &str,
+        ttl: usize,
+        retry_count: u32,
+        retry_delay: u32,
+    ) -> Result<Self, Error> {
+        for _ in 0..retry_count {
+            match Self::try_acquire(pool, key, ttl).await {
+                Err(Error::CanNotGetLock(_)) => {
+                    sleep(Duration::from_millis(u64::from(retry_delay))).await;
+                }
+                result => return result,
+            }
+        }
+        Err(Error::CanNotGetLock(
+            error::CanNotGetLockReason::LockIsStillBusy {
+                retry_count,
+                retry_delay,
+            },
+        ))
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn fencing_token(&self) -> u64 {
+        self.fencing_token
+    }
+
+    /// The lock expired or was taken over while it was held.
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::SeqCst)
+    }
+
+    pub async fn release(mut self) -> Result<i64, Error> {
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.abort();
+        }
+        let mut con = self.pool.connection().await?;
+        unlock(&mut con, &self.key, &self.id).await
+    }
+}
+
+impl<P: LockPool> Drop for LockGuard<P> {
+    fn drop(&mut self) {
+        let Some(watchdog) = self.watchdog.take() else {
+            return;
+        };
+        watchdog.abort();
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            log::warn!("lock '{}' is not released, no runtime available", self.key);
+            return;
+        };
+        let pool = self.pool.clone();
+        let key = std::mem::take(&mut self.key);
+        let id = std::mem::take(&mut self.id);
+        handle.spawn(async move {
+            let result = match pool.connection().await {
+                Ok(mut con) => unlock(&mut con, &key, &id).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                log::error!("unable to release lock '{key}': {err}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use redis::aio::ConnectionLike;
+    use redis::{Arg, Cmd, Pipeline, RedisFuture, Script, Value};
+    use tokio::time::{sleep, Duration};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct State {
+        values: HashMap<String, String>,
+        counters: HashMap<String, i64>,
+        commands: Vec<Vec<String>>,
+    }
+
+    /// Redis which evaluates the lock scripts on an in-memory map, clones share the state.
+    #[derive(Clone, Default)]
+    struct FakeRedis(Arc<Mutex<State>>);
+
+    impl FakeRedis {
+        fn state(&self) -> std::sync::MutexGuard<'_, State> {
+            self.0.lock().unwrap()
+        }
+
+        fn evalsha_count(&self, script: &str, key: &str) -> usize {
+            let hash = Script::new(script).get_hash().to_string();
+            self.state()
+                .commands
+                .iter()
+                .filter(|cmd| cmd[0] == "EVALSHA" && cmd[1] == hash && cmd[3] == key)
+                .count()
+        }
+
+        fn execute(&self, args: Vec<String>) -> Value {
+            let mut state = self.state();
+            state.commands.push(args.clone());
+            let int = |v: bool| Value::Int(i64::from(v));
+            match args[0].as_str() {
+                "GET" => state
+                    .values
+                    .get(&args[1])
+                    .cloned()
+                    .or_else(|| state.counters.get(&args[1]).map(i64::to_string))
+                    .map_or(Value::Nil, |v| Value::Data(v.into_bytes())),
+                "EVALSHA" => {
+                    // EVALSHA <hash> <numkeys> <key> ..., the lock id follows the keys.
+                    let hash = args[1].as_str();
+                    let key = args[3].clone();
+                    if hash == Script::new(FENCED_LOCK_SCRIPT).get_hash() {
+                        if state.values.contains_key(&key) {
+                            return Value::Nil;
+                        }
+                        state.values.insert(key, args[5].clone());
+                        let token = state.counters.entry(args[4].clone()).or_default();
+                        *token += 1;
+                        return Value::Int(*token);
+                    }
+                    let holds = state.values.get(&key) == Some(&args[4]);
+                    if hash == Script::new(EXTEND_SCRIPT).get_hash() {
+                        int(holds)
+                    } else if hash == Script::new(UNLOCK_SCRIPT).get_hash() {
+                        if holds {
+                            state.values.remove(&key);
+                        }
+                        int(holds)
+                    } else {
+                        Value::Nil
+                    }
+                }
+                _ => Value::Nil,
+            }
+        }
+    }
+
+    impl ConnectionLike for FakeRedis {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+            let args = cmd
+                .args_iter()
+                .filter_map(|arg| match arg {
+                    Arg::Simple(arg) => Some(String::from_utf8_lossy(arg).to_string()),
+                    Arg::Cursor => None,
+                })
+                .collect();
+            let value = self.execute(args);
+            Box::pin(async { Ok(value) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _pipeline: &'a Pipeline,
+            _offset: usize,
+            count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            Box::pin(async move { Ok(vec![Value::Nil; count]) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LockPool for FakeRedis {
+        type Connection = FakeRedis;
+
+        async fn connection(&self) -> Result<Self::Connection, Error> {
+            Ok(self.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn fencing_token_test() {
+        let redis = FakeRedis::default();
+        let first = LockGuard::try_acquire(&redis, "test", 10_000)
+            .await
+            .unwrap();
+        assert_eq!(first.fencing_token(), 1);
+        assert_eq!(
+            LockGuard::try_acquire(&redis, "test", 10_000).await.err(),
+            Some(Error::CanNotGetLock(
+                error::CanNotGetLockReason::LockIsBussy
+            ))
+        );
+        assert_eq!(first.release().await.unwrap(), 1);
+        let second = LockGuard::try_acquire(&redis, "test", 10_000)
+            .await
+            .unwrap();
+        assert_eq!(second.fencing_token(), 2);
+        assert_eq!(fencing_token(&mut redis.clone(), "test").await.unwrap(), 2);
+        second.release().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn extend_failure_sets_lost_test() {
+        let redis = FakeRedis::default();
+        let guard = LockGuard::try_acquire(&redis, "test", 30).await.unwrap();
+        sleep(Duration::from_millis(40)).await;
+        assert!(redis.evalsha_count(EXTEND_SCRIPT, "test") > 0);
+        assert!(!guard.is_lost());
+        // Another holder took over the expired lock.
+        redis
+            .state()
+            .values
+            .insert("test".to_string(), "other".to_string());
+        sleep(Duration::from_millis(40)).await;
+        assert!(guard.is_lost());
+        let extends = redis.evalsha_count(EXTEND_SCRIPT, "test");
+        sleep(Duration::from_millis(40)).await;
+        // The watchdog stops after the lock was lost.
+        assert_eq!(redis.evalsha_count(EXTEND_SCRIPT, "test"), extends);
+        // Releasing a lost lock keeps the lock of the new holder.
+        assert_eq!(guard.release().await.unwrap(), 0);
+        assert_eq!(
+            redis.state().values.get("test").map(String::as_str),
+            Some("other")
+        );
+    }
+
+    #[tokio::test]
+    async fn drop_unlocks_test() {
+        let redis = FakeRedis::default();
+        let guard = LockGuard::try_acquire(&redis, "test", 10_000)
+            .await
+            .unwrap();
+        let id = guard.id().to_string();
+        drop(guard);
+        sleep(Duration::from_millis(10)).await;
+        let hash = Script::new(UNLOCK_SCRIPT).get_hash().to_string();
+        assert!(redis.state().commands.contains(&vec![
+            "EVALSHA".to_string(),
+            hash,
+            "1".to_string(),
+            "test".to_string(),
+            id
+        ]));
+        assert!(!redis.state().values.contains_key("test"));
+    }
+}
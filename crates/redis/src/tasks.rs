@@ -0,0 +1,237 @@
+use futures::FutureExt;
+use serde::de::DeserializeOwned;
+use std::future::{poll_fn, Future};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinSet;
+
+use crate::connection::Connections;
+use crate::{run_promoter_worker, run_recovery_worker, run_worker_queue, AsyncWorker, Redis};
+
+/// Result of a finished worker task.
+pub struct TaskExit {
+    pub name: String,
+    pub result: anyhow::Result<()>,
+}
+
+struct Inner {
+    redis: Redis,
+    is_running: Arc<AtomicBool>,
+    tasks: Mutex<JoinSet<TaskExit>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Dropping the `JoinSet` aborts the remaining tasks.
+        self.is_running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Runs workers as tasks on the current Tokio runtime.
+///
+/// In contrast to [`crate::Workers`] no threads are spawned, the tasks share the connection
+/// pool of [`Redis`] and errors are reported by [`WorkerTasks::join_next`] and
+/// [`WorkerTasks::terminate`] instead of exiting the process. The tasks are aborted when the
+/// last clone of [`WorkerTasks`] is dropped.
+///
+/// A waiting worker holds a connection of the pool for up to the timeout of its queue, so the
+/// pool needs more connections than the number of workers.
+#[derive(Clone)]
+pub struct WorkerTasks {
+    inner: Arc<Inner>,
+}
+
+impl WorkerTasks {
+    pub fn new(redis: &Redis) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                redis: redis.clone(),
+                is_running: Arc::new(AtomicBool::new(true)),
+                tasks: Mutex::new(JoinSet::new()),
+            }),
+        }
+    }
+
+    fn spawn<F>(&self, name: String, fut: F)
+    where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let mut tasks = self.inner.tasks.lock().unwrap();
+        tasks.spawn(async move {
+            let result = AssertUnwindSafe(fut)
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("task panicked")));
+            TaskExit { name, result }
+        });
+    }
+
+    pub async fn start<Ctx, T>(&self, ctx: Ctx, worker: AsyncWorker<Ctx, T>) -> anyhow::Result<()>
+    where
+        Ctx: Clone + Send + Sync + 'static,
+        T: DeserializeOwned + Send + Sync + 'static,
+    {
+        if !self.inner.is_running.load(Ordering::SeqCst) {
+            anyhow::bail!("Worker tasks already terminated");
+        }
+        let worker = Arc::new(worker);
+        let client = Arc::new(self.inner.redis.client().clone());
+        let connections = Connections::Pool(self.inner.redis.pool());
+        {
+            let mut con = self.inner.redis.connect().await?;
+            worker.recover(&mut con).await?;
+        }
+        {
+            let is_running = self.inner.is_running.clone();
+            let worker = worker.clone();
+            let name = format!("{} recovery", worker.prefix);
+            self.spawn(
+                name,
+                run_recovery_worker(connections.clone(), is_running, worker),
+            );
+        }
+        {
+            let is_running = self.inner.is_running.clone();
+            let worker = worker.clone();
+            let name = format!("{} promoter", worker.prefix);
+            self.spawn(
+                name,
+                run_promoter_worker(connections.clone(), is_running, worker),
+            );
+        }
+        for worker_id in 0..worker.num_workers {
+            let client = client.clone();
+            let connections = connections.clone();
+            let is_running = self.inner.is_running.clone();
+            let worker = worker.clone();
+            let ctx = ctx.clone();
+            let name = format!("{} worker #{worker_id}", worker.prefix);
+            self.spawn(
+                name,
+                run_worker_queue(ctx, client, connections, is_running, worker, worker_id),
+            );
+        }
+        Ok(())
+    }
+
+    /// Waits for the next task to finish, returns `None` if no tasks are running.
+    pub async fn join_next(&self) -> Option<TaskExit> {
+        let result = poll_fn(|cx| self.inner.tasks.lock().unwrap().poll_join_next(cx)).await?;
+        // Panics are caught in the task, so only aborted tasks end up here.
+        Some(result.unwrap_or_else(|err| TaskExit {
+            name: "aborted task".to_string(),
+            result: Err(err.into()),
+        }))
+    }
+
+    /// Stops all tasks and waits until they are finished, returns the first error of a task.
+    pub async fn terminate(&self) -> anyhow::Result<()> {
+        if !self.inner.is_running.swap(false, Ordering::SeqCst) {
+            anyhow::bail!("Worker tasks already terminated");
+        }
+        let mut first_error = None;
+        while let Some(exit) = self.join_next().await {
+            match exit.result {
+                Ok(()) => log::info!("stopped {}", exit.name),
+                Err(err) => {
+                    log::error!("{} failed: {err:#?}", exit.name);
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use super::WorkerTasks;
+    use crate::Redis;
+
+    fn tasks() -> WorkerTasks {
+        WorkerTasks::new(&Redis::new().unwrap())
+    }
+
+    /// Task running until the worker tasks are terminated.
+    fn run_until_terminated(tasks: &WorkerTasks, name: &str) {
+        let is_running = tasks.inner.is_running.clone();
+        tasks.spawn(name.to_string(), async move {
+            while is_running.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            Ok(())
+        });
+    }
+
+    #[tokio::test]
+    async fn join_next_test() {
+        let tasks = tasks();
+        assert!(tasks.join_next().await.is_none());
+        tasks.spawn("ok".to_string(), async { Ok(()) });
+        tasks.spawn("failed".to_string(), async { anyhow::bail!("failed") });
+        tasks.spawn("panicked".to_string(), async { panic!("panicked") });
+        let mut exits = vec![];
+        while let Some(exit) = tasks.join_next().await {
+            exits.push((exit.name, exit.result.is_ok()));
+        }
+        exits.sort();
+        assert_eq!(
+            exits,
+            vec![
+                ("failed".to_string(), false),
+                ("ok".to_string(), true),
+                ("panicked".to_string(), false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn join_next_waits_for_spawned_task_test() {
+        let tasks = tasks();
+        run_until_terminated(&tasks, "worker");
+        let join = tokio::spawn({
+            let tasks = tasks.clone();
+            async move { tasks.join_next().await.map(|exit| exit.name) }
+        });
+        // The task spawned while `join_next` waits is joined as well.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        tasks.spawn("late".to_string(), async { Ok(()) });
+        assert_eq!(join.await.unwrap().as_deref(), Some("late"));
+    }
+
+    #[tokio::test]
+    async fn terminate_test() {
+        let tasks = tasks();
+        run_until_terminated(&tasks, "a");
+        run_until_terminated(&tasks, "b");
+        tasks.terminate().await.unwrap();
+        assert!(tasks.join_next().await.is_none());
+        assert!(tasks.terminate().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn terminate_reports_error_test() {
+        let tasks = tasks();
+        run_until_terminated(&tasks, "a");
+        tasks.spawn("failed".to_string(), async { anyhow::bail!("failed") });
+        assert!(tasks.terminate().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn drop_aborts_tasks_test() {
+        let tasks = tasks();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        tasks.spawn("worker".to_string(), async move {
+            let _tx = tx;
+            std::future::pending::<()>().await;
+            Ok(())
+        });
+        drop(tasks);
+        // The sender is dropped together with the aborted task.
+        assert!(rx.await.is_err());
+    }
+}
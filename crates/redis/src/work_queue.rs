@@ -1,5 +1,5 @@
 use std::future::Future;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use deadpool_redis::redis::{self, AsyncCommands, RedisResult, Value};
 use serde::{Deserialize, Serialize};
@@ -87,12 +87,67 @@ impl Item {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct DeadItem {
+    pub item: Item,
+    pub attempts: u32,
+}
+
+/// Moves an item from the list `KEYS[1]` to the sorted set `KEYS[2]` with score `ARGV[2]`
+/// and removes the lease `KEYS[3]`.
+const RETRY_LATER_SCRIPT: &str = r#"
+  if redis.call("lrem", KEYS[1], 0, ARGV[1]) == 0 then
+    return 0
+  end
+  redis.call("zadd", KEYS[2], ARGV[2], ARGV[1])
+  redis.call("del", KEYS[3])
+  return 1
+"#;
+
+/// Moves an item from the list `KEYS[1]` to the list `KEYS[2]` and removes the key `KEYS[3]`.
+const MOVE_SCRIPT: &str = r#"
+  if redis.call("lrem", KEYS[1], 0, ARGV[1]) == 0 then
+    return 0
+  end
+  redis.call("lpush", KEYS[2], ARGV[1])
+  redis.call("del", KEYS[3])
+  return 1
+"#;
+
+/// Moves all items of the sorted set `KEYS[1]` with a score up to `ARGV[1]` to the list `KEYS[2]`.
+const PROMOTE_DUE_SCRIPT: &str = r#"
+  local due = redis.call("zrangebyscore", KEYS[1], "-inf", ARGV[1])
+  for _, id in ipairs(due) do
+    redis.call("zrem", KEYS[1], id)
+    redis.call("lpush", KEYS[2], id)
+  end
+  return #due
+"#;
+
+/// Increments the counter `KEYS[2]` if the item data `KEYS[1]` exists, it is removed on completion.
+const RECORD_ATTEMPT_SCRIPT: &str = r#"
+  if redis.call("exists", KEYS[1]) == 0 then
+    return nil
+  end
+  return redis.call("incr", KEYS[2])
+"#;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 pub struct WorkQueue {
     session: String,
     main_queue_key: String,
     processing_key: String,
+    delayed_key: String,
+    dead_key: String,
     lease_key: KeyPrefix,
     item_data_key: KeyPrefix,
+    attempts_key: KeyPrefix,
 }
 
 impl WorkQueue {
@@ -101,8 +156,11 @@ impl WorkQueue {
             session: Uuid::new_v4().to_string(),
             main_queue_key: name.of(":queue"),
             processing_key: name.of(":processing"),
+            delayed_key: name.of(":delayed"),
+            dead_key: name.of(":dead"),
             lease_key: name.and(":leased_by_session:"),
             item_data_key: name.and(":item:"),
+            attempts_key: name.and(":attempts:"),
         }
     }
 
@@ -136,6 +194,41 @@ impl WorkQueue {
         pipeline.query_async(db).await
     }
 
+    pub fn add_item_at_to_pipeline(
+        &self,
+        pipeline: &mut redis::Pipeline,
+        item: &Item,
+        at: SystemTime,
+    ) {
+        let at = at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        pipeline.set(self.item_data_key.of(&item.id), item.data.as_ref());
+        pipeline.zadd(&self.delayed_key, &item.id, at);
+    }
+
+    /// Adds an item which is moved to the main queue by `promote_due` at the given time.
+    pub async fn add_item_at<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        item: &Item,
+        at: SystemTime,
+    ) -> RedisResult<()> {
+        let mut pipeline = Box::new(redis::pipe());
+        self.add_item_at_to_pipeline(&mut pipeline, item, at);
+        pipeline.query_async(db).await
+    }
+
+    pub async fn add_item_delayed<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        item: &Item,
+        delay: Duration,
+    ) -> RedisResult<()> {
+        self.add_item_at(db, item, SystemTime::now() + delay).await
+    }
+
     pub fn queue_len<'a, C: AsyncCommands>(
         &'a self,
         db: &'a mut C,
@@ -150,6 +243,13 @@ impl WorkQueue {
         db.llen(&self.processing_key)
     }
 
+    pub fn delayed<'a, C: AsyncCommands>(
+        &'a self,
+        db: &'a mut C,
+    ) -> impl Future<Output = RedisResult<usize>> + 'a {
+        db.zcard(&self.delayed_key)
+    }
+
     pub async fn lease<C: AsyncCommands>(
         &self,
         db: &mut C,
@@ -189,7 +289,7 @@ impl WorkQueue {
             None => return Ok(None),
         };
 
-        db.set_ex(
+        db.set_ex::<_, _, ()>(
             self.lease_key.of(&item.id),
             &self.session,
             lease_duration.as_secs(),
@@ -207,8 +307,296 @@ impl WorkQueue {
         redis::pipe()
             .del(self.item_data_key.of(&item.id))
             .del(self.lease_key.of(&item.id))
-            .query_async(db)
+            .del(self.attempts_key.of(&item.id))
+            .query_async::<_, ()>(db)
             .await?;
         Ok(true)
     }
+
+    /// Increments the attempt counter of a leased item and returns the number of failed attempts.
+    ///
+    /// Returns `None` without a counter if the item was completed, e.g. by the failed work itself.
+    pub async fn record_attempt<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        item: &Item,
+    ) -> RedisResult<Option<u32>> {
+        redis::Script::new(RECORD_ATTEMPT_SCRIPT)
+            .key(self.item_data_key.of(&item.id))
+            .key(self.attempts_key.of(&item.id))
+            .invoke_async(db)
+            .await
+    }
+
+    pub async fn attempts<C: AsyncCommands>(&self, db: &mut C, item_id: &str) -> RedisResult<u32> {
+        Ok(db
+            .get::<_, Option<u32>>(self.attempts_key.of(item_id))
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Moves a leased item to the delayed set, it is requeued by `promote_due` after `delay`.
+    pub async fn retry_later<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        item: &Item,
+        delay: Duration,
+    ) -> RedisResult<bool> {
+        let moved: i64 = redis::Script::new(RETRY_LATER_SCRIPT)
+            .key(&self.processing_key)
+            .key(&self.delayed_key)
+            .key(self.lease_key.of(&item.id))
+            .arg(&item.id)
+            .arg(now_millis() + delay.as_millis() as u64)
+            .invoke_async(db)
+            .await?;
+        Ok(moved == 1)
+    }
+
+    /// Moves all delayed items which are due to the main queue, returns the number of moved items.
+    pub async fn promote_due<C: AsyncCommands>(&self, db: &mut C) -> RedisResult<usize> {
+        redis::Script::new(PROMOTE_DUE_SCRIPT)
+            .key(&self.delayed_key)
+            .key(&self.main_queue_key)
+            .arg(now_millis())
+            .invoke_async(db)
+            .await
+    }
+
+    /// Moves a leased item to the dead letter list, the item data is kept for inspection.
+    pub async fn bury<C: AsyncCommands>(&self, db: &mut C, item: &Item) -> RedisResult<bool> {
+        let moved: i64 = redis::Script::new(MOVE_SCRIPT)
+            .key(&self.processing_key)
+            .key(&self.dead_key)
+            .key(self.lease_key.of(&item.id))
+            .arg(&item.id)
+            .invoke_async(db)
+            .await?;
+        Ok(moved == 1)
+    }
+
+    pub fn dead_len<'a, C: AsyncCommands>(
+        &'a self,
+        db: &'a mut C,
+    ) -> impl Future<Output = RedisResult<usize>> + 'a {
+        db.llen(&self.dead_key)
+    }
+
+    pub async fn dead_items<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        offset: isize,
+        count: isize,
+    ) -> RedisResult<Vec<DeadItem>> {
+        if count <= 0 {
+            return Ok(vec![]);
+        }
+        let ids: Vec<String> = db
+            .lrange(&self.dead_key, offset, offset + count - 1)
+            .await?;
+        let mut result = Vec::with_capacity(ids.len());
+        for id in ids {
+            let data: Option<Vec<u8>> = db.get(self.item_data_key.of(&id)).await?;
+            let attempts = self.attempts(db, &id).await?;
+            if let Some(data) = data {
+                result.push(DeadItem {
+                    item: Item {
+                        id,
+                        data: data.into_boxed_slice(),
+                    },
+                    attempts,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    /// Moves a dead item back to the main queue and resets its attempt counter.
+    pub async fn requeue_dead<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        item_id: &str,
+    ) -> RedisResult<bool> {
+        let moved: i64 = redis::Script::new(MOVE_SCRIPT)
+            .key(&self.dead_key)
+            .key(&self.main_queue_key)
+            .key(self.attempts_key.of(item_id))
+            .arg(item_id)
+            .invoke_async(db)
+            .await?;
+        Ok(moved == 1)
+    }
+
+    pub async fn requeue_all_dead<C: AsyncCommands>(&self, db: &mut C) -> RedisResult<usize> {
+        let ids: Vec<String> = db.lrange(&self.dead_key, 0, -1).await?;
+        let mut count = 0;
+        for id in ids {
+            if self.requeue_dead(db, &id).await? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Removes a dead item including its data.
+    pub async fn purge_dead<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        item_id: &str,
+    ) -> RedisResult<bool> {
+        let removed: usize = db.lrem(&self.dead_key, 0, item_id).await?;
+        if removed == 0 {
+            return Ok(false);
+        }
+        redis::pipe()
+            .del(self.item_data_key.of(item_id))
+            .del(self.attempts_key.of(item_id))
+            .query_async::<_, ()>(db)
+            .await?;
+        Ok(true)
+    }
+
+    pub async fn purge_all_dead<C: AsyncCommands>(&self, db: &mut C) -> RedisResult<usize> {
+        let ids: Vec<String> = db.lrange(&self.dead_key, 0, -1).await?;
+        let mut count = 0;
+        for id in ids {
+            if self.purge_dead(db, &id).await? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use deadpool_redis::redis::{aio::ConnectionLike, Arg, Cmd, Pipeline, RedisFuture, Value};
+
+    use super::{Item, WorkQueue};
+
+    /// Connection recording the sent commands, every command returns nil.
+    #[derive(Default)]
+    struct Recorder {
+        commands: Vec<Vec<String>>,
+    }
+
+    impl Recorder {
+        fn record(&mut self, cmd: &Cmd) {
+            self.commands.push(
+                cmd.args_iter()
+                    .filter_map(|arg| match arg {
+                        Arg::Simple(arg) => Some(String::from_utf8_lossy(arg).to_string()),
+                        Arg::Cursor => None,
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    impl ConnectionLike for Recorder {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+            self.record(cmd);
+            Box::pin(async { Ok(Value::Nil) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            pipeline: &'a Pipeline,
+            _offset: usize,
+            count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            for cmd in pipeline.cmd_iter() {
+                self.record(cmd);
+            }
+            Box::pin(async move { Ok(vec![Value::Nil; count]) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    fn millis(at: SystemTime) -> u64 {
+        at.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+    }
+
+    /// Returns the score of the item in the `ZADD` command to the delayed set.
+    fn delayed_score(con: &Recorder, item: &Item) -> u64 {
+        let zadd = con
+            .commands
+            .iter()
+            .find(|cmd| cmd[0] == "ZADD")
+            .expect("ZADD command");
+        assert_eq!(zadd[1], "test:delayed");
+        assert_eq!(zadd[3], item.id);
+        zadd[2].parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn add_item_at_test() {
+        let queue = WorkQueue::new("test".into());
+        let mut con = Recorder::default();
+        let item = Item::from_string_data("data".to_string());
+        let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        queue.add_item_at(&mut con, &item, at).await.unwrap();
+        assert_eq!(
+            con.commands[0],
+            vec![
+                "SET".to_string(),
+                format!("test:item:{}", item.id),
+                "data".to_string()
+            ]
+        );
+        assert_eq!(delayed_score(&con, &item), millis(at));
+        // Scheduled items are not pushed to the main queue.
+        assert!(!con.commands.iter().any(|cmd| cmd[0] == "LPUSH"));
+    }
+
+    #[tokio::test]
+    async fn add_item_delayed_test() {
+        let queue = WorkQueue::new("test".into());
+        let mut con = Recorder::default();
+        let item = Item::from_string_data("data".to_string());
+        let delay = Duration::from_secs(60);
+        let before = millis(SystemTime::now() + delay);
+        queue
+            .add_item_delayed(&mut con, &item, delay)
+            .await
+            .unwrap();
+        let after = millis(SystemTime::now() + delay);
+        let score = delayed_score(&con, &item);
+        assert!(before <= score && score <= after);
+    }
+
+    #[tokio::test]
+    async fn dead_items_without_count_test() {
+        let queue = WorkQueue::new("test".into());
+        let mut con = Recorder::default();
+        assert!(queue.dead_items(&mut con, 0, 0).await.unwrap().is_empty());
+        assert!(queue.dead_items(&mut con, 5, -1).await.unwrap().is_empty());
+        assert!(con.commands.is_empty());
+        queue.dead_items(&mut con, 5, 2).await.unwrap();
+        assert_eq!(con.commands, vec![vec!["LRANGE", "test:dead", "5", "6"]]);
+    }
+
+    #[tokio::test]
+    async fn record_attempt_test() {
+        let queue = WorkQueue::new("test".into());
+        let mut con = Recorder::default();
+        let item = Item::from_string_data("data".to_string());
+        // The recorder has no item data, like after a completion, so no attempt is counted.
+        assert_eq!(queue.record_attempt(&mut con, &item).await.unwrap(), None);
+        let evalsha = &con.commands[0];
+        assert_eq!(evalsha[0], "EVALSHA");
+        assert_eq!(
+            evalsha[2..],
+            [
+                "2".to_string(),
+                format!("test:item:{}", item.id),
+                format!("test:attempts:{}", item.id)
+            ]
+        );
+    }
 }
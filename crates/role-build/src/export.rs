@@ -0,0 +1,355 @@
+use std::rc::Rc;
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::parser::ParseResult;
+
+/// Additional outputs which can be generated from the role definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Access levels, groups, roles, resources and permissions as JSON.
+    Json,
+    /// Access levels, groups, roles, resources and permissions as YAML.
+    Yaml,
+    /// Realm roles and groups as fragment of a Keycloak realm import.
+    KeycloakRealm,
+    /// Permission matrix of roles and groups as markdown tables.
+    MatrixMarkdown,
+    /// Permission matrix of roles and groups as HTML document.
+    MatrixHtml,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Yaml => "yaml",
+            ExportFormat::KeycloakRealm => "realm.json",
+            ExportFormat::MatrixMarkdown => "matrix.md",
+            ExportFormat::MatrixHtml => "matrix.html",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AccessLevelExport {
+    name: String,
+    display_name: Rc<str>,
+    description: Rc<str>,
+}
+
+#[derive(Serialize)]
+struct GroupExport {
+    name: Rc<str>,
+    path: String,
+    display_name: Rc<str>,
+    description: Rc<str>,
+    extends: Rc<[Rc<str>]>,
+    access_levels: Vec<String>,
+    roles: Rc<[Rc<str>]>,
+}
+
+#[derive(Serialize)]
+struct RoleExport {
+    name: Rc<str>,
+    description: Option<Rc<str>>,
+    resource: Rc<str>,
+    permission: Option<Rc<str>>,
+}
+
+#[derive(Serialize)]
+struct Export {
+    access_levels: Vec<AccessLevelExport>,
+    groups: Vec<GroupExport>,
+    roles: Vec<RoleExport>,
+    resources: Rc<[Rc<str>]>,
+    permissions: Rc<[Rc<str>]>,
+}
+
+/// Serialized name of a `qm_role::AccessLevel`, e.g. `customer_unit`.
+fn access_level_value(s: &str) -> String {
+    inflector::cases::snakecase::to_snake_case(s.trim())
+}
+
+fn group_path(path: &str) -> String {
+    format!("/app{path}")
+}
+
+impl From<&ParseResult> for Export {
+    fn from(value: &ParseResult) -> Self {
+        Self {
+            access_levels: value
+                .access_level_definitions()
+                .into_iter()
+                .map(|v| AccessLevelExport {
+                    name: access_level_value(&v.access_level),
+                    display_name: v.display_name,
+                    description: v.description,
+                })
+                .collect(),
+            groups: value
+                .user_group_name_mappings
+                .iter()
+                .map(|v| GroupExport {
+                    name: v.user_group.clone(),
+                    path: group_path(&v.path),
+                    display_name: v.display_name.clone(),
+                    description: v.description.clone(),
+                    extends: v.extends.clone(),
+                    access_levels: v.access_levels().map(access_level_value).collect(),
+                    roles: Rc::from(value.group_roles(&v.user_group)),
+                })
+                .collect(),
+            roles: value
+                .roles
+                .iter()
+                .map(|role| {
+                    let (resource, permission) = role
+                        .split_once(':')
+                        .map(|(r, p)| (Rc::from(r), Some(Rc::from(p))))
+                        .unwrap_or_else(|| (role.clone(), None));
+                    RoleExport {
+                        name: role.clone(),
+                        description: value.role_descriptions.get(role).cloned(),
+                        resource,
+                        permission,
+                    }
+                })
+                .collect(),
+            resources: value.resources.clone(),
+            permissions: value.permissions.clone(),
+        }
+    }
+}
+
+/// Realm roles and the group tree below `/app` in the format of the Keycloak realm import.
+fn keycloak_realm(value: &ParseResult) -> serde_json::Value {
+    let roles: Vec<serde_json::Value> = value
+        .roles
+        .iter()
+        .map(|role| match value.role_descriptions.get(role) {
+            Some(description) => json!({ "name": role, "description": description }),
+            None => json!({ "name": role }),
+        })
+        .collect();
+    let groups: Vec<serde_json::Value> = value
+        .user_group_name_mappings
+        .iter()
+        .map(|v| {
+            let path = group_path(&v.path);
+            let name = path.rsplit('/').next().unwrap_or_default().to_string();
+            let access_levels = v
+                .access_levels()
+                .map(access_level_value)
+                .collect::<Vec<String>>()
+                .join(",");
+            json!({
+                "name": name,
+                "path": path,
+                "attributes": {
+                    "built_in": ["1"],
+                    "allowed_access_levels": [access_levels],
+                    "display_name": [v.display_name],
+                },
+                "realmRoles": value.group_roles(&v.user_group),
+                "subGroups": [],
+            })
+        })
+        .collect();
+    json!({
+        "roles": { "realm": roles },
+        "groups": [{
+            "name": "app",
+            "path": "/app",
+            "subGroups": groups,
+        }],
+    })
+}
+
+/// Rows of the permission matrix, one per role with a column per group.
+fn matrix(value: &ParseResult) -> (Vec<String>, Vec<Vec<String>>) {
+    let with_description = !value.role_descriptions.is_empty();
+    let mut headers = vec!["Role".to_string()];
+    if with_description {
+        headers.push("Description".to_string());
+    }
+    headers.extend(
+        value
+            .user_group_name_mappings
+            .iter()
+            .map(|v| v.user_group.to_string()),
+    );
+    let rows = value
+        .roles
+        .iter()
+        .map(|role| {
+            let mut row = vec![role.to_string()];
+            if with_description {
+                row.push(
+                    value
+                        .role_descriptions
+                        .get(role)
+                        .map(|v| v.to_string())
+                        .unwrap_or_default(),
+                );
+            }
+            row.extend(value.user_group_name_mappings.iter().map(|v| {
+                if value.group_roles(&v.user_group).contains(role) {
+                    "x".to_string()
+                } else {
+                    String::new()
+                }
+            }));
+            row
+        })
+        .collect();
+    (headers, rows)
+}
+
+fn access_level_rows(value: &ParseResult) -> (Vec<String>, Vec<Vec<String>>) {
+    let headers = ["Access Level", "Display Name", "Description"]
+        .map(String::from)
+        .to_vec();
+    let rows = value
+        .access_level_definitions()
+        .into_iter()
+        .map(|v| {
+            vec![
+                v.access_level.to_string(),
+                v.display_name.to_string(),
+                v.description.to_string(),
+            ]
+        })
+        .collect();
+    (headers, rows)
+}
+
+fn group_rows(value: &ParseResult) -> (Vec<String>, Vec<Vec<String>>) {
+    let headers = [
+        "Group",
+        "Path",
+        "Display Name",
+        "Access Levels",
+        "Extends",
+        "Description",
+    ]
+    .map(String::from)
+    .to_vec();
+    let rows = value
+        .user_group_name_mappings
+        .iter()
+        .map(|v| {
+            vec![
+                v.user_group.to_string(),
+                group_path(&v.path),
+                v.display_name.to_string(),
+                v.access_levels().collect::<Vec<&str>>().join(", "),
+                v.extends
+                    .iter()
+                    .map(|v| v.as_ref())
+                    .collect::<Vec<&str>>()
+                    .join(", "),
+                v.description.to_string(),
+            ]
+        })
+        .collect();
+    (headers, rows)
+}
+
+/// Markdown table with padded columns, so that changes are easy to spot in a diff.
+fn markdown_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(idx, header)| {
+            rows.iter()
+                .filter_map(|row| row.get(idx))
+                .map(|v| v.chars().count())
+                .chain([header.chars().count(), 3])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |columns: &mut dyn Iterator<Item = String>| {
+        let columns: Vec<String> = columns
+            .zip(widths.iter())
+            .map(|(v, width)| format!(" {v:<width$} "))
+            .collect();
+        format!("|{}|\n", columns.join("|"))
+    };
+    let mut result = line(&mut headers.iter().cloned());
+    result += &line(&mut widths.iter().map(|width| "-".repeat(*width)));
+    for row in rows {
+        result += &line(&mut row.iter().map(|v| v.replace('|', "\\|")));
+    }
+    result
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let mut result = "<table>\n  <thead>\n    <tr>".to_string();
+    for header in headers {
+        result += &format!("<th>{}</th>", escape_html(header));
+    }
+    result += "</tr>\n  </thead>\n  <tbody>\n";
+    for row in rows {
+        result += "    <tr>";
+        for column in row {
+            result += &format!("<td>{}</td>", escape_html(column));
+        }
+        result += "</tr>\n";
+    }
+    result += "  </tbody>\n</table>\n";
+    result
+}
+
+fn matrix_markdown(value: &ParseResult) -> String {
+    let (access_level_headers, access_level_rows) = access_level_rows(value);
+    let (group_headers, group_rows) = group_rows(value);
+    let (headers, rows) = matrix(value);
+    format!(
+        "# Permission Matrix\n\n## Access Levels\n\n{}\n## Groups\n\n{}\n## Roles\n\n{}",
+        markdown_table(&access_level_headers, &access_level_rows),
+        markdown_table(&group_headers, &group_rows),
+        markdown_table(&headers, &rows),
+    )
+}
+
+fn matrix_html(value: &ParseResult) -> String {
+    let (access_level_headers, access_level_rows) = access_level_rows(value);
+    let (group_headers, group_rows) = group_rows(value);
+    let (headers, rows) = matrix(value);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Permission Matrix</title>\n</head>\n<body>\n<h1>Permission Matrix</h1>\n<h2>Access Levels</h2>\n{}<h2>Groups</h2>\n{}<h2>Roles</h2>\n{}</body>\n</html>\n",
+        html_table(&access_level_headers, &access_level_rows),
+        html_table(&group_headers, &group_rows),
+        html_table(&headers, &rows),
+    )
+}
+
+pub fn export<W>(mut w: W, parse_result: &ParseResult, format: ExportFormat) -> anyhow::Result<()>
+where
+    W: std::io::Write,
+{
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut w, &Export::from(parse_result))?;
+            writeln!(w)?;
+        }
+        ExportFormat::Yaml => serde_yaml::to_writer(&mut w, &Export::from(parse_result))?,
+        ExportFormat::KeycloakRealm => {
+            serde_json::to_writer_pretty(&mut w, &keycloak_realm(parse_result))?;
+            writeln!(w)?;
+        }
+        ExportFormat::MatrixMarkdown => w.write_all(matrix_markdown(parse_result).as_bytes())?,
+        ExportFormat::MatrixHtml => w.write_all(matrix_html(parse_result).as_bytes())?,
+    }
+    Ok(())
+}
@@ -1,5 +1,8 @@
 use std::path::{Path, PathBuf};
 
+pub use export::ExportFormat;
+
+mod export;
 mod model;
 mod parser;
 mod reader;
@@ -33,11 +36,65 @@ pub fn generate_to_writer<W: std::io::Write>(
     Ok(())
 }
 
+/// Writes `format` next to the generated code into `OUT_DIR`, e.g. `roles.matrix.md` for `roles.md`.
+pub fn export(input_file_path: &Path, format: ExportFormat) -> anyhow::Result<PathBuf> {
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    export_to_dir(input_file_path, format, &out_dir)
+}
+
+/// Writes `format` into `out_dir`, e.g. a directory committed with the crate so frontends,
+/// the Keycloak import and reviews of the role matrix can use the exports.
+///
+/// Build scripts should only write into `OUT_DIR`, see [export], update committed exports with
+/// an explicit step and compare them with [export_to_writer] in a test.
+///
+/// The file is only rewritten if its content changed.
+pub fn export_to_dir(
+    input_file_path: &Path,
+    format: ExportFormat,
+    out_dir: &Path,
+) -> anyhow::Result<PathBuf> {
+    let out_file_path = export_path(input_file_path, format, out_dir)?;
+    let mut content = vec![];
+    export_to_writer(input_file_path, format, &mut content)?;
+    std::fs::create_dir_all(out_dir)?;
+    if std::fs::read(&out_file_path).ok().as_ref() != Some(&content) {
+        std::fs::write(&out_file_path, content)?;
+    }
+    Ok(out_file_path)
+}
+
+fn export_path(
+    input_file_path: &Path,
+    format: ExportFormat,
+    out_dir: &Path,
+) -> anyhow::Result<PathBuf> {
+    let out = input_file_path.with_extension(format.extension());
+    let file_name = out
+        .file_name()
+        .ok_or(anyhow::anyhow!("invalid input filename"))?;
+    Ok(out_dir.join(file_name))
+}
+
+pub fn export_to_writer<W: std::io::Write>(
+    input_file_path: &Path,
+    format: ExportFormat,
+    writer: W,
+) -> anyhow::Result<()> {
+    let tables = reader::Reader::from_file(input_file_path)?.read()?;
+    let parse_result = crate::parser::parse(tables)?;
+
+    export::export(writer, &parse_result, format)?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
         model::{RoleMapping, Table},
         reader::Reader,
+        ExportFormat,
     };
     use std::rc::Rc;
 
@@ -101,7 +158,7 @@ mod test {
                         "Reader".to_string(),
                         "/employee_reader".to_string(),
                         "Reader".to_string(),
-                        "Customer, Institutions".to_string(),
+                        "Customer, Institution".to_string(),
                     ],
                 ],
             }
@@ -235,4 +292,151 @@ mod test {
         eprintln!("{code}");
         Ok(())
     }
+
+    const METADATA_INPUT: &str = r#"# Access Levels `access_levels`
+
+| Name        | Display Name | Description                 |
+| ----------- | ------------ | --------------------------- |
+| Admin       | Admin        | Access to everything        |
+| Customer    | Customer     | Access to a single customer |
+| Institution | Institution  |                             |
+
+# User Groups `user_groups`
+
+| Name             | Path                  | Display Name         | Access Levels | Description          | Extends |
+| ---------------- | --------------------- | -------------------- | ------------- | -------------------- | ------- |
+| Admin            | /administration_owner | Admin                | Admin         | Administrators       |         |
+| InstitutionOwner | /institution_owner    | Owner of Institution | Institution   | Manages institutions | Reader  |
+| Reader           | /employee_reader      | Reader               | Customer      |                      |         |
+
+# Role Mappings `roles`
+
+| Roles          | Description        | Admin | InstitutionOwner | Reader |
+| -------------- | ------------------ | ----- | ---------------- | ------ |
+| administration | Full access        | x     |                  |        |
+| entity:update  | Update entities    |       | x                |        |
+| entity:list    |                    |       |                  | x      |"#;
+
+    #[test]
+    fn test_md_table_metadata_parser() -> anyhow::Result<()> {
+        let result = crate::parser::parse(Reader::from_str(METADATA_INPUT).read()?)?;
+        assert_eq!(result.access_levels.len(), 3);
+        assert_eq!(result.access_levels[1].access_level.as_ref(), "Customer");
+        assert_eq!(
+            result.access_levels[1].description.as_ref(),
+            "Access to a single customer"
+        );
+        let owner = &result.user_group_name_mappings[1];
+        assert_eq!(owner.description.as_ref(), "Manages institutions");
+        assert_eq!(owner.extends.as_ref(), &[Rc::from("Reader")]);
+        assert_eq!(
+            &RoleMapping {
+                user_group: Rc::from("InstitutionOwner"),
+                roles: Rc::from([Rc::from("entity:update"), Rc::from("entity:list")]),
+            },
+            &result.role_mappings[1]
+        );
+        assert_eq!(
+            result
+                .role_descriptions
+                .get("administration")
+                .map(|v| v.as_ref()),
+            Some("Full access")
+        );
+        assert!(!result.role_descriptions.contains_key("entity:list"));
+        let code = crate::writer::Writer::in_memory()
+            .write(result)?
+            .into_inner();
+        assert!(code.contains("pub const ACCESS_LEVELS: [AccessLevelDefinition; 3]"));
+        assert!(code.contains("pub const GROUPS: [GroupDefinition; 3]"));
+        assert!(code.contains("pub const ROLES: [RoleDefinition; 3]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_md_table_validation() -> anyhow::Result<()> {
+        const INVALID_INPUT: &str = r#"# User Groups `user_groups`
+
+| Name   | Path             | Display Name | Access Levels | Extends |
+| ------ | ---------------- | ------------ | ------------- | ------- |
+| Admin  | /administration  | Admin        |               | Owner   |
+| Reader | /employee_reader | Reader       | Customer      | Reader  |
+
+# Role Mappings `roles`
+
+| Roles          | Admin | Reader | Unknown |
+| -------------- | ----- | ------ | ------- |
+| administration | x     |        |         |
+| administration | x     |        |         |"#;
+        let err = crate::parser::parse(Reader::from_str(INVALID_INPUT).read()?)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("user group 'Admin' has no access levels"));
+        assert!(err.contains("user group 'Admin' extends unknown user group 'Owner'"));
+        assert!(err.contains("user group 'Reader' extends itself"));
+        assert!(err.contains("unknown user group 'Unknown' in `roles` table"));
+        assert!(err.contains("duplicate role 'administration' in `roles` table"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_exports() -> anyhow::Result<()> {
+        let result = crate::parser::parse(Reader::from_str(METADATA_INPUT).read()?)?;
+        let export = |format| -> anyhow::Result<String> {
+            let mut w = vec![];
+            crate::export::export(&mut w, &result, format)?;
+            Ok(String::from_utf8(w)?)
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&export(ExportFormat::Json)?)?;
+        assert_eq!(json["access_levels"][1]["name"], "customer");
+        assert_eq!(json["groups"][1]["path"], "/app/institution_owner");
+        assert_eq!(
+            json["groups"][1]["roles"],
+            serde_json::json!(["entity:update", "entity:list"])
+        );
+        assert_eq!(json["roles"][0]["resource"], "administration");
+        assert_eq!(json["roles"][0]["description"], "Full access");
+
+        let yaml: serde_json::Value = serde_yaml::from_str(&export(ExportFormat::Yaml)?)?;
+        assert_eq!(yaml, json);
+
+        let realm: serde_json::Value = serde_json::from_str(&export(ExportFormat::KeycloakRealm)?)?;
+        assert_eq!(realm["roles"]["realm"].as_array().map(Vec::len), Some(3));
+        let group = &realm["groups"][0]["subGroups"][1];
+        assert_eq!(group["name"], "institution_owner");
+        assert_eq!(
+            group["attributes"]["allowed_access_levels"][0],
+            "institution"
+        );
+
+        let markdown = export(ExportFormat::MatrixMarkdown)?;
+        assert!(markdown
+            .contains("| entity:update  | Update entities |       | x                |        |"));
+        let html = export(ExportFormat::MatrixHtml)?;
+        assert!(html.contains("<th>InstitutionOwner</th>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_to_dir() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("qm-role-build-{}", std::process::id()));
+        let out_dir = dir.join("exports");
+        std::fs::create_dir_all(&dir)?;
+        let input = dir.join("roles.md");
+        std::fs::write(&input, METADATA_INPUT)?;
+
+        let path = crate::export_to_dir(&input, ExportFormat::MatrixMarkdown, &out_dir)?;
+        assert_eq!(path, out_dir.join("roles.matrix.md"));
+        assert!(std::fs::read_to_string(&path)?.contains("| entity:update  | Update entities |"));
+        let modified = std::fs::metadata(&path)?.modified()?;
+
+        // Unchanged exports are not rewritten.
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        crate::export_to_dir(&input, ExportFormat::MatrixMarkdown, &out_dir)?;
+        assert_eq!(std::fs::metadata(&path)?.modified()?, modified);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
 }
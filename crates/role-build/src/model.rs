@@ -9,13 +9,24 @@ pub struct Table {
     pub rows: Vec<Row>,
 }
 
+impl Table {
+    /// Index of the column with the header `name`, compared case insensitive.
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+    }
+}
+
 #[derive(Default)]
 pub struct OptMdTables {
+    pub access_levels: Option<Table>,
     pub user_groups: Option<Table>,
     pub roles: Option<Table>,
 }
 
 pub struct MdTables {
+    pub access_levels: Option<Table>,
     pub user_groups: Table,
     pub roles: Table,
 }
@@ -24,6 +35,7 @@ impl TryFrom<OptMdTables> for MdTables {
     type Error = anyhow::Error;
     fn try_from(value: OptMdTables) -> Result<Self, Self::Error> {
         Ok(Self {
+            access_levels: value.access_levels,
             user_groups: value
                 .user_groups
                 .ok_or(anyhow::anyhow!("unable to find `user_groups` table"))?,
@@ -34,12 +46,31 @@ impl TryFrom<OptMdTables> for MdTables {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct AccessLevelMapping {
+    /// Variant of `qm_role::AccessLevel` in class case.
+    pub access_level: Rc<str>,
+    pub display_name: Rc<str>,
+    pub description: Rc<str>,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct UserGroupNameMapping {
     pub user_group: Rc<str>,
     pub path: Rc<str>,
     pub display_name: Rc<str>,
     pub access_level: Rc<str>,
+    pub description: Rc<str>,
+    pub extends: Rc<[Rc<str>]>,
+}
+
+impl UserGroupNameMapping {
+    pub fn access_levels(&self) -> impl Iterator<Item = &str> {
+        self.access_level
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
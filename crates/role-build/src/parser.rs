@@ -1,19 +1,46 @@
-use crate::model::MdTables;
+use crate::model::{AccessLevelMapping, MdTables, Table};
 use crate::model::{RoleMapping, UserGroupNameMapping};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::rc::Rc;
 
+/// Variants of `qm_role::AccessLevel` which can be referenced in the `access_levels` table.
+const KNOWN_ACCESS_LEVELS: [&str; 7] = [
+    "Admin",
+    "Support",
+    "Customer",
+    "Organization",
+    "CustomerUnit",
+    "InstitutionUnit",
+    "Institution",
+];
+
 fn sorted(v: HashSet<Rc<str>>) -> Rc<[Rc<str>]> {
     let mut v: Vec<Rc<str>> = v.into_iter().collect();
     v.sort();
     Rc::from(v)
 }
 
+pub fn access_level_name(s: &str) -> String {
+    inflector::cases::classcase::to_class_case(s.trim())
+}
+
+fn cell(row: &[String], column: Option<usize>) -> Rc<str> {
+    Rc::from(
+        column
+            .and_then(|idx| row.get(idx))
+            .map(|s| s.trim())
+            .unwrap_or_default(),
+    )
+}
+
 #[derive(Debug)]
 pub struct ParseResult {
+    pub access_levels: Vec<AccessLevelMapping>,
     pub user_group_name_mappings: Vec<UserGroupNameMapping>,
     pub role_mappings: Vec<RoleMapping>,
+    pub role_descriptions: BTreeMap<Rc<str>, Rc<str>>,
     pub roles: Rc<[Rc<str>]>,
+    #[allow(dead_code)]
     pub user_groups: Rc<[Rc<str>]>,
     pub permissions: Rc<[Rc<str>]>,
     pub resources: Rc<[Rc<str>]>,
@@ -21,8 +48,10 @@ pub struct ParseResult {
 
 impl ParseResult {
     fn new(
+        access_levels: Vec<AccessLevelMapping>,
         user_group_name_mappings: Vec<UserGroupNameMapping>,
         role_mappings: Vec<RoleMapping>,
+        role_descriptions: BTreeMap<Rc<str>, Rc<str>>,
     ) -> Self {
         let user_groups: HashSet<Rc<str>> = user_group_name_mappings
             .iter()
@@ -46,73 +75,282 @@ impl ParseResult {
             state
         }));
         Self {
+            access_levels,
             user_group_name_mappings,
             role_mappings,
+            role_descriptions,
             roles,
             user_groups: sorted(user_groups),
             permissions,
             resources,
         }
     }
+
+    /// Access levels of the `access_levels` table, or the access levels used by
+    /// the user groups if the table is missing.
+    pub fn access_level_definitions(&self) -> Vec<AccessLevelMapping> {
+        if !self.access_levels.is_empty() {
+            return self
+                .access_levels
+                .iter()
+                .map(|v| AccessLevelMapping {
+                    access_level: v.access_level.clone(),
+                    display_name: if v.display_name.is_empty() {
+                        v.access_level.clone()
+                    } else {
+                        v.display_name.clone()
+                    },
+                    description: v.description.clone(),
+                })
+                .collect();
+        }
+        self.user_group_name_mappings
+            .iter()
+            .flat_map(|v| v.access_levels())
+            .map(access_level_name)
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .map(|v| {
+                let access_level: Rc<str> = Rc::from(v);
+                AccessLevelMapping {
+                    access_level: access_level.clone(),
+                    display_name: access_level,
+                    description: Rc::from(""),
+                }
+            })
+            .collect()
+    }
+
+    /// Roles of `user_group` including the roles of the extended groups.
+    pub fn group_roles(&self, user_group: &str) -> &[Rc<str>] {
+        self.role_mappings
+            .iter()
+            .find(|v| v.user_group.as_ref() == user_group)
+            .map(|v| v.roles.as_ref())
+            .unwrap_or_default()
+    }
 }
 
-pub fn parse(tables: MdTables) -> anyhow::Result<ParseResult> {
-    let user_group_name_mappings: Vec<UserGroupNameMapping> = tables
-        .user_groups
+fn parse_access_levels(table: Table, errors: &mut Vec<String>) -> Vec<AccessLevelMapping> {
+    let display_name = table.column("display name");
+    let description = table.column("description");
+    let mut names = HashSet::new();
+    table
         .rows
-        .into_iter()
-        .filter_map(|mut t| {
-            let access_level = t.pop();
-            let display_name = t.pop();
-            let path = t.pop();
-            let user_group = t.pop();
-            user_group
-                .zip(path.zip(display_name.zip(access_level)))
-                .map(
-                    |(user_group, (path, (display_name, access_level)))| UserGroupNameMapping {
-                        user_group: Rc::from(user_group),
-                        display_name: Rc::from(display_name),
-                        path: Rc::from(path),
-                        access_level: Rc::from(access_level),
-                    },
-                )
+        .iter()
+        .filter(|row| !row.is_empty())
+        .filter_map(|row| {
+            let access_level = access_level_name(&row[0]);
+            if !KNOWN_ACCESS_LEVELS.contains(&access_level.as_str()) {
+                errors.push(format!(
+                    "unknown access level '{}' in `access_levels` table",
+                    row[0]
+                ));
+                return None;
+            }
+            if !names.insert(access_level.clone()) {
+                errors.push(format!("duplicate access level '{}'", row[0]));
+                return None;
+            }
+            Some(AccessLevelMapping {
+                access_level: Rc::from(access_level),
+                display_name: cell(row, display_name),
+                description: cell(row, description),
+            })
+        })
+        .collect()
+}
+
+fn parse_user_groups(table: Table, errors: &mut Vec<String>) -> Vec<UserGroupNameMapping> {
+    let description = table.column("description");
+    let extends = table.column("extends");
+    let mut names = HashSet::new();
+    table
+        .rows
+        .iter()
+        .filter_map(|row| {
+            if row.len() < 4 {
+                return None;
+            }
+            let user_group: Rc<str> = Rc::from(row[0].as_str());
+            if !names.insert(user_group.clone()) {
+                errors.push(format!("duplicate user group '{user_group}'"));
+                return None;
+            }
+            Some(UserGroupNameMapping {
+                user_group,
+                path: Rc::from(row[1].as_str()),
+                display_name: Rc::from(row[2].as_str()),
+                access_level: Rc::from(row[3].as_str()),
+                description: cell(row, description),
+                extends: cell(row, extends)
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(Rc::from)
+                    .collect(),
+            })
         })
-        .collect::<Vec<UserGroupNameMapping>>();
+        .collect()
+}
+
+fn validate_user_groups(
+    user_groups: &[UserGroupNameMapping],
+    access_levels: &[AccessLevelMapping],
+    errors: &mut Vec<String>,
+) {
+    let names: HashSet<&str> = user_groups.iter().map(|v| v.user_group.as_ref()).collect();
+    for user_group in user_groups {
+        if user_group.access_levels().next().is_none() {
+            errors.push(format!(
+                "user group '{}' has no access levels",
+                user_group.user_group
+            ));
+        }
+        for access_level in user_group.access_levels() {
+            let access_level = access_level_name(access_level);
+            let known = if access_levels.is_empty() {
+                KNOWN_ACCESS_LEVELS.contains(&access_level.as_str())
+            } else {
+                access_levels
+                    .iter()
+                    .any(|v| v.access_level.as_ref() == access_level)
+            };
+            if !known {
+                errors.push(format!(
+                    "user group '{}' has unknown access level '{access_level}'",
+                    user_group.user_group
+                ));
+            }
+        }
+        for parent in user_group.extends.iter() {
+            if !names.contains(parent.as_ref()) {
+                errors.push(format!(
+                    "user group '{}' extends unknown user group '{parent}'",
+                    user_group.user_group
+                ));
+            }
+        }
+    }
+}
+
+/// Appends the roles of the extended groups, parents are resolved before their children.
+fn resolve_extends(
+    user_group: &Rc<str>,
+    extends: &HashMap<Rc<str>, Rc<[Rc<str>]>>,
+    own_roles: &HashMap<Rc<str>, Vec<Rc<str>>>,
+    path: &mut Vec<Rc<str>>,
+    errors: &mut Vec<String>,
+) -> Vec<Rc<str>> {
+    if path.contains(user_group) {
+        errors.push(format!(
+            "user group '{user_group}' extends itself ({} -> {user_group})",
+            path.iter()
+                .map(|v| v.as_ref())
+                .collect::<Vec<&str>>()
+                .join(" -> ")
+        ));
+        return vec![];
+    }
+    path.push(user_group.clone());
+    let mut roles = own_roles.get(user_group).cloned().unwrap_or_default();
+    for parent in extends.get(user_group).iter().flat_map(|v| v.iter()) {
+        for role in resolve_extends(parent, extends, own_roles, path, errors) {
+            if !roles.contains(&role) {
+                roles.push(role);
+            }
+        }
+    }
+    path.pop();
+    roles
+}
+
+pub fn parse(tables: MdTables) -> anyhow::Result<ParseResult> {
+    let mut errors = vec![];
+    let access_levels = tables
+        .access_levels
+        .map(|table| parse_access_levels(table, &mut errors))
+        .unwrap_or_default();
+    let user_group_name_mappings = parse_user_groups(tables.user_groups, &mut errors);
+    validate_user_groups(&user_group_name_mappings, &access_levels, &mut errors);
+    let user_group_names: HashSet<Rc<str>> = user_group_name_mappings
+        .iter()
+        .map(|v| v.user_group.clone())
+        .collect();
 
     let role_mappings = tables.roles;
-    let role_mapping_headers: Rc<[Rc<str>]> = role_mappings
+    let description_column = role_mappings.column("description");
+    let role_mapping_headers: Vec<Option<Rc<str>>> = role_mappings
         .headers
-        .into_iter()
+        .iter()
+        .enumerate()
         .skip(1)
-        .map(Rc::from)
+        .map(|(idx, header)| {
+            if Some(idx) == description_column {
+                return None;
+            }
+            let user_group: Rc<str> = Rc::from(header.as_str());
+            if !user_group_names.contains(&user_group) {
+                errors.push(format!("unknown user group '{header}' in `roles` table"));
+            }
+            Some(user_group)
+        })
         .collect();
-    let role_mappings_map: HashMap<Rc<str>, Vec<Rc<str>>> =
-        role_mappings
-            .rows
-            .into_iter()
-            .fold(HashMap::default(), |mut state, mut row| {
-                if !row.is_empty() {
-                    let role: Rc<str> = Rc::from(row.remove(0));
-                    for (idx, col) in row.into_iter().enumerate() {
-                        if let Some(user_group) = role_mapping_headers.get(idx) {
-                            if col.trim() == "x" {
-                                state
-                                    .entry(user_group.clone())
-                                    .or_default()
-                                    .push(role.clone())
-                            }
-                        }
-                    }
+    let mut role_descriptions = BTreeMap::new();
+    let mut role_names = HashSet::new();
+    let mut role_mappings_map: HashMap<Rc<str>, Vec<Rc<str>>> = HashMap::default();
+    for row in role_mappings.rows.iter().filter(|row| !row.is_empty()) {
+        let role: Rc<str> = Rc::from(row[0].as_str());
+        if !role_names.insert(role.clone()) {
+            errors.push(format!("duplicate role '{role}' in `roles` table"));
+            continue;
+        }
+        let description = cell(row, description_column);
+        if !description.is_empty() {
+            role_descriptions.insert(role.clone(), description);
+        }
+        for (col, user_group) in row.iter().skip(1).zip(role_mapping_headers.iter()) {
+            if let Some(user_group) = user_group {
+                if col.trim() == "x" {
+                    role_mappings_map
+                        .entry(user_group.clone())
+                        .or_default()
+                        .push(role.clone());
                 }
-                state
-            });
-    let mut role_mappings: Vec<RoleMapping> = role_mappings_map
-        .into_iter()
-        .map(|(user_group, roles)| RoleMapping {
-            user_group,
-            roles: Rc::from(roles),
+            }
+        }
+    }
+
+    let extends: HashMap<Rc<str>, Rc<[Rc<str>]>> = user_group_name_mappings
+        .iter()
+        .map(|v| (v.user_group.clone(), v.extends.clone()))
+        .collect();
+    let mut role_mappings: Vec<RoleMapping> = user_group_name_mappings
+        .iter()
+        .filter_map(|v| {
+            let roles = resolve_extends(
+                &v.user_group,
+                &extends,
+                &role_mappings_map,
+                &mut vec![],
+                &mut errors,
+            );
+            (!roles.is_empty()).then(|| RoleMapping {
+                user_group: v.user_group.clone(),
+                roles: Rc::from(roles),
+            })
         })
         .collect();
     role_mappings.sort_by_key(|v| v.user_group.clone());
-    Ok(ParseResult::new(user_group_name_mappings, role_mappings))
+
+    if !errors.is_empty() {
+        errors.dedup();
+        anyhow::bail!("invalid role definition:\n  {}", errors.join("\n  "));
+    }
+    Ok(ParseResult::new(
+        access_levels,
+        user_group_name_mappings,
+        role_mappings,
+        role_descriptions,
+    ))
 }
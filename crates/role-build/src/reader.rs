@@ -27,6 +27,7 @@ impl Reader<std::io::BufReader<std::fs::File>> {
 
 #[derive(Debug, Clone, Copy)]
 enum CurrentTable {
+    AccessLevels,
     UserGroups,
     Roles,
     None,
@@ -41,6 +42,9 @@ fn set_table(rows: &mut Vec<Vec<String>>, tables: &mut OptMdTables, current_tabl
         rows: table_rows,
     };
     match current_table {
+        CurrentTable::AccessLevels => {
+            tables.access_levels = Some(table);
+        }
         CurrentTable::UserGroups => {
             tables.user_groups = Some(table);
         }
@@ -79,13 +83,16 @@ where
                 row.pop();
                 let is_divider = row
                     .iter()
-                    .all(|s| s.contains('-') && s.replace('-', "") == "");
+                    .all(|s| s.contains('-') && s.replace('-', "").is_empty());
                 if !is_divider {
                     rows.push(row);
                 }
             } else if !rows.is_empty() {
                 set_table(&mut rows, &mut tables, &current_table);
             } else {
+                if line.contains("`access_levels`") {
+                    current_table = CurrentTable::AccessLevels;
+                }
                 if line.contains("`user_groups`") {
                     current_table = CurrentTable::UserGroups;
                 }
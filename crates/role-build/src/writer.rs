@@ -3,7 +3,9 @@ use std::{
     path::Path,
 };
 
+use crate::model::{AccessLevelMapping, RoleMapping, UserGroupNameMapping};
 use crate::parser::ParseResult;
+use std::rc::Rc;
 
 pub struct WriteResult<W> {
     _w: W,
@@ -60,18 +62,26 @@ where
     }
 
     pub fn write(mut self, parse_result: ParseResult) -> anyhow::Result<WriteResult<W>> {
+        let access_levels = parse_result.access_level_definitions();
         let ParseResult {
             permissions,
             resources,
             role_mappings,
-            user_group_name_mappings,
+            role_descriptions,
+            roles,
+            user_group_name_mappings: user_groups,
             ..
         } = parse_result;
-        let user_group_name_mappings = BTreeMap::from_iter(
-            user_group_name_mappings
-                .into_iter()
-                .map(|v| (v.user_group, (v.path, v.display_name, v.access_level))),
-        );
+        let user_group_name_mappings = BTreeMap::from_iter(user_groups.iter().map(|v| {
+            (
+                v.user_group.clone(),
+                (
+                    v.path.clone(),
+                    v.display_name.clone(),
+                    v.access_level.clone(),
+                ),
+            )
+        }));
         self.write_line(0, "use strum::{EnumString, EnumIter, AsRefStr};")?;
         self.write_line(0, "use qm::role::AccessLevel;")?;
         self.write_line(0, "")?;
@@ -206,6 +216,15 @@ where
         self.write_line(0, ENUM_DERIVE_BUILT_IN_GROUP)?;
         self.write_line(0, "pub enum BuiltInGroup {")?;
         for group_name in group_names.iter() {
+            // Doc comments are used as descriptions of the GraphQL enum values.
+            if let Some(description) = user_groups
+                .iter()
+                .find(|v| &&v.path == group_name)
+                .map(|v| v.description.as_ref())
+                .filter(|v| !v.is_empty())
+            {
+                self.write_line(1, &format!("/// {description}"))?;
+            }
             self.write_line(
                 1,
                 &format!(
@@ -239,6 +258,159 @@ where
         self.write_line(2, "}")?;
         self.write_line(1, "}")?;
         self.write_line(0, "}")?;
+        self.write_definitions(
+            &access_levels,
+            &user_groups,
+            &role_mappings,
+            &roles,
+            &role_descriptions,
+        )?;
         Ok(WriteResult { _w: self.w })
     }
+
+    fn write_definitions(
+        &mut self,
+        access_levels: &[AccessLevelMapping],
+        user_groups: &[UserGroupNameMapping],
+        role_mappings: &[RoleMapping],
+        roles: &[Rc<str>],
+        role_descriptions: &BTreeMap<Rc<str>, Rc<str>>,
+    ) -> anyhow::Result<()> {
+        let access_level = |s: &str| {
+            format!(
+                "AccessLevel::{}",
+                inflector::cases::classcase::to_class_case(s.trim())
+            )
+        };
+        self.write_line(0, "")?;
+        self.write_line(0, "#[derive(Clone, Copy, Debug)]")?;
+        self.write_line(0, "pub struct AccessLevelDefinition {")?;
+        self.write_line(1, "pub access_level: AccessLevel,")?;
+        self.write_line(1, "pub display_name: &'static str,")?;
+        self.write_line(1, "pub description: &'static str,")?;
+        self.write_line(0, "}")?;
+        self.write_line(0, "")?;
+        self.write_line(
+            0,
+            &format!(
+                "pub const ACCESS_LEVELS: [AccessLevelDefinition; {}] = [",
+                access_levels.len()
+            ),
+        )?;
+        for v in access_levels.iter() {
+            self.write_line(
+                1,
+                &format!(
+                    "AccessLevelDefinition {{ access_level: {}, display_name: {:?}, description: {:?} }},",
+                    access_level(&v.access_level),
+                    v.display_name.as_ref(),
+                    v.description.as_ref(),
+                ),
+            )?;
+        }
+        self.write_line(0, "];")?;
+        self.write_line(0, "")?;
+        self.write_line(0, "#[derive(Clone, Copy, Debug)]")?;
+        self.write_line(0, "pub struct GroupDefinition {")?;
+        self.write_line(1, "pub name: &'static str,")?;
+        self.write_line(1, "pub path: &'static str,")?;
+        self.write_line(1, "pub display_name: &'static str,")?;
+        self.write_line(1, "pub description: &'static str,")?;
+        self.write_line(1, "pub extends: &'static [&'static str],")?;
+        self.write_line(1, "pub access_levels: &'static [AccessLevel],")?;
+        self.write_line(
+            1,
+            "/// Roles of the group including the roles of the extended groups.",
+        )?;
+        self.write_line(1, "pub roles: &'static [&'static str],")?;
+        self.write_line(0, "}")?;
+        self.write_line(0, "")?;
+        self.write_line(
+            0,
+            &format!(
+                "pub const GROUPS: [GroupDefinition; {}] = [",
+                user_groups.len()
+            ),
+        )?;
+        for user_group in user_groups.iter() {
+            let roles = role_mappings
+                .iter()
+                .find(|v| v.user_group == user_group.user_group)
+                .map(|v| v.roles.as_ref())
+                .unwrap_or_default();
+            self.write_line(1, "GroupDefinition {")?;
+            self.write_line(2, &format!("name: {:?},", user_group.user_group.as_ref()))?;
+            self.write_line(2, &format!("path: \"/app{}\",", user_group.path))?;
+            self.write_line(
+                2,
+                &format!("display_name: {:?},", user_group.display_name.as_ref()),
+            )?;
+            self.write_line(
+                2,
+                &format!("description: {:?},", user_group.description.as_ref()),
+            )?;
+            self.write_line(
+                2,
+                &format!(
+                    "extends: &[{}],",
+                    user_group
+                        .extends
+                        .iter()
+                        .map(|v| format!("{:?}", v.as_ref()))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            )?;
+            self.write_line(
+                2,
+                &format!(
+                    "access_levels: &[{}],",
+                    user_group
+                        .access_levels()
+                        .map(access_level)
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            )?;
+            self.write_line(
+                2,
+                &format!(
+                    "roles: &[{}],",
+                    roles
+                        .iter()
+                        .map(|v| format!("{:?}", v.as_ref()))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            )?;
+            self.write_line(1, "},")?;
+        }
+        self.write_line(0, "];")?;
+        self.write_line(0, "")?;
+        self.write_line(0, "#[derive(Clone, Copy, Debug)]")?;
+        self.write_line(0, "pub struct RoleDefinition {")?;
+        self.write_line(1, "pub name: &'static str,")?;
+        self.write_line(1, "pub description: &'static str,")?;
+        self.write_line(0, "}")?;
+        self.write_line(0, "")?;
+        self.write_line(
+            0,
+            &format!("pub const ROLES: [RoleDefinition; {}] = [", roles.len()),
+        )?;
+        for role in roles.iter() {
+            let description = role_descriptions
+                .get(role)
+                .map(|v| v.as_ref())
+                .unwrap_or_default();
+            self.write_line(
+                1,
+                &format!(
+                    "RoleDefinition {{ name: {:?}, description: {description:?} }},",
+                    role.as_ref()
+                ),
+            )?;
+        }
+        self.write_line(0, "];")?;
+        Ok(())
+    }
 }
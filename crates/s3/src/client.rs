@@ -0,0 +1,197 @@
+use s3::creds::Credentials;
+use s3::serde_types::Object;
+use s3::{Bucket, BucketConfiguration, Region};
+use std::sync::Arc;
+
+use crate::config::Config as S3Config;
+
+struct Inner {
+    config: S3Config,
+    region: Region,
+    credentials: Credentials,
+}
+
+#[derive(Clone)]
+pub struct S3 {
+    inner: Arc<Inner>,
+}
+
+impl S3 {
+    pub fn new() -> anyhow::Result<Self> {
+        Self::new_with_config(S3Config::new()?)
+    }
+
+    pub fn new_with_config(config: S3Config) -> anyhow::Result<Self> {
+        let region = Region::Custom {
+            region: config.region().to_string(),
+            endpoint: config.address().to_string(),
+        };
+        let credentials =
+            Credentials::new(config.access_key(), config.secret_key(), None, None, None)?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                config,
+                region,
+                credentials,
+            }),
+        })
+    }
+
+    pub fn config(&self) -> &S3Config {
+        &self.inner.config
+    }
+
+    pub fn bucket(&self, name: &str) -> anyhow::Result<Bucket> {
+        let bucket = Bucket::new(
+            name,
+            self.inner.region.clone(),
+            self.inner.credentials.clone(),
+        )?;
+        if self.inner.config.path_style() {
+            Ok(bucket.with_path_style())
+        } else {
+            Ok(bucket)
+        }
+    }
+
+    pub fn default_bucket(&self) -> anyhow::Result<Bucket> {
+        self.bucket(self.inner.config.bucket())
+    }
+
+    pub async fn list_buckets(&self) -> anyhow::Result<Vec<String>> {
+        let response =
+            Bucket::list_buckets(self.inner.region.clone(), self.inner.credentials.clone()).await?;
+        Ok(response.bucket_names().collect())
+    }
+
+    pub async fn create_bucket(&self, name: &str) -> anyhow::Result<Bucket> {
+        log::info!("create s3 bucket '{name}'");
+        let response = if self.inner.config.path_style() {
+            Bucket::create_with_path_style(
+                name,
+                self.inner.region.clone(),
+                self.inner.credentials.clone(),
+                BucketConfiguration::default(),
+            )
+            .await?
+        } else {
+            Bucket::create(
+                name,
+                self.inner.region.clone(),
+                self.inner.credentials.clone(),
+                BucketConfiguration::default(),
+            )
+            .await?
+        };
+        if !response.success() {
+            anyhow::bail!(
+                "unable to create bucket '{name}': {} {}",
+                response.response_code,
+                response.response_text
+            );
+        }
+        Ok(response.bucket)
+    }
+
+    pub async fn ensure_buckets(&self, names: &[&str]) -> anyhow::Result<()> {
+        let buckets = self.list_buckets().await?;
+        for name in names {
+            if !buckets.iter().any(|b| b == name) {
+                self.create_bucket(name).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn ensure_default_bucket(&self) -> anyhow::Result<()> {
+        self.ensure_buckets(&[self.inner.config.bucket()]).await
+    }
+
+    pub async fn remove_bucket(&self, name: &str) -> anyhow::Result<()> {
+        log::info!("remove s3 bucket '{name}'");
+        self.clear_bucket(name).await?;
+        self.bucket(name)?.delete().await?;
+        Ok(())
+    }
+
+    pub async fn clear_bucket(&self, name: &str) -> anyhow::Result<usize> {
+        let bucket = self.bucket(name)?;
+        let mut count = 0;
+        for object in self.list_objects(name, "").await? {
+            bucket.delete_object(&object.key).await?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub async fn put_object(
+        &self,
+        bucket: &str,
+        path: &str,
+        content: &[u8],
+        content_type: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let bucket = self.bucket(bucket)?;
+        if let Some(content_type) = content_type {
+            bucket
+                .put_object_with_content_type(path, content, content_type)
+                .await?;
+        } else {
+            bucket.put_object(path, content).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_object(&self, bucket: &str, path: &str) -> anyhow::Result<Vec<u8>> {
+        let response = self.bucket(bucket)?.get_object(path).await?;
+        Ok(response.to_vec())
+    }
+
+    pub async fn exists(&self, bucket: &str, path: &str) -> anyhow::Result<bool> {
+        let list = self.bucket(bucket)?.list(path.to_string(), None).await?;
+        Ok(list
+            .iter()
+            .flat_map(|r| r.contents.iter())
+            .any(|o| o.key == path))
+    }
+
+    pub async fn delete_object(&self, bucket: &str, path: &str) -> anyhow::Result<()> {
+        self.bucket(bucket)?.delete_object(path).await?;
+        Ok(())
+    }
+
+    pub async fn list_objects(&self, bucket: &str, prefix: &str) -> anyhow::Result<Vec<Object>> {
+        Ok(self
+            .bucket(bucket)?
+            .list(prefix.to_string(), None)
+            .await?
+            .into_iter()
+            .flat_map(|r| r.contents)
+            .collect())
+    }
+
+    pub async fn presign_get(&self, bucket: &str, path: &str) -> anyhow::Result<String> {
+        Ok(self
+            .bucket(bucket)?
+            .presign_get(path, self.inner.config.presign_expiry(), None)
+            .await?)
+    }
+
+    pub async fn presign_put(&self, bucket: &str, path: &str) -> anyhow::Result<String> {
+        Ok(self
+            .bucket(bucket)?
+            .presign_put(path, self.inner.config.presign_expiry(), None)
+            .await?)
+    }
+
+    /// Removes all objects of the configured bucket, other buckets reachable
+    /// with the same credentials are not touched.
+    pub async fn cleanup(&self) -> anyhow::Result<()> {
+        let bucket = self.inner.config.bucket();
+        if self.list_buckets().await?.iter().any(|b| b == bucket) {
+            let count = self.clear_bucket(bucket).await?;
+            log::debug!("removed {count} objects from s3 bucket '{bucket}'");
+        }
+        Ok(())
+    }
+}
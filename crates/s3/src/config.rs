@@ -0,0 +1,140 @@
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct Config {
+    host: Option<Arc<str>>,
+    port: Option<u16>,
+    region: Option<Arc<str>>,
+    access_key: Option<Arc<str>>,
+    secret_key: Option<Arc<str>>,
+    bucket: Option<Arc<str>>,
+    path_style: Option<bool>,
+    presign_expiry: Option<u32>,
+    #[serde(skip)]
+    address: Option<Arc<str>>,
+}
+
+impl Config {
+    pub fn new() -> envy::Result<Self> {
+        ConfigBuilder::default().build()
+    }
+
+    pub fn builder<'a>() -> ConfigBuilder<'a> {
+        ConfigBuilder::default()
+    }
+
+    pub fn address(&self) -> &str {
+        self.address.as_deref().unwrap()
+    }
+
+    pub fn region(&self) -> &str {
+        self.region.as_deref().unwrap_or("garage")
+    }
+
+    pub fn access_key(&self) -> Option<&str> {
+        self.access_key.as_deref()
+    }
+
+    pub fn secret_key(&self) -> Option<&str> {
+        self.secret_key.as_deref()
+    }
+
+    pub fn bucket(&self) -> &str {
+        self.bucket.as_deref().unwrap()
+    }
+
+    pub fn path_style(&self) -> bool {
+        self.path_style.unwrap_or(true)
+    }
+
+    pub fn presign_expiry(&self) -> u32 {
+        self.presign_expiry.unwrap_or(60 * 60)
+    }
+}
+
+#[derive(Default)]
+pub struct ConfigBuilder<'a> {
+    prefix: Option<&'a str>,
+}
+
+impl<'a> ConfigBuilder<'a> {
+    pub fn with_prefix(mut self, prefix: &'a str) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    pub fn build(self) -> envy::Result<Config> {
+        self.build_from_iter(std::env::vars())
+    }
+
+    /// Builds the config from `vars` instead of the process environment.
+    pub fn build_from_iter<Iter>(self, vars: Iter) -> envy::Result<Config>
+    where
+        Iter: IntoIterator<Item = (String, String)>,
+    {
+        let mut cfg: Config = envy::prefixed(self.prefix.unwrap_or("S3_")).from_iter(vars)?;
+
+        if cfg.bucket.is_none() {
+            cfg.bucket = Some(Arc::from("qm"));
+        }
+
+        let host = cfg.host.as_deref().unwrap_or("127.0.0.1");
+        let port = cfg.port.unwrap_or(3900);
+        cfg.address = Some(Arc::from(format!("http://{}:{}", host, port)));
+        Ok(cfg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_builtin_config_test() -> envy::Result<()> {
+        let cfg = super::Config::builder().build_from_iter(vec![])?;
+        assert_eq!(cfg.address(), "http://127.0.0.1:3900");
+        assert_eq!(cfg.region(), "garage");
+        assert_eq!(cfg.bucket(), "qm");
+        assert!(cfg.path_style());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_default_config_test() -> envy::Result<()> {
+        let cfg = super::Config::builder().build_from_iter(vars(&[
+            ("S3_HOST", "localhost"),
+            ("S3_PORT", "3900"),
+            ("S3_REGION", "garage"),
+            ("S3_ACCESS_KEY", "GK0123456789"),
+            ("S3_SECRET_KEY", "secret"),
+            ("S3_BUCKET", "uploads"),
+        ]))?;
+        assert_eq!(cfg.address(), "http://localhost:3900");
+        assert_eq!(cfg.access_key(), Some("GK0123456789"));
+        assert_eq!(cfg.secret_key(), Some("secret"));
+        assert_eq!(cfg.bucket(), "uploads");
+        Ok(())
+    }
+
+    #[test]
+    fn parse_prefixed_config_test() -> envy::Result<()> {
+        let cfg = super::Config::builder()
+            .with_prefix("S3_FILES_")
+            .build_from_iter(vars(&[
+                ("S3_HOST", "ignored"),
+                ("S3_FILES_HOST", "localhost"),
+                ("S3_FILES_PORT", "9000"),
+                ("S3_FILES_REGION", "eu-central-1"),
+                ("S3_FILES_PATH_STYLE", "false"),
+            ]))?;
+        assert_eq!(cfg.address(), "http://localhost:9000");
+        assert_eq!(cfg.region(), "eu-central-1");
+        assert!(!cfg.path_style());
+        Ok(())
+    }
+}
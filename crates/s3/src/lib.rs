@@ -1,14 +1,18 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
-}
+pub use s3::*;
+
+mod client;
+mod config;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub use crate::client::S3;
+pub use crate::config::Config as S3Config;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
+#[macro_export]
+macro_rules! s3 {
+    ($storage:ty) => {
+        impl AsRef<qm::s3::S3> for $storage {
+            fn as_ref(&self) -> &qm::s3::S3 {
+                &self.inner.s3
+            }
+        }
+    };
 }
@@ -1,8 +1,84 @@
+use proc_macro2::{Span, TokenStream};
+use proc_macro_crate::{crate_name, FoundCrate};
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields};
+
+fn utils_crate() -> syn::Result<TokenStream> {
+    match crate_name("qm-utils") {
+        Ok(FoundCrate::Itself) => Ok(quote!(crate)),
+        Ok(FoundCrate::Name(name)) => {
+            let name = format_ident!("{name}");
+            Ok(quote!(::#name))
+        }
+        Err(_) => match crate_name("qm") {
+            Ok(FoundCrate::Itself) => Ok(quote!(crate::utils)),
+            Ok(FoundCrate::Name(name)) => {
+                let name = format_ident!("{name}");
+                Ok(quote!(::#name::utils))
+            }
+            Err(err) => Err(syn::Error::new(Span::call_site(), err)),
+        },
+    }
+}
+
+fn fields(data: &Data) -> syn::Result<Vec<&syn::Field>> {
+    match data {
+        Data::Struct(data) => Ok(data.fields.iter().collect()),
+        Data::Enum(data) => Ok(data
+            .variants
+            .iter()
+            .flat_map(|variant| match &variant.fields {
+                Fields::Named(fields) => fields.named.iter().collect(),
+                Fields::Unnamed(fields) => fields.unnamed.iter().collect(),
+                Fields::Unit => vec![],
+            })
+            .collect()),
+        Data::Union(data) => Err(syn::Error::new_spanned(
+            data.union_token,
+            "CheapClone can not be derived for unions",
+        )),
+    }
+}
+
+fn expand_derive_cheap_clone(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let krate = utils_crate()?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let assertions = fields(&input.data)?
+        .into_iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let ty = &field.ty;
+            // the function name shows up in the error message of a field which is not cheap to clone
+            let assert_fn = match &field.ident {
+                Some(ident) => format_ident!("field_{}_must_be_arc_rc_or_cheap_clone", ident),
+                None => format_ident!("field_{}_must_be_arc_rc_or_cheap_clone", idx),
+            };
+            quote_spanned! {ty.span()=>
+                {
+                    fn #assert_fn<T: #krate::CheapClone>() {}
+                    #assert_fn::<#ty>();
+                }
+            }
+        });
+    Ok(quote! {
+        impl #impl_generics #krate::CheapClone for #name #ty_generics #where_clause {}
+
+        const _: () = {
+            impl #impl_generics #name #ty_generics #where_clause {
+                #[allow(dead_code)]
+                fn __assert_cheap_clone_fields() {
+                    #(#assertions)*
+                }
+            }
+        };
+    })
+}
+
 pub fn expand(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    // Parse the string representation
-    // let mut input = syn::parse_macro_input!(input as syn::DeriveInput);
-    // expand_derive_rcobj(&mut input)
-    //     .unwrap_or_else(syn::Error::into_compile_error)
-    //     .into()
-    input
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand_derive_cheap_clone(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
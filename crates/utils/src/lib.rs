@@ -1 +1,73 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
 pub use qm_utils_derive::CheapClone;
+
+/// Marker for types where `clone` is cheap, e.g. only increments a reference count.
+///
+/// Derive it with `#[derive(Clone, CheapClone)]`, which only compiles if every field is an
+/// `Arc`, `Rc` or another `CheapClone` type.
+pub trait CheapClone: Clone {
+    #[inline]
+    fn cheap_clone(&self) -> Self {
+        self.clone()
+    }
+}
+
+impl<T: ?Sized> CheapClone for Arc<T> {}
+impl<T: ?Sized> CheapClone for Rc<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::CheapClone;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[derive(Clone, CheapClone)]
+    struct Inner {
+        name: Arc<str>,
+    }
+
+    #[derive(Clone, CheapClone)]
+    struct Storage {
+        inner: Arc<Vec<u8>>,
+        local: Rc<String>,
+        nested: Inner,
+    }
+
+    #[derive(Clone, CheapClone)]
+    struct Generic<T: CheapClone>(T, Arc<str>);
+
+    #[derive(Clone, CheapClone)]
+    enum Either {
+        Left(Arc<str>),
+        Right { value: Rc<str> },
+    }
+
+    #[test]
+    fn cheap_clone_test() {
+        let storage = Storage {
+            inner: Arc::new(vec![1, 2, 3]),
+            local: Rc::new("local".to_string()),
+            nested: Inner {
+                name: Arc::from("nested"),
+            },
+        };
+        let cloned = storage.cheap_clone();
+        assert!(Arc::ptr_eq(&storage.inner, &cloned.inner));
+        assert!(Rc::ptr_eq(&storage.local, &cloned.local));
+        assert!(Arc::ptr_eq(&storage.nested.name, &cloned.nested.name));
+        let generic = Generic(storage.nested.cheap_clone(), Arc::from("generic"));
+        let cloned = generic.cheap_clone();
+        assert!(Arc::ptr_eq(&cloned.0.name, &generic.0.name));
+        assert!(Arc::ptr_eq(&cloned.1, &generic.1));
+        let either = Either::Left(Arc::from("left"));
+        assert!(matches!(either.cheap_clone(), Either::Left(s) if s.as_ref() == "left"));
+        let either = Either::Right {
+            value: Rc::from("right"),
+        };
+        assert!(
+            matches!(either.cheap_clone(), Either::Right { value } if value.as_ref() == "right")
+        );
+    }
+}
@@ -0,0 +1,11 @@
+use std::sync::Arc;
+
+use qm_utils::CheapClone;
+
+#[derive(Clone, CheapClone)]
+struct Settings {
+    name: Arc<str>,
+    values: Vec<String>,
+}
+
+fn main() {}
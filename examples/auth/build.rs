@@ -1,6 +1,8 @@
 use std::path::Path;
 
 fn main() -> anyhow::Result<()> {
-    qm_role_build::generate(Path::new("./templates/roles.md"))?;
+    let input = Path::new("./templates/roles.md");
+    println!("cargo:rerun-if-changed={}", input.display());
+    qm_role_build::generate(input)?;
     Ok(())
 }
@@ -1 +1,33 @@
 qm::role::include_roles!("roles");
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use qm_role_build::ExportFormat;
+
+    const INPUT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/templates/roles.md");
+    const EXPORT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/templates/exports");
+
+    /// The exports are committed, so frontends and the Keycloak import can use them and changes
+    /// to the role matrix show up in reviews. `UPDATE_ROLE_EXPORTS=1 cargo test` updates them.
+    #[test]
+    fn role_exports_test() -> anyhow::Result<()> {
+        let input = Path::new(INPUT);
+        let export_dir = Path::new(EXPORT_DIR);
+        for format in [ExportFormat::Json, ExportFormat::MatrixMarkdown] {
+            if std::env::var_os("UPDATE_ROLE_EXPORTS").is_some() {
+                qm_role_build::export_to_dir(input, format, export_dir)?;
+            }
+            let mut expected = vec![];
+            qm_role_build::export_to_writer(input, format, &mut expected)?;
+            let path = export_dir.join(format!("roles.{}", format.extension()));
+            assert!(
+                std::fs::read(&path)? == expected,
+                "{} is outdated, update it with `UPDATE_ROLE_EXPORTS=1 cargo test`",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
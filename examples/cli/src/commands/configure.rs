@@ -5,16 +5,26 @@
 use crate::commands::ConfigureCommand;
 use std::collections::BTreeSet;
 
-async fn configure_keycloak() -> anyhow::Result<()> {
+async fn configure_keycloak(dry_run: bool) -> anyhow::Result<()> {
     let keycloak = qm::keycloak::Keycloak::builder()
         .with_no_refresh()
         .build()
         .await?;
     let realms = keycloak.realms().await?;
     if !realms.contains(&keycloak.config().realm().into()) {
+        if dry_run {
+            println!("realm '{}' will be created", keycloak.config().realm());
+            return Ok(());
+        }
         qm::keycloak::realm::create(&keycloak).await?;
     }
-    qm::keycloak::realm::configure_realm(&keycloak, qm_example_auth::roles::groups()).await?;
+    let plan =
+        qm::keycloak::realm::reconcile_realm(&keycloak, &qm_example_auth::roles::groups(), dry_run)
+            .await?;
+    if dry_run {
+        println!("{plan}");
+        return Ok(());
+    }
     let keycloak_config = keycloak.config();
     qm::keycloak::realm::ensure_admin_user(
         keycloak_config.realm(),
@@ -33,14 +43,23 @@ async fn configure_keycloak() -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn configure_s3() -> anyhow::Result<()> {
+    let s3 = qm::s3::S3::new()?;
+    s3.ensure_default_bucket().await?;
+    Ok(())
+}
+
 impl ConfigureCommand {
     pub async fn run(self) -> anyhow::Result<()> {
         match self.resource {
             super::Resource::All => {
-                configure_keycloak().await?;
+                configure_keycloak(self.dry_run).await?;
             }
             super::Resource::KeycloakRealm => {
-                configure_keycloak().await?;
+                configure_keycloak(self.dry_run).await?;
+            }
+            super::Resource::S3 => {
+                configure_s3().await?;
             }
             _ => {
                 unimplemented!()
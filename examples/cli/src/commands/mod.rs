@@ -2,6 +2,7 @@ use clap::Parser;
 
 mod configure;
 mod remove;
+mod validate;
 
 #[derive(Clone, Parser)]
 pub enum Resource {
@@ -23,16 +24,31 @@ pub struct RemoveCommand {
 pub struct ConfigureCommand {
     #[clap(long)]
     pub reset: bool,
+    /// Prints the changes to the Keycloak realm without applying them
+    #[clap(long)]
+    pub dry_run: bool,
     #[clap(subcommand)]
     pub resource: Resource,
 }
 
+#[derive(Parser)]
+pub struct ValidateCommand {
+    /// Fixes the errors of the Keycloak realm and validates it again
+    #[clap(long)]
+    pub fix: bool,
+    /// Id of an error to fix, all fixable errors are fixed if none is given
+    #[clap(long)]
+    pub id: Vec<String>,
+}
+
 #[derive(Parser)]
 pub enum SubCommand {
     /// remove
     Remove(RemoveCommand),
     /// configure
     Configure(ConfigureCommand),
+    /// validate the keycloak realm
+    Validate(ValidateCommand),
 }
 
 #[derive(Parser)]
@@ -0,0 +1,26 @@
+//! # validate command
+//!
+//! This command validates the Keycloak realm and optionally fixes the errors.
+//!
+use crate::commands::ValidateCommand;
+use qm::keycloak::validation::report::Fix;
+
+impl ValidateCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let keycloak = qm::keycloak::Keycloak::builder()
+            .with_no_refresh()
+            .build()
+            .await?;
+        let fix = match (self.fix, self.id.is_empty()) {
+            (false, _) => Fix::None,
+            (true, true) => Fix::All,
+            (true, false) => Fix::Only(self.id),
+        };
+        let report = qm::keycloak::realm::validate_realm_report(&keycloak, fix).await?;
+        println!("{report}");
+        if !report.is_valid() {
+            anyhow::bail!("realm validation failed");
+        }
+        Ok(())
+    }
+}
@@ -9,6 +9,7 @@ use qm::{
     mongodb::DB,
     redis::Redis,
     server::ServerConfig,
+    utils::CheapClone,
 };
 use std::sync::Arc;
 
@@ -26,7 +27,7 @@ struct Inner {
     cleanup_task_producer: CleanupProducer,
 }
 
-#[derive(Clone)]
+#[derive(Clone, CheapClone)]
 pub struct Storage {
     inner: Arc<Inner>,
 }
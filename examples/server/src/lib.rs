@@ -1,3 +1,5 @@
+#![recursion_limit = "256"]
+
 use async_graphql::http::GraphiQLSource;
 use axum::{
     extract::Extension,
@@ -10,6 +12,8 @@ use tower_http::cors::{AllowOrigin, CorsLayer};
 
 pub mod schema;
 
+use qm::customer::context::{CustomerDB, MutationEventProducer};
+use qm::customer::outbox::OutboxRelay;
 use qm_example_ctx::Storage;
 
 const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
@@ -58,8 +62,16 @@ async fn router(store: Storage) -> Router {
 pub async fn start() -> anyhow::Result<()> {
     let store = Storage::new().await?;
     let address = store.server_config().address().to_string();
+    let relay = store.mutation_event_producer().map(|producer| {
+        OutboxRelay::builder(store.customer_db().pool().clone(), producer.clone())
+            .build()
+            .start()
+    });
     let router = router(store).await;
     let listener = tokio::net::TcpListener::bind(address).await.unwrap();
     axum::serve(listener, router).await.unwrap();
+    if let Some(relay) = relay {
+        relay.terminate().await?;
+    }
     Ok(())
 }